@@ -9,14 +9,14 @@ use lain::rand::Rng;
 // your fuzzer's threads
 use lain::driver::*;
 
-use std::io::prelude::*;
-use std::net::TcpStream;
+use lain::transport::{TcpTransport, Transport, TransportConfig};
 use std::sync::{Arc, RwLock};
 
 const THREAD_COUNT: usize = 10;
 
 #[derive(Default)]
 struct FuzzerThreadContext {
+    transport: Option<TcpTransport>,
     last_packet: Option<PacketData>,
     scratch_packet: PacketData,
     thread_packet_iterations: usize,
@@ -79,8 +79,10 @@ fn main() {
 }
 
 fn fuzzer_routine<R: Rng>(mutator: &mut Mutator<R>, thread_context: &mut FuzzerThreadContext, _global_context: Option<Arc<RwLock<GlobalContext>>>) -> Result<(), ()> {
-    // TODO: we have overhead here of re-estabilishing the connection every time
-    let mut stream = TcpStream::connect("127.0.0.1:8080").expect("server isn't running. possible crash?");
+    // the connection is kept open between iterations and re-established if the server drops it
+    let transport = thread_context.transport.get_or_insert_with(|| {
+        TcpTransport::new("127.0.0.1:8080", TransportConfig::new()).expect("invalid address")
+    });
 
     let packet = match thread_context.last_packet {
         Some(ref mut last_packet) => {
@@ -95,17 +97,13 @@ fn fuzzer_routine<R: Rng>(mutator: &mut Mutator<R>, thread_context: &mut FuzzerT
         }
     };
 
-    let mut serialized_data = Vec::with_capacity(packet.serialized_size());
-    packet.binary_serialize::<_, LittleEndian>(&mut serialized_data);
-
     println!("Sending packet: {:?}", packet);
 
-    stream.write(&serialized_data).expect("failed to write data");
-
-    let mut response_data = Vec::new();
-    stream.read(&mut response_data);
+    let result = transport.exchange_object::<_, LittleEndian>(packet);
+    println!("Received response: {:?}", result.response);
 
     thread_context.thread_packet_iterations += 1;
 
-    Ok(())
+    // the server going away after receiving a packet is a possible crash
+    result.as_iteration_result()
 }
@@ -0,0 +1,94 @@
+//! Bit-granular serialization. This backs the `#[lain(bit_order = "msb")]` container attribute of
+//! the `BinarySerialize` derive, where consecutive `#[lain(bits = N)]` fields are packed into a
+//! single stream of bits that may cross byte boundaries.
+
+use std::io::Write;
+
+#[cfg(feature = "serde_support")]
+use serde::{Deserialize, Serialize};
+
+/// The order in which bits are packed into each byte of a [BitWriter]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub enum BitOrder {
+    /// The first bit written is the most significant bit of the first byte, and values are
+    /// written starting with their most significant bit. This is used by most network protocols
+    /// and media formats (e.g. H.264).
+    MsbFirst,
+    /// The first bit written is the least significant bit of the first byte, and values are
+    /// written starting with their least significant bit (e.g. DEFLATE).
+    LsbFirst,
+}
+
+/// Accumulates values of arbitrary bit widths into bytes
+#[derive(Debug, Clone)]
+pub struct BitWriter {
+    order: BitOrder,
+    bytes: Vec<u8>,
+    bit_len: usize,
+}
+
+impl BitWriter {
+    pub fn new(order: BitOrder) -> Self {
+        BitWriter {
+            order,
+            bytes: Vec::new(),
+            bit_len: 0,
+        }
+    }
+
+    /// Appends the low `bits` bits of `value`. Any higher bits are ignored.
+    pub fn write_bits(&mut self, value: u128, bits: usize) {
+        assert!(bits <= 128, "cannot write more than 128 bits at once");
+
+        for i in 0..bits {
+            let bit_idx = match self.order {
+                BitOrder::MsbFirst => bits - 1 - i,
+                BitOrder::LsbFirst => i,
+            };
+
+            self.push_bit((value >> bit_idx) & 1 == 1);
+        }
+    }
+
+    /// Appends `bits` zero bits
+    pub fn pad(&mut self, bits: usize) {
+        for _ in 0..bits {
+            self.push_bit(false);
+        }
+    }
+
+    /// The number of bits written so far
+    pub fn bit_len(&self) -> usize {
+        self.bit_len
+    }
+
+    /// Returns the packed bytes. The final byte is padded with zero bits.
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    /// Writes the packed bytes to `buffer` and returns the number of bytes written. The final byte
+    /// is padded with zero bits.
+    pub fn finish<W: Write>(self, buffer: &mut W) -> usize {
+        buffer.write(&self.bytes).unwrap()
+    }
+
+    fn push_bit(&mut self, set: bool) {
+        let offset = self.bit_len % 8;
+        if offset == 0 {
+            self.bytes.push(0);
+        }
+
+        if set {
+            let shift = match self.order {
+                BitOrder::MsbFirst => 7 - offset,
+                BitOrder::LsbFirst => offset,
+            };
+
+            *self.bytes.last_mut().unwrap() |= 1 << shift;
+        }
+
+        self.bit_len += 1;
+    }
+}
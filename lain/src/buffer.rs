@@ -1,7 +1,11 @@
+use crate::encoding::{EncodedSequence, Encoding, LengthPrefix};
 use crate::traits::*;
-use crate::types::UnsafeEnum;
+use crate::types::{
+    AsciiString, LengthPrefixed, PaddingSeed, Sequence, TagOverride, UnsafeEnum, Utf8String, VarInt,
+};
 use byteorder::{ByteOrder, WriteBytesExt};
 use paste::paste;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 use std::io::Write;
 
 /// Default implementation of SerializedSize for slices of items. This runs in O(n) complexity since
@@ -36,46 +40,31 @@ where
     }
 }
 
-macro_rules! impl_serialized_size_array {
-    ( $($size:expr),* ) => {
-        $(
-            impl<T> SerializedSize for [T; $size]
-            where T: SerializedSize {
-                #[inline]
-                fn serialized_size(&self) -> usize {
-                    trace!("using default serialized_size for array");
-                    if $size == 0 {
-                        return 0;
-                    }
-
-                    let size = self
-                        .iter()
-                        .map(SerializedSize::serialized_size)
-                        .fold(0, |sum, i| sum + i);
+impl<T, const N: usize> SerializedSize for [T; N]
+where
+    T: SerializedSize,
+{
+    #[inline]
+    fn serialized_size(&self) -> usize {
+        trace!("using default serialized_size for array");
+        if N == 0 {
+            return 0;
+        }
 
-                    size
-                }
+        self.iter().map(SerializedSize::serialized_size).sum()
+    }
 
-                #[inline]
-                fn min_nonzero_elements_size() -> usize {
-                    T::min_nonzero_elements_size() * $size
-                }
+    #[inline]
+    fn min_nonzero_elements_size() -> usize {
+        T::min_nonzero_elements_size() * N
+    }
 
-                #[inline]
-                fn max_default_object_size() -> usize {
-                    T::max_default_object_size() * $size
-                }
-            }
-        )*
+    #[inline]
+    fn max_default_object_size() -> usize {
+        T::max_default_object_size() * N
     }
 }
 
-impl_serialized_size_array!(
-    0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25,
-    26, 27, 28, 29, 30, 31, 32, 33, 34, 35, 36, 37, 38, 39, 40, 41, 42, 43, 44, 45, 46, 47, 48, 49,
-    50, 51, 52, 53, 54, 55, 56, 57, 58, 59, 60
-);
-
 impl<T> SerializedSize for Vec<T>
 where
     T: SerializedSize,
@@ -106,6 +95,26 @@ where
     }
 }
 
+impl<T> SerializedSize for Sequence<T>
+where
+    T: SerializedSize,
+{
+    #[inline]
+    fn serialized_size(&self) -> usize {
+        self.messages.serialized_size()
+    }
+
+    #[inline]
+    fn min_nonzero_elements_size() -> usize {
+        T::min_nonzero_elements_size()
+    }
+
+    #[inline]
+    fn max_default_object_size() -> usize {
+        T::max_default_object_size()
+    }
+}
+
 impl SerializedSize for str {
     #[inline]
     fn serialized_size(&self) -> usize {
@@ -142,6 +151,144 @@ impl SerializedSize for String {
     }
 }
 
+impl SerializedSize for Utf8String {
+    #[inline]
+    fn serialized_size(&self) -> usize {
+        self.len()
+    }
+
+    #[inline]
+    fn min_nonzero_elements_size() -> usize {
+        1
+    }
+
+    #[inline]
+    fn max_default_object_size() -> usize {
+        1
+    }
+}
+
+impl SerializedSize for AsciiString {
+    #[inline]
+    fn serialized_size(&self) -> usize {
+        self.len()
+    }
+
+    #[inline]
+    fn min_nonzero_elements_size() -> usize {
+        1
+    }
+
+    #[inline]
+    fn max_default_object_size() -> usize {
+        1
+    }
+}
+
+impl<T: VarIntValue> SerializedSize for VarInt<T> {
+    #[inline]
+    fn serialized_size(&self) -> usize {
+        self.encoded_len()
+    }
+
+    #[inline]
+    fn min_nonzero_elements_size() -> usize {
+        1
+    }
+
+    #[inline]
+    fn max_default_object_size() -> usize {
+        T::MAX_ENCODED_LEN
+    }
+}
+
+/// LEB128 has no byte order, so `E` is ignored
+impl<T: VarIntValue> BinarySerialize for VarInt<T> {
+    fn binary_serialize<W: Write, E: ByteOrder>(&self, buffer: &mut W) -> usize {
+        buffer.write(&self.encode()).unwrap()
+    }
+}
+
+impl<P, T> SerializedSize for LengthPrefixed<P, T>
+where
+    P: LengthPrefix + BinarySerialize,
+    T: EncodedSequence + SerializedSize,
+{
+    #[inline]
+    fn serialized_size(&self) -> usize {
+        self.encoded_size(Encoding::default())
+    }
+
+    #[inline]
+    fn min_nonzero_elements_size() -> usize {
+        P::SIZE + T::min_nonzero_elements_size()
+    }
+
+    #[inline]
+    fn max_default_object_size() -> usize {
+        P::SIZE + T::max_default_object_size()
+    }
+}
+
+impl SerializedSize for PaddingSeed {
+    #[inline]
+    fn serialized_size(&self) -> usize {
+        0
+    }
+
+    #[inline]
+    fn min_nonzero_elements_size() -> usize {
+        0
+    }
+}
+
+/// The seed only affects padding written by the container, so nothing is written for it
+impl BinarySerialize for PaddingSeed {
+    #[inline(always)]
+    fn binary_serialize<W: Write, E: ByteOrder>(&self, _buffer: &mut W) -> usize {
+        0
+    }
+}
+
+impl<T: SerializedSize> SerializedSize for TagOverride<T> {
+    #[inline]
+    fn serialized_size(&self) -> usize {
+        self.value.serialized_size()
+    }
+
+    #[inline]
+    fn min_nonzero_elements_size() -> usize {
+        T::min_nonzero_elements_size()
+    }
+
+    #[inline]
+    fn max_default_object_size() -> usize {
+        T::max_default_object_size()
+    }
+
+    #[inline]
+    fn min_enum_variant_size(&self) -> usize {
+        self.value.min_enum_variant_size()
+    }
+}
+
+impl<T: TaggedEnum> BinarySerialize for TagOverride<T> {
+    fn binary_serialize<W: Write, E: ByteOrder>(&self, buffer: &mut W) -> usize {
+        self.value
+            .binary_serialize_with_tag::<W, E>(self.tag_override, buffer)
+    }
+}
+
+impl<P, T> BinarySerialize for LengthPrefixed<P, T>
+where
+    P: LengthPrefix + BinarySerialize,
+    T: EncodedSequence,
+{
+    fn binary_serialize<W: Write, E: ByteOrder>(&self, buffer: &mut W) -> usize {
+        self.encoded_serialize::<W, E>(Encoding::default(), buffer)
+    }
+}
+
 impl<T> BinarySerialize for Vec<T>
 where
     T: BinarySerialize,
@@ -152,6 +299,15 @@ where
     }
 }
 
+impl<T> BinarySerialize for Sequence<T>
+where
+    T: BinarySerialize,
+{
+    fn binary_serialize<W: Write, E: ByteOrder>(&self, buffer: &mut W) -> usize {
+        self.messages.binary_serialize::<_, E>(buffer)
+    }
+}
+
 impl BinarySerialize for bool {
     #[inline(always)]
     default fn binary_serialize<W: Write, E: ByteOrder>(&self, buffer: &mut W) -> usize {
@@ -194,8 +350,19 @@ where
     #[inline(always)]
     default fn binary_serialize<W: Write, E: ByteOrder>(&self, buffer: &mut W) -> usize {
         let mut bytes_written = 0;
-        for item in self.iter() {
+
+        if !crate::dump::is_recording() {
+            for item in self.iter() {
+                bytes_written += item.binary_serialize::<W, E>(buffer);
+            }
+
+            return bytes_written;
+        }
+
+        for (i, item) in self.iter().enumerate() {
+            crate::dump::enter_index(i);
             bytes_written += item.binary_serialize::<W, E>(buffer);
+            crate::dump::exit_field();
         }
 
         bytes_written
@@ -232,6 +399,20 @@ impl BinarySerialize for String {
     }
 }
 
+impl BinarySerialize for Utf8String {
+    #[inline(always)]
+    fn binary_serialize<W: Write, E: ByteOrder>(&self, buffer: &mut W) -> usize {
+        self.as_bytes().binary_serialize::<_, E>(buffer)
+    }
+}
+
+impl BinarySerialize for AsciiString {
+    #[inline(always)]
+    fn binary_serialize<W: Write, E: ByteOrder>(&self, buffer: &mut W) -> usize {
+        self.as_bytes().binary_serialize::<_, E>(buffer)
+    }
+}
+
 /// This probably could and should be on a generic impl where T: Deref, but currently
 /// this causes a specialization issue since other crates could impl Deref<Target=T> for
 /// bool (specifically) in the future. See: https://github.com/rust-lang/rust/issues/45542
@@ -306,7 +487,15 @@ macro_rules! impl_binary_serialize {
     }
 }
 
-impl_binary_serialize!(i64, u64, i32, u32, i16, u16, f32, f64);
+impl_binary_serialize!(i128, u128, i64, u64, i32, u32, i16, u16, f32, f64);
+
+impl BinarySerialize for char {
+    #[inline(always)]
+    fn binary_serialize<W: Write, E: ByteOrder>(&self, buffer: &mut W) -> usize {
+        // chars are serialized as their UTF-32 codepoint
+        (*self as u32).binary_serialize::<W, E>(buffer)
+    }
+}
 
 macro_rules! impl_serialized_size {
     ( $($name:ident),* ) => {
@@ -331,7 +520,7 @@ macro_rules! impl_serialized_size {
     }
 }
 
-impl_serialized_size!(i64, u64, i32, u32, i16, u16, f32, f64, u8, i8, bool);
+impl_serialized_size!(i128, u128, i64, u64, i32, u32, i16, u16, f32, f64, u8, i8, bool, char);
 
 impl<T, U> SerializedSize for T
 where
@@ -431,3 +620,220 @@ where
         T::max_default_object_size()
     }
 }
+
+macro_rules! impl_serialize_tuple {
+    ( $( ( $($name:ident . $idx:tt),+ ) ),* ) => {
+        $(
+            impl<$($name),+> SerializedSize for ($($name,)+)
+            where $($name: SerializedSize),+ {
+                #[inline]
+                fn serialized_size(&self) -> usize {
+                    0 $(+ self.$idx.serialized_size())+
+                }
+
+                #[inline]
+                fn min_nonzero_elements_size() -> usize {
+                    0 $(+ <$name>::min_nonzero_elements_size())+
+                }
+
+                #[inline]
+                fn max_default_object_size() -> usize {
+                    0 $(+ <$name>::max_default_object_size())+
+                }
+            }
+
+            impl<$($name),+> BinarySerialize for ($($name,)+)
+            where $($name: BinarySerialize),+ {
+                #[inline]
+                fn binary_serialize<W: Write, E: ByteOrder>(&self, buffer: &mut W) -> usize {
+                    0 $(+ self.$idx.binary_serialize::<W, E>(buffer))+
+                }
+            }
+        )*
+    }
+}
+
+impl_serialize_tuple!(
+    (T0.0),
+    (T0.0, T1.1),
+    (T0.0, T1.1, T2.2),
+    (T0.0, T1.1, T2.2, T3.3),
+    (T0.0, T1.1, T2.2, T3.3, T4.4),
+    (T0.0, T1.1, T2.2, T3.3, T4.4, T5.5),
+    (T0.0, T1.1, T2.2, T3.3, T4.4, T5.5, T6.6),
+    (T0.0, T1.1, T2.2, T3.3, T4.4, T5.5, T6.6, T7.7),
+    (T0.0, T1.1, T2.2, T3.3, T4.4, T5.5, T6.6, T7.7, T8.8),
+    (T0.0, T1.1, T2.2, T3.3, T4.4, T5.5, T6.6, T7.7, T8.8, T9.9),
+    (T0.0, T1.1, T2.2, T3.3, T4.4, T5.5, T6.6, T7.7, T8.8, T9.9, T10.10),
+    (T0.0, T1.1, T2.2, T3.3, T4.4, T5.5, T6.6, T7.7, T8.8, T9.9, T10.10, T11.11)
+);
+
+/// Maps are serialized as a `u32` entry count followed by each key and its value
+macro_rules! impl_serialize_map {
+    ( $($map:ident),* ) => {
+        $(
+            impl<K, V> SerializedSize for $map<K, V>
+            where K: SerializedSize, V: SerializedSize {
+                #[inline]
+                fn serialized_size(&self) -> usize {
+                    std::mem::size_of::<u32>()
+                        + self
+                            .iter()
+                            .map(|(key, value)| key.serialized_size() + value.serialized_size())
+                            .sum::<usize>()
+                }
+
+                #[inline]
+                fn min_nonzero_elements_size() -> usize {
+                    std::mem::size_of::<u32>() + K::min_nonzero_elements_size() + V::min_nonzero_elements_size()
+                }
+
+                #[inline]
+                fn max_default_object_size() -> usize {
+                    std::mem::size_of::<u32>() + K::max_default_object_size() + V::max_default_object_size()
+                }
+            }
+
+        )*
+    }
+}
+
+impl_serialize_map!(HashMap, BTreeMap);
+
+fn serialize_map_entries<'a, K, V, I, W, E>(len: usize, entries: I, buffer: &mut W) -> usize
+where
+    K: BinarySerialize + 'a,
+    V: BinarySerialize + 'a,
+    I: Iterator<Item = (&'a K, &'a V)>,
+    W: Write,
+    E: ByteOrder,
+{
+    let mut bytes_written = (len as u32).binary_serialize::<W, E>(buffer);
+    for (key, value) in entries {
+        bytes_written += key.binary_serialize::<W, E>(buffer);
+        bytes_written += value.binary_serialize::<W, E>(buffer);
+    }
+
+    bytes_written
+}
+
+impl<K, V> BinarySerialize for BTreeMap<K, V>
+where
+    K: BinarySerialize,
+    V: BinarySerialize,
+{
+    fn binary_serialize<W: Write, E: ByteOrder>(&self, buffer: &mut W) -> usize {
+        serialize_map_entries::<_, _, _, W, E>(self.len(), self.iter(), buffer)
+    }
+}
+
+/// `HashMap` iteration order changes between runs, so entries are serialized in key order to
+/// produce the same bytes for the same map
+impl<K, V> BinarySerialize for HashMap<K, V>
+where
+    K: BinarySerialize + Ord,
+    V: BinarySerialize,
+{
+    fn binary_serialize<W: Write, E: ByteOrder>(&self, buffer: &mut W) -> usize {
+        let mut entries: Vec<(&K, &V)> = self.iter().collect();
+        entries.sort_unstable_by(|a, b| a.0.cmp(b.0));
+
+        serialize_map_entries::<_, _, _, W, E>(self.len(), entries.into_iter(), buffer)
+    }
+}
+
+/// Sets are serialized as a `u32` element count followed by each element
+macro_rules! impl_serialize_set {
+    ( $($set:ident),* ) => {
+        $(
+            impl<T> SerializedSize for $set<T>
+            where T: SerializedSize {
+                #[inline]
+                fn serialized_size(&self) -> usize {
+                    std::mem::size_of::<u32>() + self.iter().map(SerializedSize::serialized_size).sum::<usize>()
+                }
+
+                #[inline]
+                fn min_nonzero_elements_size() -> usize {
+                    std::mem::size_of::<u32>() + T::min_nonzero_elements_size()
+                }
+
+                #[inline]
+                fn max_default_object_size() -> usize {
+                    std::mem::size_of::<u32>() + T::max_default_object_size()
+                }
+            }
+
+        )*
+    }
+}
+
+impl_serialize_set!(HashSet, BTreeSet);
+
+fn serialize_set_elements<'a, T, I, W, E>(len: usize, elements: I, buffer: &mut W) -> usize
+where
+    T: BinarySerialize + 'a,
+    I: Iterator<Item = &'a T>,
+    W: Write,
+    E: ByteOrder,
+{
+    let mut bytes_written = (len as u32).binary_serialize::<W, E>(buffer);
+    for element in elements {
+        bytes_written += element.binary_serialize::<W, E>(buffer);
+    }
+
+    bytes_written
+}
+
+impl<T> BinarySerialize for BTreeSet<T>
+where
+    T: BinarySerialize,
+{
+    fn binary_serialize<W: Write, E: ByteOrder>(&self, buffer: &mut W) -> usize {
+        serialize_set_elements::<_, _, W, E>(self.len(), self.iter(), buffer)
+    }
+}
+
+/// `HashSet` iteration order changes between runs, so elements are serialized in sorted order
+/// to produce the same bytes for the same set
+impl<T> BinarySerialize for HashSet<T>
+where
+    T: BinarySerialize + Ord,
+{
+    fn binary_serialize<W: Write, E: ByteOrder>(&self, buffer: &mut W) -> usize {
+        let mut elements: Vec<&T> = self.iter().collect();
+        elements.sort_unstable();
+
+        serialize_set_elements::<_, _, W, E>(self.len(), elements.into_iter(), buffer)
+    }
+}
+
+impl<T> SerializedSize for VecDeque<T>
+where
+    T: SerializedSize,
+{
+    #[inline]
+    fn serialized_size(&self) -> usize {
+        self.iter().map(SerializedSize::serialized_size).sum()
+    }
+
+    #[inline]
+    fn min_nonzero_elements_size() -> usize {
+        T::min_nonzero_elements_size()
+    }
+
+    #[inline]
+    fn max_default_object_size() -> usize {
+        T::max_default_object_size()
+    }
+}
+
+impl<T> BinarySerialize for VecDeque<T>
+where
+    T: BinarySerialize,
+{
+    fn binary_serialize<W: Write, E: ByteOrder>(&self, buffer: &mut W) -> usize {
+        let (front, back) = self.as_slices();
+        front.binary_serialize::<_, E>(buffer) + back.binary_serialize::<_, E>(buffer)
+    }
+}
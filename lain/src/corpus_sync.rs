@@ -0,0 +1,403 @@
+//! Corpus synchronization between fuzzer processes. [FuzzerDriver](crate::driver::FuzzerDriver)
+//! only shares state between threads of a single process, so to run several lain processes (e.g.
+//! one per NUMA node or machine) or to run lain next to AFL, each instance periodically exports
+//! its findings to and imports other instances' findings from a shared directory.
+//!
+//! The directory uses AFL's `-M`/`-S` layout: every instance owns `<root>/<instance>/queue` for
+//! corpus entries and `<root>/<instance>/crashes` for crashing inputs, and only ever writes to its
+//! own directories. Files are named with AFL's comma-separated `key:value` format, which records
+//! where each input came from:
+//!
+//! ```text
+//! sync/
+//!   lain-0/queue/id:000000,op:seed,time:0
+//!   lain-0/queue/id:000001,src:000000,op:havoc,time:1520
+//!   lain-0/crashes/id:000000,sig:11,src:000001,time:4100
+//!   lain-1/queue/id:000000,sync:lain-0,src:000001,time:2003
+//!   afl-main/queue/id:000004,src:000001,op:flip1,pos:0
+//! ```
+//!
+//! Inputs are exchanged as the serialized bytes that were sent to the target, which is the format
+//! other fuzzers understand. Since lain can't parse arbitrary bytes back into a structured input,
+//! imported inputs are handed back as bytes for the fuzzer to replay or convert.
+//!
+//! ```no_run
+//! use lain::corpus_sync::{Origin, SyncDir, SyncKind};
+//!
+//! let mut sync = SyncDir::new("/dev/shm/sync", "lain-0").unwrap();
+//!
+//! // after finding something new
+//! # let data = vec![];
+//! sync.export_entry(&data, &Origin::mutation(Some(3), "havoc")).unwrap();
+//!
+//! // between iterations
+//! for input in sync.import_if_due().unwrap() {
+//!     match input.kind {
+//!         SyncKind::Entry => { /* replay input.data and keep it if it hits new coverage */ }
+//!         SyncKind::Crash => { /* a crash someone else already found */ }
+//!     }
+//! }
+//! ```
+
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// How often [SyncDir::import_if_due] imports by default
+pub const DEFAULT_SYNC_INTERVAL: Duration = Duration::from_secs(30);
+
+const QUEUE_DIR: &str = "queue";
+const CRASHES_DIR: &str = "crashes";
+
+/// Whether a synced input is a corpus entry or a crash
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum SyncKind {
+    Entry,
+    Crash,
+}
+
+impl SyncKind {
+    fn dir_name(self) -> &'static str {
+        match self {
+            SyncKind::Entry => QUEUE_DIR,
+            SyncKind::Crash => CRASHES_DIR,
+        }
+    }
+}
+
+/// Where an input came from, as recorded in its file name
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Origin {
+    /// The instance whose directory the input was found in. Filled in on import.
+    pub instance: String,
+    /// The instance's ID for the input (`id`). Assigned on export.
+    pub id: Option<u64>,
+    /// The ID of the entry the input was derived from (`src`)
+    pub parent: Option<u64>,
+    /// How the input was produced, e.g. `havoc` or `seed` (`op`)
+    pub op: Option<String>,
+    /// The signal the input crashed the target with (`sig`)
+    pub signal: Option<i32>,
+    /// The instance the input was originally imported from (`sync`)
+    pub synced_from: Option<String>,
+    /// Milliseconds between the instance starting and the input being written (`time`)
+    pub time: Option<u64>,
+}
+
+impl Origin {
+    /// An initial seed
+    pub fn seed() -> Origin {
+        Origin {
+            op: Some("seed".to_string()),
+            ..Default::default()
+        }
+    }
+
+    /// An input produced by mutating entry `parent` with `op`
+    pub fn mutation(parent: Option<u64>, op: &str) -> Origin {
+        Origin {
+            parent,
+            op: Some(op.to_string()),
+            ..Default::default()
+        }
+    }
+
+    /// An input which crashed the target, optionally with a signal
+    pub fn crash(parent: Option<u64>, signal: Option<i32>) -> Origin {
+        Origin {
+            parent,
+            signal,
+            ..Default::default()
+        }
+    }
+
+    /// An input imported from another instance which is being re-exported because it was useful
+    /// to this one, mirroring AFL's `sync:<instance>,src:<id>` names
+    pub fn synced(from: &Origin) -> Origin {
+        Origin {
+            parent: from.id,
+            synced_from: Some(from.instance.clone()),
+            ..Default::default()
+        }
+    }
+
+    /// Parses an AFL-style file name. Unknown keys are ignored, so names written by AFL's other
+    /// modes still parse.
+    pub fn from_file_name(instance: &str, name: &str) -> Origin {
+        let mut origin = Origin {
+            instance: instance.to_string(),
+            ..Default::default()
+        };
+
+        for pair in name.split(',') {
+            let mut parts = pair.splitn(2, ':');
+            let (key, value) = match (parts.next(), parts.next()) {
+                (Some(key), Some(value)) => (key, value),
+                _ => continue,
+            };
+
+            match key {
+                "id" => origin.id = value.parse().ok(),
+                "src" => origin.parent = value.split('+').next().and_then(|v| v.parse().ok()),
+                "op" => origin.op = Some(value.to_string()),
+                "sig" => origin.signal = value.parse().ok(),
+                "sync" => origin.synced_from = Some(value.to_string()),
+                "time" => origin.time = value.parse().ok(),
+                _ => {}
+            }
+        }
+
+        origin
+    }
+
+    /// Formats the origin as an AFL-style file name. The ID must be set.
+    pub fn to_file_name(&self) -> String {
+        let mut name = format!("id:{:06}", self.id.unwrap_or(0));
+
+        if let Some(signal) = self.signal {
+            name += &format!(",sig:{:02}", signal);
+        }
+
+        if let Some(ref synced_from) = self.synced_from {
+            name += &format!(",sync:{}", synced_from);
+        }
+
+        if let Some(parent) = self.parent {
+            name += &format!(",src:{:06}", parent);
+        }
+
+        if let Some(ref op) = self.op {
+            name += &format!(",op:{}", op);
+        }
+
+        if let Some(time) = self.time {
+            name += &format!(",time:{}", time);
+        }
+
+        name
+    }
+}
+
+/// An input found in another instance's directory
+#[derive(Debug, Clone)]
+pub struct SyncedInput {
+    pub kind: SyncKind,
+    pub origin: Origin,
+    pub path: PathBuf,
+    pub data: Vec<u8>,
+}
+
+/// One instance's view of a shared sync directory
+#[derive(Debug)]
+pub struct SyncDir {
+    root: PathBuf,
+    instance: String,
+    started: Instant,
+    next_entry_id: u64,
+    next_crash_id: u64,
+    interval: Duration,
+    last_import: Option<Instant>,
+    /// Files which have already been imported, as (instance, kind, file name)
+    imported: HashSet<(String, SyncKind, String)>,
+}
+
+impl SyncDir {
+    /// Joins the sync directory at `root` as `instance`, creating this instance's directories if
+    /// needed. IDs continue from any inputs a previous run of the instance exported.
+    pub fn new<P: AsRef<Path>>(root: P, instance: &str) -> io::Result<SyncDir> {
+        if instance.is_empty() || instance.starts_with('.') || instance.contains(['/', '\\']) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid instance name `{}`", instance),
+            ));
+        }
+
+        let root = root.as_ref().to_owned();
+        let mut sync = SyncDir {
+            root,
+            instance: instance.to_string(),
+            started: Instant::now(),
+            next_entry_id: 0,
+            next_crash_id: 0,
+            interval: DEFAULT_SYNC_INTERVAL,
+            last_import: None,
+            imported: HashSet::new(),
+        };
+
+        for kind in [SyncKind::Entry, SyncKind::Crash].iter().cloned() {
+            let dir = sync.dir(kind);
+            fs::create_dir_all(&dir)?;
+
+            let next_id = list_inputs(&dir)?
+                .iter()
+                .filter_map(|name| Origin::from_file_name(instance, name).id)
+                .max()
+                .map(|id| id + 1)
+                .unwrap_or(0);
+
+            match kind {
+                SyncKind::Entry => sync.next_entry_id = next_id,
+                SyncKind::Crash => sync.next_crash_id = next_id,
+            }
+        }
+
+        Ok(sync)
+    }
+
+    /// This instance's name
+    pub fn instance(&self) -> &str {
+        &self.instance
+    }
+
+    /// The directory this instance writes inputs of `kind` to
+    pub fn dir(&self, kind: SyncKind) -> PathBuf {
+        self.root.join(&self.instance).join(kind.dir_name())
+    }
+
+    /// Sets how often [SyncDir::import_if_due] imports
+    pub fn set_interval(&mut self, interval: Duration) {
+        self.interval = interval;
+    }
+
+    /// Writes a corpus entry to this instance's queue. The ID and time of `origin` are assigned
+    /// here. Returns the written file's path.
+    pub fn export_entry(&mut self, data: &[u8], origin: &Origin) -> io::Result<PathBuf> {
+        self.export(SyncKind::Entry, data, origin)
+    }
+
+    /// Writes a crashing input to this instance's crashes directory. The ID and time of `origin`
+    /// are assigned here. Returns the written file's path.
+    pub fn export_crash(&mut self, data: &[u8], origin: &Origin) -> io::Result<PathBuf> {
+        self.export(SyncKind::Crash, data, origin)
+    }
+
+    fn export(&mut self, kind: SyncKind, data: &[u8], origin: &Origin) -> io::Result<PathBuf> {
+        let next_id = match kind {
+            SyncKind::Entry => &mut self.next_entry_id,
+            SyncKind::Crash => &mut self.next_crash_id,
+        };
+
+        let mut origin = origin.clone();
+        origin.instance = self.instance.clone();
+        origin.id = Some(*next_id);
+        origin.time = Some(self.started.elapsed().as_millis() as u64);
+        *next_id += 1;
+
+        let dir = self.dir(kind);
+        let name = origin.to_file_name();
+        let path = dir.join(&name);
+
+        // other instances may read the directory at any time, so the file is moved into place
+        // once it's complete
+        let temp_path = dir.join(format!(".{}.tmp", name));
+        fs::write(&temp_path, data)?;
+        fs::rename(&temp_path, &path)?;
+
+        Ok(path)
+    }
+
+    /// Imports the inputs that other instances wrote since the last import if the sync interval
+    /// has elapsed, otherwise returns nothing
+    pub fn import_if_due(&mut self) -> io::Result<Vec<SyncedInput>> {
+        let due = self
+            .last_import
+            .map(|last| last.elapsed() >= self.interval)
+            .unwrap_or(true);
+
+        if !due {
+            return Ok(vec![]);
+        }
+
+        self.import()
+    }
+
+    /// Imports every input that other instances wrote since the last import, ordered by
+    /// instance, kind, and ID. Inputs or directories which can't be read are skipped with a
+    /// warning and tried again on the next import.
+    pub fn import(&mut self) -> io::Result<Vec<SyncedInput>> {
+        self.last_import = Some(Instant::now());
+
+        let mut instances = vec![];
+        for dir_entry in fs::read_dir(&self.root)? {
+            let dir_entry = dir_entry?;
+            let name = dir_entry.file_name().to_string_lossy().into_owned();
+
+            if name == self.instance || name.starts_with('.') || !dir_entry.file_type()?.is_dir() {
+                continue;
+            }
+
+            instances.push(name);
+        }
+        instances.sort();
+
+        let mut inputs = vec![];
+        for instance in instances {
+            for kind in [SyncKind::Entry, SyncKind::Crash].iter().cloned() {
+                let dir = self.root.join(&instance).join(kind.dir_name());
+                if !dir.is_dir() {
+                    continue;
+                }
+
+                let names = match list_inputs(&dir) {
+                    Ok(names) => names,
+                    Err(e) => {
+                        warn!("could not list {}: {}", dir.display(), e);
+                        continue;
+                    }
+                };
+
+                let mut found = vec![];
+                for name in names {
+                    let key = (instance.clone(), kind, name);
+                    if self.imported.contains(&key) {
+                        continue;
+                    }
+
+                    let path = dir.join(&key.2);
+                    let data = match fs::read(&path) {
+                        Ok(data) => data,
+                        // the other instance may be cleaning up
+                        Err(ref e) if e.kind() == io::ErrorKind::NotFound => continue,
+                        Err(e) => {
+                            warn!("could not read {}: {}", path.display(), e);
+                            continue;
+                        }
+                    };
+
+                    found.push(SyncedInput {
+                        kind,
+                        origin: Origin::from_file_name(&instance, &key.2),
+                        path,
+                        data,
+                    });
+                    self.imported.insert(key);
+                }
+
+                found.sort_by_key(|input| input.origin.id);
+                inputs.extend(found);
+            }
+        }
+
+        Ok(inputs)
+    }
+}
+
+/// Lists the inputs in a queue or crashes directory, skipping hidden and temporary files and
+/// AFL's README
+fn list_inputs(dir: &Path) -> io::Result<Vec<String>> {
+    let mut names = vec![];
+    for dir_entry in fs::read_dir(dir)? {
+        let dir_entry = dir_entry?;
+        let name = dir_entry.file_name().to_string_lossy().into_owned();
+
+        if name.starts_with('.') || name == "README.txt" || !dir_entry.file_type()?.is_file() {
+            continue;
+        }
+
+        names.push(name);
+    }
+
+    Ok(names)
+}
@@ -41,6 +41,20 @@ static DANGEROUS_NUMBERS_U64: &'static [u64] = &[
     0x0000_0000_0000_0080,
 ];
 
+static DANGEROUS_NUMBERS_U128: &[u128] = &[
+    // big-endian variants
+    u128::MIN,
+    u128::MAX,
+    i128::MAX as u128,
+    (i128::MAX as u128) + 1,
+    // values which cross the 64-bit boundary
+    u64::MAX as u128,
+    (u64::MAX as u128) + 1,
+    // little-endian variants
+    0xffff_ffff_ffff_ffff_ffff_ffff_ffff_ff7f,
+    0x0000_0000_0000_0000_0000_0000_0000_0080,
+];
+
 static DANGEROUS_NUMBERS_F32: &'static [f32] = &[
     std::f32::INFINITY,
     std::f32::MAX,
@@ -85,5 +99,42 @@ dangerous_number!(u32, DANGEROUS_NUMBERS_U32);
 dangerous_number!(i32, DANGEROUS_NUMBERS_U32);
 dangerous_number!(u64, DANGEROUS_NUMBERS_U64);
 dangerous_number!(i64, DANGEROUS_NUMBERS_U64);
+dangerous_number!(u128, DANGEROUS_NUMBERS_U128);
+dangerous_number!(i128, DANGEROUS_NUMBERS_U128);
 dangerous_number!(f32, DANGEROUS_NUMBERS_F32);
 dangerous_number!(f64, DANGEROUS_NUMBERS_F64);
+
+/// Characters which commonly trip up parsers: control characters, the replacement character,
+/// byte order marks, characters right before/after the surrogate range, and the largest valid
+/// codepoint.
+static DANGEROUS_CHARS: &[char] = &[
+    '\u{0000}',
+    '\u{000a}',
+    '\u{000d}',
+    '\u{007f}',
+    '\u{0080}',
+    '\u{00ff}',
+    '\u{200b}',
+    '\u{202e}',
+    '\u{d7ff}',
+    '\u{e000}',
+    '\u{feff}',
+    '\u{fffd}',
+    '\u{ffff}',
+    '\u{10000}',
+    '\u{10ffff}',
+];
+
+impl DangerousNumber<char> for char {
+    fn select_dangerous_number<R: Rng>(rng: &mut R) -> char {
+        DANGEROUS_CHARS[rng.gen_range(0, DANGEROUS_CHARS.len())]
+    }
+
+    fn dangerous_number_at_index(idx: usize) -> char {
+        DANGEROUS_CHARS[idx]
+    }
+
+    fn dangerous_numbers_len() -> usize {
+        DANGEROUS_CHARS.len()
+    }
+}
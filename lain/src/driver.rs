@@ -1,11 +1,18 @@
+use crate::executor::{CommandExecutor, ExecutionResult};
 use crate::mutator::Mutator;
+use crate::traits::{BinarySerialize, SerializedSize};
+use byteorder::ByteOrder;
 use rand::rngs::StdRng;
 use rand::{Rng, SeedableRng};
+use std::io;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, Mutex, RwLock};
 use std::thread;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+#[cfg(feature = "tokio_support")]
+use std::future::Future;
+
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum DriverMode {
     Reproduce,
@@ -36,6 +43,95 @@ impl<T: 'static + Send + Sync> Default for FuzzerDriver<T> {
     }
 }
 
+/// Builder for a [FuzzerDriver] whose threads run harnesses produced by a factory closure.
+///
+/// ```compile_fail
+/// let driver = FuzzerDriver::<GlobalContext>::builder(4)
+///     .seed(seed)
+///     .global_context(global_context)
+///     .start_with_factory(move |thread_index| {
+///         let mut target = Target::open(&config);
+///
+///         move |mutator: &mut Mutator<StdRng>, _global_context: Option<Arc<RwLock<GlobalContext>>>| {
+///             target.run(mutator)
+///         }
+///     });
+///
+/// driver.join_threads();
+/// ```
+pub struct FuzzerDriverBuilder<T> {
+    driver: FuzzerDriver<T>,
+}
+
+impl<T: 'static + Send + Sync> FuzzerDriverBuilder<T> {
+    /// Creates a builder for a driver with the specified number of threads
+    pub fn new(num_threads: usize) -> Self {
+        FuzzerDriverBuilder {
+            driver: FuzzerDriver::new(num_threads),
+        }
+    }
+
+    /// Sets the root seed
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.driver.set_seed(seed);
+        self
+    }
+
+    /// Sets the context shared between all threads
+    pub fn global_context(mut self, context: Arc<RwLock<T>>) -> Self {
+        self.driver.set_global_context(context);
+        self
+    }
+
+    /// Configures the driver to reproduce iterations `start_iteration..end_iteration`. See
+    /// [FuzzerDriver::set_to_reproduce_mode].
+    pub fn reproduce(mut self, start_iteration: u64, end_iteration: u64) -> Self {
+        self.driver
+            .set_to_reproduce_mode(start_iteration, end_iteration);
+        self
+    }
+
+    /// Sets the max duration before a thread is flagged as stalled
+    pub fn thread_timeout(mut self, duration: Duration) -> Self {
+        self.driver.set_thread_timeout(duration);
+        self
+    }
+
+    /// Returns the configured driver without starting any threads
+    pub fn build(self) -> FuzzerDriver<T> {
+        self.driver
+    }
+
+    /// Starts the fuzzer threads using the harness `factory` and returns the running driver.
+    /// See [start_fuzzer_with_factory].
+    pub fn start_with_factory<Factory, H>(self, factory: Factory) -> Arc<FuzzerDriver<T>>
+    where
+        Factory: Fn(usize) -> H + Send + Sync + 'static,
+        H: FnMut(&mut Mutator<StdRng>, Option<Arc<RwLock<T>>>) -> Result<(), ()> + 'static,
+    {
+        let driver = Arc::new(self.driver);
+
+        start_fuzzer_with_factory(driver.clone(), factory);
+
+        driver
+    }
+
+    /// Starts a differential fuzzing job and returns the running driver. See
+    /// [start_differential_fuzzer].
+    pub fn start_differential<Factory, G, O>(self, factory: Factory) -> Arc<FuzzerDriver<T>>
+    where
+        Factory: Fn(usize) -> (Differential<O>, G) + Send + Sync + 'static,
+        G: FnMut(&mut Mutator<StdRng>, Option<Arc<RwLock<T>>>) -> Vec<u8> + 'static,
+        O: 'static,
+    {
+        let driver = Arc::new(self.driver);
+
+        start_differential_fuzzer(driver.clone(), factory);
+
+        driver
+    }
+}
+
 impl<T: 'static + Send + Sync> FuzzerDriver<T> {
     /// Instantiates new FuzzerDriver with the specified number of threads and uses
     /// the thread-local RNG to generate a seed
@@ -63,6 +159,11 @@ impl<T: 'static + Send + Sync> FuzzerDriver<T> {
         }
     }
 
+    /// Returns a [FuzzerDriverBuilder] for a driver with the specified number of threads
+    pub fn builder(num_threads: usize) -> FuzzerDriverBuilder<T> {
+        FuzzerDriverBuilder::new(num_threads)
+    }
+
     pub fn thread_count(&self) -> usize {
         self.thread_count
     }
@@ -151,6 +252,39 @@ impl<T: 'static + Send + Sync> FuzzerDriver<T> {
         self.mode
     }
 
+    /// Prepares the mutator for the next iteration of the thread (or task) at `thread_index`.
+    /// Returns `false` if the caller should stop fuzzing instead.
+    fn begin_iteration(
+        &self,
+        thread_index: usize,
+        thread_seed: u64,
+        mutator: &mut Mutator<StdRng>,
+    ) -> bool {
+        self.set_thread_last_execution_time(thread_index);
+
+        // TODO: here be dragons? num_iterations is a usize and we're casting it to a u64. on 64-bit systems this
+        // isn't a problem since usize should be a u64, but it's worth noting that this could be a potential issue
+        let new_seed = thread_seed.wrapping_add(self.num_iterations() as u64);
+        mutator.rng = StdRng::seed_from_u64(new_seed);
+
+        if self.should_exit() {
+            return false;
+        }
+
+        mutator.random_flags();
+
+        true
+    }
+
+    /// Records the result of a single iteration
+    fn end_iteration(&self, result: Result<(), ()>) {
+        if result.is_err() {
+            self.num_failed_iterations.fetch_add(1, Ordering::SeqCst);
+        }
+
+        self.num_iterations.fetch_add(1, Ordering::SeqCst);
+    }
+
     pub(crate) fn set_thread_last_execution_time(&self, thread_index: usize) {
         let since_the_epoch = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
 
@@ -174,10 +308,11 @@ impl<T: 'static + Send + Sync> FuzzerDriver<T> {
             }
 
             if since_the_epoch - last_update > self.thread_timeout {
-                error!(
-                    "{:?} has stalled!",
-                    self.threads.read().unwrap()[i].thread().id()
-                );
+                // async fuzzing tasks don't have a thread handle, so fall back to the index
+                match self.threads.read().unwrap().get(i) {
+                    Some(handle) => error!("{:?} has stalled!", handle.thread().id()),
+                    None => error!("fuzzer task {} has stalled!", i),
+                }
                 threads_have_stalled = true;
             }
         }
@@ -198,6 +333,8 @@ impl<T: 'static + Send + Sync> FuzzerDriver<T> {
 /// ```compile_fail
 /// fn iteration_routine<R: Rng>(mutator: &mut Mutator<R>, thread_context: &mut FuzzerThreadContext, _global_context: Option<Arc<RwLock<GlobalContext>>>) -> Result<(), ()>
 /// ```
+///
+/// If your callback needs to capture state, see [start_fuzzer_with_factory].
 pub fn start_fuzzer<F: 'static, C: 'static, T: 'static + Send + Sync>(
     driver: Arc<FuzzerDriver<T>>,
     callback: F,
@@ -207,13 +344,47 @@ pub fn start_fuzzer<F: 'static, C: 'static, T: 'static + Send + Sync>(
         + std::marker::Sync
         + Copy,
     C: Default,
+{
+    start_fuzzer_with_factory(driver, move |_thread_index| {
+        let mut context = C::default();
+
+        move |mutator: &mut Mutator<StdRng>, global_context: Option<Arc<RwLock<T>>>| {
+            (callback)(mutator, &mut context, global_context)
+        }
+    });
+}
+
+/// Kicks off a fuzzing job using the driver and a harness factory.
+///
+/// The factory is invoked once on each fuzzer thread with the thread's index and returns the
+/// harness that thread will call on every iteration. Since the harness is created on the thread
+/// that runs it, it may own state that isn't `Send` (e.g. a connection or handle to the target)
+/// and be set up exactly once per thread:
+///
+/// ```compile_fail
+/// start_fuzzer_with_factory(driver, move |thread_index| {
+///     let mut stream = TcpStream::connect(addr).unwrap();
+///
+///     move |mutator: &mut Mutator<StdRng>, _global_context: Option<Arc<RwLock<GlobalContext>>>| {
+///         // ...
+///         Ok(())
+///     }
+/// });
+/// ```
+pub fn start_fuzzer_with_factory<Factory, H, T>(driver: Arc<FuzzerDriver<T>>, factory: Factory)
+where
+    Factory: Fn(usize) -> H + Send + Sync + 'static,
+    H: FnMut(&mut Mutator<StdRng>, Option<Arc<RwLock<T>>>) -> Result<(), ()> + 'static,
+    T: 'static + Send + Sync,
 {
     let mut root_rng = StdRng::seed_from_u64(driver.seed());
+    let factory = Arc::new(factory);
 
     let mut threads = driver.threads.write().unwrap();
 
-    for i in 0..threads.capacity() {
+    for i in 0..driver.thread_count() {
         let thread_driver = driver.clone();
+        let thread_factory = factory.clone();
         let thread_name = format!("Fuzzer thread {}", i);
 
         let thread_seed: u64 = root_rng.gen();
@@ -225,37 +396,315 @@ pub fn start_fuzzer<F: 'static, C: 'static, T: 'static + Send + Sync>(
                 // on the first loop iteration
                 let thread_rng = StdRng::seed_from_u64(0u64);
                 let mut mutator = Mutator::new(thread_rng);
-                let mut context = C::default();
+                let mut harness = (thread_factory)(i);
 
                 // loop until we get a signal that we should exit
-                loop {
-                    thread_driver.set_thread_last_execution_time(i);
-
-                    // TODO: here be dragons? num_iterations is a usize and we're casting it to a u64. on 64-bit systems this
-                    // isn't a problem since usize should be a u64, but it's worth noting that this could be a potential issue
-                    let new_seed = thread_seed.wrapping_add(thread_driver.num_iterations() as u64);
-                    mutator.rng = StdRng::seed_from_u64(new_seed);
-
-                    if thread_driver.should_exit() {
-                        log::info!("{} exiting", thread::current().name().unwrap());
-                        return;
-                    }
-
-                    mutator.random_flags();
-
-                    if let Err(_) =
-                        (callback)(&mut mutator, &mut context, thread_driver.global_context())
-                    {
-                        thread_driver
-                            .num_failed_iterations
-                            .fetch_add(1, Ordering::SeqCst);
-                    }
-
-                    thread_driver.num_iterations.fetch_add(1, Ordering::SeqCst);
+                while thread_driver.begin_iteration(i, thread_seed, &mut mutator) {
+                    let result = (harness)(&mut mutator, thread_driver.global_context());
+
+                    thread_driver.end_iteration(result);
                 }
+
+                log::info!("{} exiting", thread::current().name().unwrap());
             })
             .unwrap_or_else(|_| panic!("could not create new thread"));
 
         threads.push(join_handle);
     }
 }
+
+/// Kicks off a differential fuzzing job using the driver and a factory which returns the
+/// [Differential] targets and an input generator for each thread.
+///
+/// On every iteration the generator produces a serialized input which is fed to each target.
+/// Iterations where the targets' outputs aren't equivalent are recorded as [Divergence]s and
+/// counted as failed iterations. Divergences which duplicate one already recorded only bump its
+/// hit count, and at most [DEFAULT_MAX_DIVERGENCES] distinct ones are kept by default:
+///
+/// ```compile_fail
+/// let divergences = Arc::new(Mutex::new(vec![]));
+///
+/// start_differential_fuzzer(driver, move |_thread_index| {
+///     let differential = Differential::new(|a: &Vec<u8>, b: &Vec<u8>| a == b)
+///         .target("rust", |input| rust_parser::parse(input).to_bytes())
+///         .command("legacy", CommandExecutor::new("./legacy_parser"), |result| {
+///             result.map(|result| result.stdout).unwrap_or_default()
+///         })
+///         .with_divergences(divergences.clone());
+///
+///     let generate = |mutator: &mut Mutator<StdRng>, _global_context| {
+///         let mut input = vec![];
+///         Packet::new_fuzzed(mutator, None).binary_serialize::<_, LittleEndian>(&mut input);
+///         input
+///     };
+///
+///     (differential, generate)
+/// });
+/// ```
+pub fn start_differential_fuzzer<Factory, G, O, T>(driver: Arc<FuzzerDriver<T>>, factory: Factory)
+where
+    Factory: Fn(usize) -> (Differential<O>, G) + Send + Sync + 'static,
+    G: FnMut(&mut Mutator<StdRng>, Option<Arc<RwLock<T>>>) -> Vec<u8> + 'static,
+    O: 'static,
+    T: 'static + Send + Sync,
+{
+    start_fuzzer_with_factory(driver, move |thread_index| {
+        let (mut differential, mut generate) = factory(thread_index);
+
+        move |mutator: &mut Mutator<StdRng>, global_context: Option<Arc<RwLock<T>>>| {
+            let input = (generate)(mutator, global_context);
+
+            differential.run(&input)
+        }
+    });
+}
+
+/// Kicks off an asynchronous fuzzing job on the current tokio runtime using the driver and
+/// callback function. One task is spawned per configured thread, and the returned future
+/// resolves once every task has exited.
+///
+/// Seeding and reproduce mode behave exactly as they do for [start_fuzzer]. Since the callback's
+/// future must be `'static`, the mutator and thread context are moved into the callback and must
+/// be handed back along with the iteration's result. The callback should look something like:
+///
+/// ```compile_fail
+/// async fn iteration_routine(mut mutator: Mutator<StdRng>, mut thread_context: FuzzerThreadContext, _global_context: Option<Arc<RwLock<GlobalContext>>>) -> (Mutator<StdRng>, FuzzerThreadContext, Result<(), ()>)
+/// ```
+///
+/// **Note:** the global context is guarded by a synchronous `RwLock`. Do not hold its guard
+/// across an `.await`.
+#[cfg(feature = "tokio_support")]
+pub async fn start_fuzzer_async<F, Fut, C, T>(driver: Arc<FuzzerDriver<T>>, callback: F)
+where
+    F: Fn(Mutator<StdRng>, C, Option<Arc<RwLock<T>>>) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = (Mutator<StdRng>, C, Result<(), ()>)> + Send + 'static,
+    C: Default + Send + 'static,
+    T: 'static + Send + Sync,
+{
+    let mut root_rng = StdRng::seed_from_u64(driver.seed());
+    let callback = Arc::new(callback);
+
+    let mut tasks = Vec::with_capacity(driver.thread_count());
+
+    for i in 0..driver.thread_count() {
+        let task_driver = driver.clone();
+        let task_callback = callback.clone();
+
+        let task_seed: u64 = root_rng.gen();
+
+        tasks.push(tokio::spawn(async move {
+            // overwritten on the first loop iteration, same as the threaded driver
+            let mut mutator = Mutator::new(StdRng::seed_from_u64(0u64));
+            let mut context = C::default();
+
+            while task_driver.begin_iteration(i, task_seed, &mut mutator) {
+                let (returned_mutator, returned_context, result) =
+                    (task_callback)(mutator, context, task_driver.global_context()).await;
+
+                mutator = returned_mutator;
+                context = returned_context;
+
+                task_driver.end_iteration(result);
+            }
+
+            log::info!("fuzzer task {} exiting", i);
+        }));
+    }
+
+    for task in tasks {
+        if let Err(e) = task.await {
+            error!("fuzzer task failed to join: {}", e);
+        }
+    }
+}
+
+type DifferentialTarget<O> = Box<dyn FnMut(&[u8]) -> O>;
+type Equivalence<O> = Box<dyn Fn(&O, &O) -> bool>;
+type DivergenceDedup<O> = Box<dyn Fn(&Divergence<O>, &Divergence<O>) -> bool>;
+
+/// The number of distinct divergences a [Differential] keeps unless configured otherwise
+pub const DEFAULT_MAX_DIVERGENCES: usize = 1024;
+
+/// Inputs on which the targets of a [Differential] disagreed
+pub type Divergences<O> = Arc<Mutex<Vec<Divergence<O>>>>;
+
+/// An input on which the targets of a [Differential] disagreed
+#[derive(Debug, Clone)]
+pub struct Divergence<O> {
+    /// The input that was fed to every target
+    pub input: Vec<u8>,
+    /// The name and output of every target, in the order the targets were added
+    pub outputs: Vec<(String, O)>,
+    /// The indices into `outputs` of the first pair of targets whose outputs weren't equivalent
+    pub mismatch: (usize, usize),
+    /// How many times this divergence or a duplicate of it was seen
+    pub hits: u64,
+}
+
+impl<O> Divergence<O> {
+    /// The name and output of the first target in the mismatched pair
+    pub fn left(&self) -> &(String, O) {
+        &self.outputs[self.mismatch.0]
+    }
+
+    /// The name and output of the second target in the mismatched pair
+    pub fn right(&self) -> &(String, O) {
+        &self.outputs[self.mismatch.1]
+    }
+}
+
+/// Feeds the same input to several implementations of the same thing, such as a parser and the
+/// legacy library it replaces, and records inputs on which their outputs diverge.
+///
+/// Targets may be in-process closures or subprocesses. Every target must produce the same output
+/// type `O`, which is compared using the equivalence function the helper was created with. Each
+/// target's output is compared against the first target's.
+///
+/// By default two divergences are duplicates if the same pair of targets mismatched and both of
+/// their outputs are equivalent. This can be replaced using [Differential::dedup_by].
+pub struct Differential<O> {
+    targets: Vec<(String, DifferentialTarget<O>)>,
+    equivalent: Equivalence<O>,
+    dedup: Option<DivergenceDedup<O>>,
+    max_divergences: usize,
+    divergences: Divergences<O>,
+}
+
+impl<O: 'static> Differential<O> {
+    /// Creates a helper with no targets which considers two outputs the same if `equivalent`
+    /// returns true
+    pub fn new<F>(equivalent: F) -> Differential<O>
+    where
+        F: Fn(&O, &O) -> bool + 'static,
+    {
+        Differential {
+            targets: vec![],
+            equivalent: Box::new(equivalent),
+            dedup: None,
+            max_divergences: DEFAULT_MAX_DIVERGENCES,
+            divergences: Arc::new(Mutex::new(vec![])),
+        }
+    }
+
+    /// Adds an in-process target
+    pub fn target<F>(mut self, name: &str, target: F) -> Self
+    where
+        F: FnMut(&[u8]) -> O + 'static,
+    {
+        self.targets.push((name.to_string(), Box::new(target)));
+        self
+    }
+
+    /// Adds a subprocess target. `to_output` converts the result of running the process into the
+    /// output that's compared, e.g. by taking its stdout and exit status.
+    pub fn command<F>(self, name: &str, mut executor: CommandExecutor, to_output: F) -> Self
+    where
+        F: Fn(io::Result<ExecutionResult>) -> O + 'static,
+    {
+        self.target(name, move |input| to_output(executor.execute(input)))
+    }
+
+    /// Records divergences in `divergences` instead of a list owned by this helper, so that the
+    /// helpers of several fuzzer threads can share one list
+    pub fn with_divergences(mut self, divergences: Divergences<O>) -> Self {
+        self.divergences = divergences;
+        self
+    }
+
+    /// Considers a divergence a duplicate of one already recorded if `same` returns true, e.g. by
+    /// comparing a crash signature extracted from the outputs
+    pub fn dedup_by<F>(mut self, same: F) -> Self
+    where
+        F: Fn(&Divergence<O>, &Divergence<O>) -> bool + 'static,
+    {
+        self.dedup = Some(Box::new(same));
+        self
+    }
+
+    /// Sets the most distinct divergences that are kept. Once the list is full new divergences
+    /// are still counted as failed iterations but aren't recorded.
+    pub fn with_max_divergences(mut self, max_divergences: usize) -> Self {
+        self.max_divergences = max_divergences;
+        self
+    }
+
+    /// The divergences recorded so far
+    pub fn divergences(&self) -> Divergences<O> {
+        self.divergences.clone()
+    }
+
+    fn is_duplicate(&self, recorded: &Divergence<O>, divergence: &Divergence<O>) -> bool {
+        if let Some(same) = &self.dedup {
+            return same(recorded, divergence);
+        }
+
+        recorded.mismatch == divergence.mismatch
+            && (self.equivalent)(&recorded.left().1, &divergence.left().1)
+            && (self.equivalent)(&recorded.right().1, &divergence.right().1)
+    }
+
+    /// Runs every target with `input`. If any target's output isn't equivalent to the first
+    /// target's, the divergence is recorded unless it duplicates one already recorded or the list
+    /// is full, and an error is returned so that the driver counts the iteration as failed.
+    #[allow(clippy::result_unit_err)]
+    pub fn run(&mut self, input: &[u8]) -> Result<(), ()> {
+        let outputs: Vec<(String, O)> = self
+            .targets
+            .iter_mut()
+            .map(|(name, target)| (name.clone(), (target)(input)))
+            .collect();
+
+        let mismatch = outputs
+            .iter()
+            .enumerate()
+            .skip(1)
+            .find(|(_, (_, output))| !(self.equivalent)(&outputs[0].1, output))
+            .map(|(i, _)| (0, i));
+
+        let mismatch = match mismatch {
+            Some(mismatch) => mismatch,
+            None => return Ok(()),
+        };
+
+        let divergence = Divergence {
+            input: input.to_vec(),
+            outputs,
+            mismatch,
+            hits: 1,
+        };
+
+        let mut divergences = self.divergences.lock().unwrap();
+        if let Some(recorded) = divergences
+            .iter_mut()
+            .find(|recorded| self.is_duplicate(recorded, &divergence))
+        {
+            recorded.hits += 1;
+            return Err(());
+        }
+
+        warn!(
+            "targets `{}` and `{}` diverged",
+            divergence.left().0,
+            divergence.right().0
+        );
+
+        if divergences.len() < self.max_divergences {
+            divergences.push(divergence);
+        }
+
+        Err(())
+    }
+
+    /// Serializes `object` and runs every target with it. See [Differential::run].
+    #[allow(clippy::result_unit_err)]
+    pub fn run_object<T, E>(&mut self, object: &T) -> Result<(), ()>
+    where
+        T: BinarySerialize + SerializedSize,
+        E: ByteOrder,
+    {
+        let mut input = Vec::with_capacity(object.serialized_size());
+        object.binary_serialize::<_, E>(&mut input);
+
+        self.run(&input)
+    }
+}
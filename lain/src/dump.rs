@@ -0,0 +1,458 @@
+//! Annotated dumps of serialized objects for triage. A [Dump] serializes an object while recording
+//! the bytes that each of its fields occupies, which derived `BinarySerialize` implementations
+//! report as they write them. The dump can then be printed as a table of field paths and their
+//! bytes, or compared against the dump of another instance to find the fields that changed.
+//!
+//! ```ignore
+//! let dump = Dump::new::<_, BigEndian>(&packet);
+//! println!("{}", dump);
+//! // 0000  header.magic                 4C 41 49 4E
+//! // 0004  header.flags                 (2 bytes)
+//! // 0004  header.flags.urgent [0..1]   = 0x1
+//! // ...
+//!
+//! println!("{}", dump.diff(&Dump::new::<_, BigEndian>(&mutated_packet)));
+//! ```
+
+use crate::traits::BinarySerialize;
+use byteorder::ByteOrder;
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::io::Write;
+use std::ops::Range;
+
+thread_local! {
+    static RECORDER: RefCell<Option<Recorder>> = const { RefCell::new(None) };
+    // checked before touching `RECORDER` so that serializing outside of a dump stays cheap
+    static RECORDING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// The most bytes of a field that are printed on a single line
+const MAX_BYTES_PER_LINE: usize = 16;
+
+/// Where a bitfield's bits are within the bytes of its [FieldSpan]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BitSpan {
+    /// For `#[lain(bits = N)]` fields packed into an integer, the shift of the value within that
+    /// integer. For fields of a bit stream, the offset of the first bit within the first byte of
+    /// the span, in the stream's bit order.
+    pub offset: usize,
+    /// The number of bits
+    pub len: usize,
+    /// The value written to the bits
+    pub value: u128,
+}
+
+/// The bytes occupied by one field of a serialized object
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldSpan {
+    /// The path to the field from the dumped object, e.g. `header.flags`, `items[2].len` or
+    /// `Open.handle` for the fields of an enum variant
+    pub path: String,
+    /// How many fields this field is nested in
+    pub depth: usize,
+    /// The bytes of the serialized output written for this field
+    pub range: Range<usize>,
+    /// Set for bitfields, which share their bytes with their neighbours
+    pub bits: Option<BitSpan>,
+}
+
+impl FieldSpan {
+    fn label(&self) -> String {
+        match self.bits {
+            Some(ref bits) => format!(
+                "{} [{}..{}]",
+                self.path,
+                bits.offset,
+                bits.offset + bits.len
+            ),
+            None => self.path.clone(),
+        }
+    }
+}
+
+/// A serialized object along with the spans of its fields
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dump {
+    /// The serialized object
+    pub bytes: Vec<u8>,
+    /// Every field that was written, ordered by offset with parents before their children
+    pub fields: Vec<FieldSpan>,
+}
+
+impl Dump {
+    /// Serializes `value` and records where each of its fields was written
+    pub fn new<T: BinarySerialize + ?Sized, E: ByteOrder>(value: &T) -> Dump {
+        let recording = RecordingGuard::start();
+
+        let mut bytes = vec![];
+        value.binary_serialize::<_, E>(&mut RecordingWriter { inner: &mut bytes });
+
+        let mut fields = recording.finish();
+
+        // fields are recorded as they finish, so children come before their parents
+        fields.sort_by_key(|field| (field.range.start, field.depth));
+
+        Dump { bytes, fields }
+    }
+
+    /// Returns the span of the field at `path`
+    pub fn field(&self, path: &str) -> Option<&FieldSpan> {
+        self.fields.iter().find(|field| field.path == path)
+    }
+
+    /// Returns the serialized bytes of `field`
+    pub fn field_bytes(&self, field: &FieldSpan) -> &[u8] {
+        &self.bytes[field.range.clone()]
+    }
+
+    /// Returns the paths of the fields whose serialized bytes (or bitfield values) differ between
+    /// the two dumps, including fields which only exist in one of them
+    pub fn changed_fields<'a>(&'a self, other: &'a Dump) -> Vec<&'a str> {
+        self.diff_entries(other)
+            .into_iter()
+            .filter(|entry| entry.kind != DiffKind::Unchanged)
+            .map(|entry| entry.path)
+            .collect()
+    }
+
+    /// Prints the fields of both dumps. Changed fields are marked with `*` and show the old and
+    /// new bytes, while fields which only exist in `self` or `other` are marked with `-` and `+`.
+    pub fn diff(&self, other: &Dump) -> String {
+        let entries = self.diff_entries(other);
+        let width = entries
+            .iter()
+            .map(|entry| entry.span().label().len())
+            .max()
+            .unwrap_or(0);
+
+        let mut ret = String::new();
+        for entry in entries.iter() {
+            let (marker, span) = match entry.kind {
+                DiffKind::Unchanged => (' ', entry.old.unwrap()),
+                DiffKind::Changed => ('*', entry.old.unwrap()),
+                DiffKind::Removed => ('-', entry.old.unwrap()),
+                DiffKind::Added => ('+', entry.new.unwrap()),
+            };
+
+            let dump = if entry.kind == DiffKind::Added {
+                other
+            } else {
+                self
+            };
+
+            ret += &format!(
+                "{} {:04X}  {:<width$}  {}",
+                marker,
+                span.range.start,
+                span.label(),
+                dump.describe(span),
+                width = width
+            );
+
+            if entry.kind == DiffKind::Changed {
+                ret += &format!("  ->  {}", other.describe(entry.new.unwrap()));
+            }
+
+            ret += "\n";
+        }
+
+        ret
+    }
+
+    fn diff_entries<'a>(&'a self, other: &'a Dump) -> Vec<DiffEntry<'a>> {
+        let mut entries: Vec<DiffEntry> = self
+            .fields
+            .iter()
+            .map(|old| {
+                let new = other.field(&old.path);
+                let kind = match new {
+                    None => DiffKind::Removed,
+                    Some(new) if self.same_value(old, other, new) => DiffKind::Unchanged,
+                    Some(_) => DiffKind::Changed,
+                };
+
+                DiffEntry {
+                    path: &old.path,
+                    kind,
+                    old: Some(old),
+                    new,
+                }
+            })
+            .collect();
+
+        for new in other.fields.iter() {
+            if self.field(&new.path).is_none() {
+                entries.push(DiffEntry {
+                    path: &new.path,
+                    kind: DiffKind::Added,
+                    old: None,
+                    new: Some(new),
+                });
+            }
+        }
+
+        entries
+    }
+
+    fn same_value(&self, field: &FieldSpan, other: &Dump, other_field: &FieldSpan) -> bool {
+        match (&field.bits, &other_field.bits) {
+            (Some(bits), Some(other_bits)) => bits.value == other_bits.value,
+            (None, None) => self.field_bytes(field) == other.field_bytes(other_field),
+            _ => false,
+        }
+    }
+
+    fn has_children(&self, field: &FieldSpan) -> bool {
+        self.fields
+            .iter()
+            .any(|other| other.depth > field.depth && is_child(&field.path, &other.path))
+    }
+
+    /// The bytes of a field, its value for bitfields, or its size for fields containing others
+    fn describe(&self, field: &FieldSpan) -> String {
+        if let Some(ref bits) = field.bits {
+            return format!("= {:#X}", bits.value);
+        }
+
+        if self.has_children(field) {
+            return format!("({} bytes)", field.range.len());
+        }
+
+        let bytes = self.field_bytes(field);
+        let mut ret = bytes
+            .iter()
+            .take(MAX_BYTES_PER_LINE)
+            .map(|b| format!("{:02X}", b))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        if bytes.len() > MAX_BYTES_PER_LINE {
+            ret += &format!(" .. ({} bytes)", bytes.len());
+        }
+
+        ret
+    }
+}
+
+/// Prints one line per field with its offset, path and bytes
+impl fmt::Display for Dump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self
+            .fields
+            .iter()
+            .map(|field| field.label().len())
+            .max()
+            .unwrap_or(0);
+
+        for field in self.fields.iter() {
+            writeln!(
+                f,
+                "{:04X}  {:<width$}  {}",
+                field.range.start,
+                field.label(),
+                self.describe(field),
+                width = width
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum DiffKind {
+    Unchanged,
+    Changed,
+    Removed,
+    Added,
+}
+
+struct DiffEntry<'a> {
+    path: &'a str,
+    kind: DiffKind,
+    old: Option<&'a FieldSpan>,
+    new: Option<&'a FieldSpan>,
+}
+
+impl<'a> DiffEntry<'a> {
+    fn span(&self) -> &'a FieldSpan {
+        self.old.or(self.new).unwrap()
+    }
+}
+
+fn is_child(parent: &str, path: &str) -> bool {
+    path.len() > parent.len()
+        && path.starts_with(parent)
+        && matches!(path.as_bytes()[parent.len()], b'.' | b'[')
+}
+
+#[derive(Default)]
+struct Recorder {
+    /// Bytes written so far
+    position: usize,
+    /// Fields which are being written, along with where they started
+    open: Vec<(String, usize)>,
+    fields: Vec<FieldSpan>,
+}
+
+impl Recorder {
+    fn path(&self, name: &str) -> String {
+        match self.open.last() {
+            Some((parent, _)) if name.starts_with('[') => format!("{}{}", parent, name),
+            Some((parent, _)) => format!("{}.{}", parent, name),
+            None => name.to_string(),
+        }
+    }
+
+    fn record_bits(&mut self, name: &str, range: Range<usize>, bits: BitSpan) {
+        self.fields.push(FieldSpan {
+            path: self.path(name),
+            depth: self.open.len(),
+            range,
+            bits: Some(bits),
+        });
+    }
+}
+
+struct RecordingWriter<'a> {
+    inner: &'a mut Vec<u8>,
+}
+
+impl<'a> Write for RecordingWriter<'a> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.inner.extend_from_slice(buf);
+        with_recorder(|recorder| recorder.position += buf.len());
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Stops recording when dropped, so that a panic while serializing doesn't leave this thread
+/// recording
+struct RecordingGuard;
+
+impl RecordingGuard {
+    fn start() -> RecordingGuard {
+        RECORDER.with(|r| *r.borrow_mut() = Some(Recorder::default()));
+        RECORDING.with(|r| r.set(true));
+
+        RecordingGuard
+    }
+
+    /// Stops recording and returns the recorded fields
+    fn finish(self) -> Vec<FieldSpan> {
+        RECORDER
+            .with(|r| r.borrow_mut().take())
+            .map(|recorder| recorder.fields)
+            .unwrap_or_default()
+    }
+}
+
+impl Drop for RecordingGuard {
+    fn drop(&mut self) {
+        RECORDING.with(|r| r.set(false));
+        RECORDER.with(|r| r.borrow_mut().take());
+    }
+}
+
+/// Returns whether a [Dump] is being recorded on this thread. Manual `BinarySerialize`
+/// implementations may use this to skip reporting their fields.
+#[inline]
+pub fn is_recording() -> bool {
+    RECORDING.with(Cell::get)
+}
+
+#[inline]
+fn with_recorder<F: FnOnce(&mut Recorder)>(f: F) {
+    if !is_recording() {
+        return;
+    }
+
+    RECORDER.with(|r| {
+        if let Some(recorder) = r.borrow_mut().as_mut() {
+            f(recorder);
+        }
+    });
+}
+
+/// Marks the start of a field. Called by derived `BinarySerialize` implementations.
+#[doc(hidden)]
+#[inline]
+pub fn enter_field(name: &str) {
+    with_recorder(|recorder| {
+        let path = recorder.path(name);
+        let position = recorder.position;
+        recorder.open.push((path, position));
+    });
+}
+
+/// Marks the start of an element of a sequence
+#[doc(hidden)]
+#[inline]
+pub fn enter_index(idx: usize) {
+    with_recorder(|recorder| {
+        let path = recorder.path(&format!("[{}]", idx));
+        let position = recorder.position;
+        recorder.open.push((path, position));
+    });
+}
+
+/// Marks the end of the field or element most recently entered
+#[doc(hidden)]
+#[inline]
+pub fn exit_field() {
+    with_recorder(|recorder| {
+        if let Some((path, start)) = recorder.open.pop() {
+            recorder.fields.push(FieldSpan {
+                path,
+                depth: recorder.open.len(),
+                range: start..recorder.position,
+                bits: None,
+            });
+        }
+    });
+}
+
+/// Records a `#[lain(bits = N)]` field packed into an integer of `size` bytes, which will be
+/// written once the integer's final field has been set
+#[doc(hidden)]
+#[inline]
+pub fn record_bitfield(name: &str, size: usize, shift: usize, bits: usize, value: u128) {
+    with_recorder(|recorder| {
+        let range = recorder.position..recorder.position + size;
+        recorder.record_bits(
+            name,
+            range,
+            BitSpan {
+                offset: shift,
+                len: bits,
+                value,
+            },
+        );
+    });
+}
+
+/// Records a field of a bit stream which starts `bit_offset` bits into the stream. The stream
+/// is written once its final field has been added.
+#[doc(hidden)]
+#[inline]
+pub fn record_bit_stream_field(name: &str, bit_offset: usize, bits: usize, value: u128) {
+    with_recorder(|recorder| {
+        let start = recorder.position + bit_offset / 8;
+        let end = recorder.position + (bit_offset + bits).div_ceil(8);
+        recorder.record_bits(
+            name,
+            start..end,
+            BitSpan {
+                offset: bit_offset % 8,
+                len: bits,
+                value,
+            },
+        );
+    });
+}
@@ -0,0 +1,442 @@
+//! Wire encodings for variable-length fields. These back the `#[lain(prefix = "u16")]`,
+//! `#[lain(nul_terminated)]` and `#[lain(utf16le)]` field attributes of the `BinarySerialize`
+//! derive, but may also be used directly by manual `BinarySerialize` implementations.
+//!
+//! A length prefix holds the number of elements in the field: bytes for `String`, `&str`,
+//! `Utf8String` and `AsciiString`, UTF-16 code units for strings encoded with `utf16le`, and items
+//! for `Vec<T>` and `[T; N]`. The prefix never counts the terminator.
+//!
+//! Prefixes written for the `prefix` attribute are always exact. Derived `NewFuzzed` and
+//! `Mutatable` implementations keep prefixed fields short enough for their prefix type (see
+//! [fit_to_prefix]). Use [LengthPrefixed][crate::types::LengthPrefixed] for a length prefix which
+//! mutation may make disagree with the data that follows it.
+
+use crate::traits::{BinarySerialize, DangerousNumber, SerializedSize};
+use crate::types::{AsciiString, LengthPrefixed, Utf8String};
+use byteorder::{ByteOrder, LittleEndian};
+use num::{Bounded, NumCast};
+use num_traits::{WrappingAdd, WrappingSub};
+use rand::Rng;
+use std::io::Write;
+
+#[cfg(feature = "serde_support")]
+use serde::{Deserialize, Serialize};
+
+/// Options controlling how a sequence is written to the wire
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub struct Encoding {
+    /// Write strings as UTF-16LE code units rather than UTF-8. Ignored for non-string types.
+    pub utf16le: bool,
+    /// Write a zeroed element after the data
+    pub nul_terminated: bool,
+}
+
+/// A sequence which may be written with a length prefix, terminator, or alternate string encoding
+pub trait EncodedSequence {
+    /// The number of elements that a length prefix should hold
+    fn encoded_len(&self, encoding: Encoding) -> usize;
+
+    /// The number of bytes written by [EncodedSequence::encoded_serialize], including the terminator
+    fn encoded_size(&self, encoding: Encoding) -> usize;
+
+    /// Serializes the sequence and its terminator (if any) and returns the number of bytes written
+    fn encoded_serialize<W: Write, E: ByteOrder>(
+        &self,
+        encoding: Encoding,
+        buffer: &mut W,
+    ) -> usize;
+
+    /// The size of the terminator written for this type
+    fn terminator_size(encoding: Encoding) -> usize;
+
+    /// Shortens the sequence so that [EncodedSequence::encoded_len] is at most `max_len`. Fixed
+    /// size sequences are left as-is.
+    fn truncate_encoded(&mut self, max_len: usize, encoding: Encoding);
+}
+
+/// Integer types which may be used as a length prefix. `()` represents the absence of a prefix.
+pub trait LengthPrefix {
+    /// The size of the prefix on the wire
+    const SIZE: usize;
+
+    /// The largest length the prefix can hold
+    const MAX_LEN: usize;
+
+    /// Writes `len` as a prefix and returns the number of bytes written.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `len` is larger than [LengthPrefix::MAX_LEN].
+    fn serialize_len<W: Write, E: ByteOrder>(len: usize, buffer: &mut W) -> usize;
+}
+
+impl LengthPrefix for () {
+    const SIZE: usize = 0;
+    const MAX_LEN: usize = usize::MAX;
+
+    #[inline(always)]
+    fn serialize_len<W: Write, E: ByteOrder>(_len: usize, _buffer: &mut W) -> usize {
+        0
+    }
+}
+
+macro_rules! impl_length_prefix {
+    ( $($name:ident),* ) => {
+        $(
+            impl LengthPrefix for $name {
+                const SIZE: usize = std::mem::size_of::<$name>();
+                const MAX_LEN: usize = if std::mem::size_of::<$name>() < std::mem::size_of::<usize>() {
+                    $name::MAX as usize
+                } else {
+                    usize::MAX
+                };
+
+                fn serialize_len<W: Write, E: ByteOrder>(len: usize, buffer: &mut W) -> usize {
+                    let prefix: $name = num::cast(len).unwrap_or_else(|| {
+                        panic!("length {} does not fit in a {} prefix", len, stringify!($name))
+                    });
+
+                    prefix.binary_serialize::<_, E>(buffer)
+                }
+            }
+        )*
+    }
+}
+
+impl_length_prefix!(u8, u16, u32, u64);
+
+/// Truncates `value` if its length does not fit in a prefix of type `P`. Derived `NewFuzzed` and
+/// `Mutatable` implementations call this on fields with a `prefix` attribute after generating or
+/// mutating them.
+pub fn fit_to_prefix<P: LengthPrefix, T: EncodedSequence>(value: &mut T, encoding: Encoding) {
+    if value.encoded_len(encoding) > P::MAX_LEN {
+        value.truncate_encoded(P::MAX_LEN, encoding);
+    }
+}
+
+/// Picks a length prefix which does not match `len`, the actual length of the data
+pub(crate) fn mismatched_prefix<P, R>(len: usize, rng: &mut R) -> P
+where
+    P: NumCast + Bounded + Copy + WrappingAdd + WrappingSub + DangerousNumber<P>,
+    R: Rng,
+{
+    // callers keep the length within the prefix range, so this only saturates for hand-built
+    // values
+    let exact: P = num::cast(len).unwrap_or_else(P::max_value);
+
+    let one: P = num::cast(1u8).unwrap();
+    match rng.gen_range(0, 5) {
+        0 => exact.wrapping_add(&one),
+        1 => exact.wrapping_sub(&one),
+        2 => num::cast(0u8).unwrap(),
+        3 => P::max_value(),
+        _ => P::select_dangerous_number(rng),
+    }
+}
+
+/// Serializes `value` preceded by a length prefix of type `P` using the given encoding. This is
+/// used by the `BinarySerialize` derive.
+///
+/// # Panics
+///
+/// Panics if the length of `value` does not fit in `P`.
+pub fn serialize_encoded<P, T, W, E>(value: &T, encoding: Encoding, buffer: &mut W) -> usize
+where
+    P: LengthPrefix,
+    T: EncodedSequence,
+    W: Write,
+    E: ByteOrder,
+{
+    let prefix_size = P::serialize_len::<W, E>(value.encoded_len(encoding), buffer);
+
+    prefix_size + value.encoded_serialize::<W, E>(encoding, buffer)
+}
+
+/// The number of bytes [serialize_encoded] writes for `value`
+pub fn encoded_serialized_size<P: LengthPrefix, T: EncodedSequence>(
+    value: &T,
+    encoding: Encoding,
+) -> usize {
+    P::SIZE + value.encoded_size(encoding)
+}
+
+fn str_encoded_len(s: &str, encoding: Encoding) -> usize {
+    if encoding.utf16le {
+        s.encode_utf16().count()
+    } else {
+        s.len()
+    }
+}
+
+fn str_terminator_size(encoding: Encoding) -> usize {
+    match (encoding.nul_terminated, encoding.utf16le) {
+        (false, _) => 0,
+        (true, false) => 1,
+        (true, true) => 2,
+    }
+}
+
+fn str_encoded_size(s: &str, encoding: Encoding) -> usize {
+    let unit_size = if encoding.utf16le { 2 } else { 1 };
+
+    str_encoded_len(s, encoding) * unit_size + str_terminator_size(encoding)
+}
+
+fn str_encoded_serialize<W: Write>(s: &str, encoding: Encoding, buffer: &mut W) -> usize {
+    if !encoding.utf16le {
+        return bytes_encoded_serialize(s.as_bytes(), encoding, buffer);
+    }
+
+    let mut bytes_written = 0;
+    for unit in s.encode_utf16() {
+        bytes_written += unit.binary_serialize::<_, LittleEndian>(buffer);
+    }
+
+    if encoding.nul_terminated {
+        bytes_written += 0u16.binary_serialize::<_, LittleEndian>(buffer);
+    }
+
+    bytes_written
+}
+
+/// The byte index to cut `s` at so that its encoded length is at most `max_len` without splitting
+/// a character
+fn str_truncate_index(s: &str, max_len: usize, encoding: Encoding) -> usize {
+    if !encoding.utf16le {
+        let mut end = std::cmp::min(max_len, s.len());
+        while !s.is_char_boundary(end) {
+            end -= 1;
+        }
+
+        return end;
+    }
+
+    let mut units = 0;
+    for (idx, c) in s.char_indices() {
+        units += c.len_utf16();
+        if units > max_len {
+            return idx;
+        }
+    }
+
+    s.len()
+}
+
+fn truncate_string(s: &mut String, end: usize) {
+    s.truncate(end);
+}
+
+fn truncate_str(s: &mut &str, end: usize) {
+    let full: &str = s;
+    *s = &full[..end];
+}
+
+fn truncate_ascii_string(s: &mut AsciiString, end: usize) {
+    s.inner.truncate(end);
+}
+
+fn bytes_encoded_serialize<W: Write>(bytes: &[u8], encoding: Encoding, buffer: &mut W) -> usize {
+    let mut bytes_written = buffer.write(bytes).unwrap();
+
+    if encoding.nul_terminated {
+        bytes_written += buffer.write(&[0]).unwrap();
+    }
+
+    bytes_written
+}
+
+macro_rules! impl_encoded_str {
+    ( $($name:ty => $truncate:ident),* ) => {
+        $(
+            impl EncodedSequence for $name {
+                fn encoded_len(&self, encoding: Encoding) -> usize {
+                    str_encoded_len(self, encoding)
+                }
+
+                fn encoded_size(&self, encoding: Encoding) -> usize {
+                    str_encoded_size(self, encoding)
+                }
+
+                fn encoded_serialize<W: Write, E: ByteOrder>(&self, encoding: Encoding, buffer: &mut W) -> usize {
+                    str_encoded_serialize(self, encoding, buffer)
+                }
+
+                fn terminator_size(encoding: Encoding) -> usize {
+                    str_terminator_size(encoding)
+                }
+
+                fn truncate_encoded(&mut self, max_len: usize, encoding: Encoding) {
+                    let end = str_truncate_index(self, max_len, encoding);
+                    $truncate(self, end);
+                }
+            }
+        )*
+    }
+}
+
+impl_encoded_str!(
+    String => truncate_string,
+    &str => truncate_str,
+    AsciiString => truncate_ascii_string
+);
+
+/// `Utf8String` may hold malformed UTF-8, which is written as-is unless `utf16le` is requested. In
+/// that case malformed sequences are replaced with U+FFFD before encoding. Truncation always cuts
+/// the raw bytes, since the data never has more UTF-16 code units than bytes.
+impl EncodedSequence for Utf8String {
+    fn encoded_len(&self, encoding: Encoding) -> usize {
+        if encoding.utf16le {
+            str_encoded_len(&self.to_string_lossy(), encoding)
+        } else {
+            self.len()
+        }
+    }
+
+    fn encoded_size(&self, encoding: Encoding) -> usize {
+        if encoding.utf16le {
+            str_encoded_size(&self.to_string_lossy(), encoding)
+        } else {
+            self.len() + str_terminator_size(encoding)
+        }
+    }
+
+    fn encoded_serialize<W: Write, E: ByteOrder>(
+        &self,
+        encoding: Encoding,
+        buffer: &mut W,
+    ) -> usize {
+        if encoding.utf16le {
+            str_encoded_serialize(&self.to_string_lossy(), encoding, buffer)
+        } else {
+            bytes_encoded_serialize(self.as_bytes(), encoding, buffer)
+        }
+    }
+
+    fn terminator_size(encoding: Encoding) -> usize {
+        str_terminator_size(encoding)
+    }
+
+    fn truncate_encoded(&mut self, max_len: usize, _encoding: Encoding) {
+        self.inner.truncate(max_len);
+    }
+}
+
+fn slice_encoded_serialize<T, W, E>(items: &[T], encoding: Encoding, buffer: &mut W) -> usize
+where
+    T: BinarySerialize + SerializedSize,
+    W: Write,
+    E: ByteOrder,
+{
+    let mut bytes_written = items.binary_serialize::<W, E>(buffer);
+
+    if encoding.nul_terminated {
+        for _ in 0..T::min_nonzero_elements_size() {
+            bytes_written += buffer.write(&[0]).unwrap();
+        }
+    }
+
+    bytes_written
+}
+
+/// The terminator of a `Vec<T>` or `[T; N]` is `T::min_nonzero_elements_size()` zero bytes.
+impl<T> EncodedSequence for Vec<T>
+where
+    T: BinarySerialize + SerializedSize,
+{
+    fn encoded_len(&self, _encoding: Encoding) -> usize {
+        self.len()
+    }
+
+    fn encoded_size(&self, encoding: Encoding) -> usize {
+        self.serialized_size() + Self::terminator_size(encoding)
+    }
+
+    fn encoded_serialize<W: Write, E: ByteOrder>(
+        &self,
+        encoding: Encoding,
+        buffer: &mut W,
+    ) -> usize {
+        slice_encoded_serialize::<T, W, E>(self, encoding, buffer)
+    }
+
+    fn terminator_size(encoding: Encoding) -> usize {
+        if encoding.nul_terminated {
+            T::min_nonzero_elements_size()
+        } else {
+            0
+        }
+    }
+
+    fn truncate_encoded(&mut self, max_len: usize, _encoding: Encoding) {
+        self.truncate(max_len);
+    }
+}
+
+impl<T, const N: usize> EncodedSequence for [T; N]
+where
+    T: BinarySerialize + SerializedSize,
+{
+    fn encoded_len(&self, _encoding: Encoding) -> usize {
+        N
+    }
+
+    fn encoded_size(&self, encoding: Encoding) -> usize {
+        self.serialized_size() + Self::terminator_size(encoding)
+    }
+
+    fn encoded_serialize<W: Write, E: ByteOrder>(
+        &self,
+        encoding: Encoding,
+        buffer: &mut W,
+    ) -> usize {
+        slice_encoded_serialize::<T, W, E>(self, encoding, buffer)
+    }
+
+    fn terminator_size(encoding: Encoding) -> usize {
+        if encoding.nul_terminated {
+            T::min_nonzero_elements_size()
+        } else {
+            0
+        }
+    }
+
+    fn truncate_encoded(&mut self, _max_len: usize, _encoding: Encoding) {}
+}
+
+/// The length prefix is written before the value, and counts elements of the value using the same
+/// encoding
+impl<P, T> EncodedSequence for LengthPrefixed<P, T>
+where
+    P: LengthPrefix + BinarySerialize,
+    T: EncodedSequence,
+{
+    fn encoded_len(&self, encoding: Encoding) -> usize {
+        self.value.encoded_len(encoding)
+    }
+
+    fn encoded_size(&self, encoding: Encoding) -> usize {
+        P::SIZE + self.value.encoded_size(encoding)
+    }
+
+    fn encoded_serialize<W: Write, E: ByteOrder>(
+        &self,
+        encoding: Encoding,
+        buffer: &mut W,
+    ) -> usize {
+        let prefix_size = match self.len_override {
+            Some(ref len) => len.binary_serialize::<W, E>(buffer),
+            None => P::serialize_len::<W, E>(self.value.encoded_len(encoding), buffer),
+        };
+
+        prefix_size + self.value.encoded_serialize::<W, E>(encoding, buffer)
+    }
+
+    fn terminator_size(encoding: Encoding) -> usize {
+        T::terminator_size(encoding)
+    }
+
+    fn truncate_encoded(&mut self, max_len: usize, encoding: Encoding) {
+        self.value.truncate_encoded(max_len, encoding);
+    }
+}
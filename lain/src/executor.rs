@@ -0,0 +1,432 @@
+//! Helpers for running a standalone target binary as a child process.
+//!
+//! A [CommandExecutor] feeds a serialized input to the target through stdin, a file, or the
+//! argument list, enforces a timeout, and classifies how the process ended. The result can be
+//! returned directly from a fuzzer callback using [ExecutionResult::as_iteration_result].
+use crate::traits::{BinarySerialize, SerializedSize};
+use byteorder::ByteOrder;
+use std::ffi::{OsStr, OsString};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Arguments equal to this string are replaced with the input file path ([InputDelivery::File])
+/// or with the input itself ([InputDelivery::Argv]). This matches AFL's convention.
+pub const INPUT_PLACEHOLDER: &str = "@@";
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+const DEFAULT_MAX_OUTPUT_SIZE: usize = 0x10000;
+
+/// How long to wait for the target's stdout and stderr to be closed after it has exited. A
+/// process the target spawned may have inherited them and keep them open indefinitely.
+const OUTPUT_GRACE_PERIOD: Duration = Duration::from_millis(100);
+
+static INPUT_FILE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// How the input is handed to the target process.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum InputDelivery {
+    /// The input is written to the target's stdin, which is then closed
+    Stdin,
+    /// The input is written to a file whose path replaces [INPUT_PLACEHOLDER] in the arguments.
+    /// If no argument is a placeholder, the path is appended to the arguments.
+    File,
+    /// The input replaces [INPUT_PLACEHOLDER] in the arguments. If no argument is a
+    /// placeholder, the input is appended to the arguments.
+    ///
+    /// Arguments can't contain NUL bytes, so the input is cut off at its first NUL byte (which is
+    /// also where a C target would consider the argument to end) and
+    /// [ExecutionResult::input_truncated] is set.
+    Argv,
+}
+
+/// Classification of how a target process ended.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ExitKind {
+    /// The process exited with a status of 0
+    Ok,
+    /// The process exited normally with the given non-zero status
+    NonZeroExit(i32),
+    /// The process was terminated by the given signal (e.g. SIGSEGV or SIGABRT) or, on Windows,
+    /// exited with an NTSTATUS exception code
+    Crash(i32),
+    /// The process did not exit before the timeout and was killed
+    Timeout,
+}
+
+impl ExitKind {
+    /// Classifies an exit status returned by the OS
+    pub fn from_status(status: ExitStatus) -> ExitKind {
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::ExitStatusExt;
+
+            if let Some(signal) = status.signal() {
+                return ExitKind::Crash(signal);
+            }
+        }
+
+        match status.code() {
+            Some(0) => ExitKind::Ok,
+            // NTSTATUS error codes (e.g. 0xC0000005 STATUS_ACCESS_VIOLATION) have the high two bits set
+            #[cfg(windows)]
+            Some(code) if (code as u32) >= 0xC000_0000 => ExitKind::Crash(code),
+            Some(code) => ExitKind::NonZeroExit(code),
+            None => ExitKind::Crash(0),
+        }
+    }
+
+    /// Returns whether the process crashed
+    pub fn is_crash(&self) -> bool {
+        matches!(self, ExitKind::Crash(_))
+    }
+
+    /// Returns whether the process crashed or timed out
+    pub fn is_failure(&self) -> bool {
+        matches!(self, ExitKind::Crash(_) | ExitKind::Timeout)
+    }
+}
+
+/// The outcome of running the target once.
+#[derive(Debug, Clone)]
+pub struct ExecutionResult {
+    pub exit_kind: ExitKind,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    pub duration: Duration,
+    /// Set when only part of the input could be delivered to the target, e.g. because an
+    /// [InputDelivery::Argv] input contained a NUL byte
+    pub input_truncated: bool,
+}
+
+impl ExecutionResult {
+    /// Converts this result into what a fuzzer callback should return: crashes and timeouts are
+    /// reported as an error so that the driver counts them as failed iterations.
+    #[allow(clippy::result_unit_err)]
+    pub fn as_iteration_result(&self) -> Result<(), ()> {
+        if self.exit_kind.is_failure() {
+            Err(())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Runs a target binary with a single input per execution.
+///
+/// Executions take `&mut self` since every execution with [InputDelivery::File] rewrites the
+/// same input file. Give each fuzzer thread its own executor.
+///
+/// ```compile_fail
+/// let mut executor = CommandExecutor::new("./target");
+/// executor
+///     .args(&["--parse", "@@"])
+///     .input_delivery(InputDelivery::File)
+///     .timeout(Duration::from_millis(500));
+///
+/// let result = executor.execute_object::<_, LittleEndian>(&packet)?;
+/// result.as_iteration_result()
+/// ```
+#[derive(Debug)]
+pub struct CommandExecutor {
+    program: OsString,
+    args: Vec<OsString>,
+    delivery: InputDelivery,
+    timeout: Duration,
+    max_output_size: usize,
+    input_file: PathBuf,
+    remove_input_file: bool,
+}
+
+impl CommandExecutor {
+    /// Creates a new executor for `program` which delivers inputs over stdin with a timeout of
+    /// 1 second
+    pub fn new<S: AsRef<OsStr>>(program: S) -> CommandExecutor {
+        let input_file = std::env::temp_dir().join(format!(
+            "lain_input_{}_{}",
+            std::process::id(),
+            INPUT_FILE_COUNTER.fetch_add(1, Ordering::SeqCst)
+        ));
+
+        CommandExecutor {
+            program: program.as_ref().to_owned(),
+            args: Vec::new(),
+            delivery: InputDelivery::Stdin,
+            timeout: DEFAULT_TIMEOUT,
+            max_output_size: DEFAULT_MAX_OUTPUT_SIZE,
+            input_file,
+            remove_input_file: true,
+        }
+    }
+
+    /// Adds an argument to pass to the target
+    pub fn arg<S: AsRef<OsStr>>(&mut self, arg: S) -> &mut CommandExecutor {
+        self.args.push(arg.as_ref().to_owned());
+        self
+    }
+
+    /// Adds multiple arguments to pass to the target
+    pub fn args<I, S>(&mut self, args: I) -> &mut CommandExecutor
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        for arg in args {
+            self.arg(arg);
+        }
+        self
+    }
+
+    /// Sets how the input is delivered to the target
+    pub fn input_delivery(&mut self, delivery: InputDelivery) -> &mut CommandExecutor {
+        self.delivery = delivery;
+        self
+    }
+
+    /// Sets the max duration the target may run before it is killed
+    pub fn timeout(&mut self, timeout: Duration) -> &mut CommandExecutor {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets the max number of bytes captured from each of the target's stdout and stderr. Any
+    /// further output is read and discarded.
+    pub fn max_output_size(&mut self, max_output_size: usize) -> &mut CommandExecutor {
+        self.max_output_size = max_output_size;
+        self
+    }
+
+    /// Overrides the path used for [InputDelivery::File]. By default a unique file in the
+    /// system's temp directory is used and removed when the executor is dropped.
+    pub fn input_file<P: AsRef<Path>>(&mut self, path: P) -> &mut CommandExecutor {
+        self.input_file = path.as_ref().to_owned();
+        self.remove_input_file = false;
+        self
+    }
+
+    /// Serializes `object` and runs the target with it
+    pub fn execute_object<T, E>(&mut self, object: &T) -> io::Result<ExecutionResult>
+    where
+        T: BinarySerialize + SerializedSize,
+        E: ByteOrder,
+    {
+        let mut input = Vec::with_capacity(object.serialized_size());
+        object.binary_serialize::<_, E>(&mut input);
+
+        self.execute(&input)
+    }
+
+    /// Runs the target once with `input`
+    pub fn execute(&mut self, input: &[u8]) -> io::Result<ExecutionResult> {
+        let mut command = Command::new(&self.program);
+        command.stdout(Stdio::piped()).stderr(Stdio::piped());
+
+        let mut input_truncated = false;
+        let substitute = match self.delivery {
+            InputDelivery::Stdin => None,
+            InputDelivery::File => {
+                std::fs::write(&self.input_file, input)?;
+                Some(self.input_file.as_os_str().to_owned())
+            }
+            InputDelivery::Argv => {
+                let len = input.iter().position(|b| *b == 0).unwrap_or(input.len());
+                input_truncated = len < input.len();
+
+                Some(bytes_to_os_string(&input[..len]))
+            }
+        };
+
+        match substitute {
+            Some(substitute) => {
+                let mut substituted = false;
+                for arg in &self.args {
+                    if arg == INPUT_PLACEHOLDER {
+                        command.arg(&substitute);
+                        substituted = true;
+                    } else {
+                        command.arg(arg);
+                    }
+                }
+
+                if !substituted {
+                    command.arg(&substitute);
+                }
+
+                command.stdin(Stdio::null());
+            }
+            None => {
+                command.args(&self.args);
+                command.stdin(Stdio::piped());
+            }
+        }
+
+        // the target gets its own process group so that processes it spawns can be killed along
+        // with it on timeout
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+
+            command.process_group(0);
+        }
+
+        let start = Instant::now();
+        let mut child = command.spawn()?;
+
+        // stdin/stdout/stderr are serviced on their own threads so that a target which doesn't
+        // read its input or fills an output pipe can't deadlock us. The stdin writer isn't joined
+        // since a process spawned by the target may hold on to stdin without reading it.
+        if let Some(mut stdin) = child.stdin.take() {
+            let input = input.to_vec();
+            thread::spawn(move || {
+                // the target may exit without reading all of its input
+                let _ = stdin.write_all(&input);
+            });
+        }
+        let max_output_size = self.max_output_size;
+        let stdout_reader = child
+            .stdout
+            .take()
+            .map(|stdout| spawn_reader(stdout, max_output_size));
+        let stderr_reader = child
+            .stderr
+            .take()
+            .map(|stderr| spawn_reader(stderr, max_output_size));
+
+        let exit_kind = match wait_with_timeout(&mut child, self.timeout)? {
+            Some(status) => ExitKind::from_status(status),
+            None => {
+                // the process may have exited between the last poll and now
+                kill_process_group(&mut child);
+                child.wait()?;
+
+                ExitKind::Timeout
+            }
+        };
+
+        let duration = start.elapsed();
+        let output_deadline = Instant::now() + OUTPUT_GRACE_PERIOD;
+
+        Ok(ExecutionResult {
+            exit_kind,
+            stdout: join_reader(stdout_reader, output_deadline),
+            stderr: join_reader(stderr_reader, output_deadline),
+            duration,
+            input_truncated,
+        })
+    }
+}
+
+impl Drop for CommandExecutor {
+    fn drop(&mut self) {
+        if self.remove_input_file && self.input_file.exists() {
+            let _ = std::fs::remove_file(&self.input_file);
+        }
+    }
+}
+
+/// Polls `child` until it exits or `timeout` elapses. Returns `None` on timeout.
+fn wait_with_timeout(child: &mut Child, timeout: Duration) -> io::Result<Option<ExitStatus>> {
+    const MAX_POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+    let deadline = Instant::now() + timeout;
+    let mut poll_interval = Duration::from_micros(50);
+
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+
+        let now = Instant::now();
+        if now >= deadline {
+            return Ok(None);
+        }
+
+        thread::sleep(std::cmp::min(poll_interval, deadline - now));
+        poll_interval = std::cmp::min(poll_interval * 2, MAX_POLL_INTERVAL);
+    }
+}
+
+/// Kills the target along with every process in its process group, so that processes it spawned
+/// don't keep its pipes open
+#[cfg(unix)]
+fn kill_process_group(child: &mut Child) {
+    // the target's process group ID is its PID since it was spawned with `process_group(0)`
+    unsafe {
+        libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+    }
+
+    let _ = child.kill();
+}
+
+#[cfg(not(unix))]
+fn kill_process_group(child: &mut Child) {
+    let _ = child.kill();
+}
+
+/// Output read from one of the target's pipes, and a channel which is closed once the pipe is
+/// closed
+type OutputReader = (Arc<Mutex<Vec<u8>>>, Receiver<()>);
+
+/// Reads from `reader` on a new thread until the pipe is closed. At most `max_size` bytes are
+/// kept in total, even after they have been taken by [join_reader], and the rest is discarded so
+/// that the writer doesn't block.
+fn spawn_reader<R: Read + Send + 'static>(mut reader: R, max_size: usize) -> OutputReader {
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let (done, finished) = mpsc::channel::<()>();
+
+    let thread_output = output.clone();
+    thread::spawn(move || {
+        // keeps the channel open until the pipe has been closed
+        let _done = done;
+        let mut buffer = [0u8; 4096];
+        let mut stored = 0;
+
+        while let Ok(len) = reader.read(&mut buffer) {
+            if len == 0 {
+                break;
+            }
+
+            let len = std::cmp::min(len, max_size - stored);
+            if len > 0 {
+                thread_output
+                    .lock()
+                    .unwrap()
+                    .extend_from_slice(&buffer[..len]);
+                stored += len;
+            }
+        }
+    });
+
+    (output, finished)
+}
+
+/// Waits until `deadline` for the pipe to be closed and returns whatever was read from it. The
+/// reader thread is left behind if the pipe is still open.
+fn join_reader(reader: Option<OutputReader>, deadline: Instant) -> Vec<u8> {
+    let (output, finished) = match reader {
+        Some(reader) => reader,
+        None => return Vec::new(),
+    };
+
+    let _ = finished.recv_timeout(deadline.saturating_duration_since(Instant::now()));
+
+    let output = std::mem::take(&mut *output.lock().unwrap());
+    output
+}
+
+#[cfg(unix)]
+fn bytes_to_os_string(bytes: &[u8]) -> OsString {
+    use std::os::unix::ffi::OsStrExt;
+
+    OsStr::from_bytes(bytes).to_owned()
+}
+
+#[cfg(not(unix))]
+fn bytes_to_os_string(bytes: &[u8]) -> OsString {
+    OsString::from(String::from_utf8_lossy(bytes).into_owned())
+}
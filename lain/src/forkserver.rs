@@ -0,0 +1,484 @@
+//! An executor which talks to a target over AFL's fork server protocol.
+//!
+//! Rather than spawning a new process per input, the target is started once and stops at the
+//! fork server. For every execution the fork server `fork()`s a fresh child which runs the input
+//! and reports its exit status back over a pipe. This avoids the `execve()` and dynamic loading
+//! cost on every iteration.
+//!
+//! The protocol is the classic AFL handshake: the target reads 4-byte requests from fd 198 and
+//! writes the 4-byte hello, child pid, and wait status to fd 199. Rust targets can call
+//! [start_forkserver] at the top of `main()`.
+//!
+//! Only the classic handshake is supported. Targets instrumented by AFL and AFL++ releases before
+//! 4.0 work as-is, but AFL++ 4.0 and later open with a versioned hello that negotiates options
+//! such as shared memory input delivery, which this executor does not implement.
+use crate::executor::{ExecutionResult, ExitKind, InputDelivery, INPUT_PLACEHOLDER};
+use crate::traits::{BinarySerialize, SerializedSize};
+use byteorder::ByteOrder;
+use std::ffi::{OsStr, OsString};
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+/// The fd the fork server reads requests from. The fork server writes responses to
+/// `FORKSRV_FD + 1`.
+pub const FORKSRV_FD: RawFd = 198;
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+const FORKSERVER_INIT_TIMEOUT: Duration = Duration::from_secs(10);
+
+static INPUT_FILE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Runs the fork server loop in the target process. This should be called as early as possible in
+/// the target's `main()` and before any threads are spawned.
+///
+/// If the process was not started by a [ForkserverExecutor] this returns immediately. Otherwise
+/// this only returns in forked children, which should go on to process a single input and exit.
+pub fn start_forkserver() {
+    let hello = [0u8; 4];
+
+    unsafe {
+        if libc::write(FORKSRV_FD + 1, hello.as_ptr() as *const libc::c_void, 4) != 4 {
+            return;
+        }
+
+        loop {
+            let mut was_killed = [0u8; 4];
+            if libc::read(FORKSRV_FD, was_killed.as_mut_ptr() as *mut libc::c_void, 4) != 4 {
+                // the executor went away
+                libc::_exit(1);
+            }
+
+            let child = libc::fork();
+            if child < 0 {
+                libc::_exit(1);
+            }
+
+            if child == 0 {
+                libc::close(FORKSRV_FD);
+                libc::close(FORKSRV_FD + 1);
+                return;
+            }
+
+            let pid = child.to_ne_bytes();
+            if libc::write(FORKSRV_FD + 1, pid.as_ptr() as *const libc::c_void, 4) != 4 {
+                libc::_exit(1);
+            }
+
+            let mut status: libc::c_int = 0;
+            if libc::waitpid(child, &mut status, 0) < 0 {
+                libc::_exit(1);
+            }
+
+            let status = status.to_ne_bytes();
+            if libc::write(FORKSRV_FD + 1, status.as_ptr() as *const libc::c_void, 4) != 4 {
+                libc::_exit(1);
+            }
+        }
+    }
+}
+
+/// A running fork server process and our ends of its pipes.
+#[derive(Debug)]
+struct Forkserver {
+    process: Child,
+    control: File,
+    status: File,
+    last_run_timed_out: bool,
+}
+
+impl Drop for Forkserver {
+    fn drop(&mut self) {
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+    }
+}
+
+/// Runs inputs through a target's fork server. The fork server is started on the first execution
+/// and restarted if it dies.
+///
+/// The target's stdout and stderr are discarded, so [ExecutionResult::stdout] and
+/// [ExecutionResult::stderr] are always empty. Only [InputDelivery::Stdin] and
+/// [InputDelivery::File] are supported since the arguments are fixed when the fork server starts.
+///
+/// Each fuzzer thread should own its own executor, e.g. by creating it in the factory passed to
+/// [start_fuzzer_with_factory](crate::driver::start_fuzzer_with_factory):
+///
+/// ```compile_fail
+/// start_fuzzer_with_factory(driver, |_thread_index| {
+///     let mut executor = ForkserverExecutor::new("./target");
+///     executor.timeout(Duration::from_millis(100));
+///
+///     move |mutator, _global_context| {
+///         let packet = Packet::new_fuzzed(mutator, None);
+///         executor
+///             .execute_object::<_, LittleEndian>(&packet)
+///             .map_err(|_| ())?
+///             .as_iteration_result()
+///     }
+/// });
+/// ```
+#[derive(Debug)]
+pub struct ForkserverExecutor {
+    program: OsString,
+    args: Vec<OsString>,
+    delivery: InputDelivery,
+    timeout: Duration,
+    input_file: PathBuf,
+    remove_input_file: bool,
+    input_handle: Option<File>,
+    server: Option<Forkserver>,
+}
+
+impl ForkserverExecutor {
+    /// Creates a new executor for `program` which delivers inputs over stdin with a timeout of
+    /// 1 second
+    pub fn new<S: AsRef<OsStr>>(program: S) -> ForkserverExecutor {
+        let input_file = std::env::temp_dir().join(format!(
+            "lain_forkserver_input_{}_{}",
+            std::process::id(),
+            INPUT_FILE_COUNTER.fetch_add(1, Ordering::SeqCst)
+        ));
+
+        ForkserverExecutor {
+            program: program.as_ref().to_owned(),
+            args: Vec::new(),
+            delivery: InputDelivery::Stdin,
+            timeout: DEFAULT_TIMEOUT,
+            input_file,
+            remove_input_file: true,
+            input_handle: None,
+            server: None,
+        }
+    }
+
+    /// Adds an argument to pass to the target
+    pub fn arg<S: AsRef<OsStr>>(&mut self, arg: S) -> &mut ForkserverExecutor {
+        self.args.push(arg.as_ref().to_owned());
+        self
+    }
+
+    /// Adds multiple arguments to pass to the target
+    pub fn args<I, S>(&mut self, args: I) -> &mut ForkserverExecutor
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        for arg in args {
+            self.arg(arg);
+        }
+        self
+    }
+
+    /// Sets how the input is delivered to the target
+    pub fn input_delivery(&mut self, delivery: InputDelivery) -> &mut ForkserverExecutor {
+        self.delivery = delivery;
+        self
+    }
+
+    /// Sets the max duration a single execution may run before it is killed
+    pub fn timeout(&mut self, timeout: Duration) -> &mut ForkserverExecutor {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Overrides the path of the file inputs are written to. By default a unique file in the
+    /// system's temp directory is used and removed when the executor is dropped.
+    pub fn input_file<P: AsRef<Path>>(&mut self, path: P) -> &mut ForkserverExecutor {
+        self.input_file = path.as_ref().to_owned();
+        self.remove_input_file = false;
+        self
+    }
+
+    /// Returns whether the fork server is currently running
+    pub fn is_running(&self) -> bool {
+        self.server.is_some()
+    }
+
+    /// Serializes `object` and runs the target with it
+    pub fn execute_object<T, E>(&mut self, object: &T) -> io::Result<ExecutionResult>
+    where
+        T: BinarySerialize + SerializedSize,
+        E: ByteOrder,
+    {
+        let mut input = Vec::with_capacity(object.serialized_size());
+        object.binary_serialize::<_, E>(&mut input);
+
+        self.execute(&input)
+    }
+
+    /// Runs the target once with `input`, starting the fork server if it is not running
+    pub fn execute(&mut self, input: &[u8]) -> io::Result<ExecutionResult> {
+        if self.server.is_none() {
+            self.server = Some(self.spawn()?);
+        }
+
+        let result = self.run(input);
+        if result.is_err() {
+            // the fork server is in an unknown state. drop it so that it's restarted next time
+            self.server = None;
+        }
+
+        result
+    }
+
+    fn run(&mut self, input: &[u8]) -> io::Result<ExecutionResult> {
+        self.write_input(input)?;
+
+        let timeout = self.timeout;
+        let server = self.server.as_mut().expect("fork server is not running");
+
+        let was_killed = server.last_run_timed_out as u32;
+        server.control.write_all(&was_killed.to_ne_bytes())?;
+
+        let start = Instant::now();
+
+        let pid = read_u32(&mut server.status, Some(FORKSERVER_INIT_TIMEOUT))?
+            .ok_or_else(|| io::Error::new(io::ErrorKind::TimedOut, "fork server did not fork"))?
+            as libc::pid_t;
+
+        if pid <= 0 {
+            return Err(io::Error::other("fork server returned an invalid pid"));
+        }
+
+        let (status, timed_out) = match read_u32(&mut server.status, Some(timeout))? {
+            Some(status) => (status, false),
+            None => {
+                unsafe {
+                    libc::kill(pid, libc::SIGKILL);
+                }
+
+                let status = read_u32(&mut server.status, None)?
+                    .ok_or_else(|| io::Error::other("fork server did not report status"))?;
+
+                (status, true)
+            }
+        };
+
+        let duration = start.elapsed();
+        server.last_run_timed_out = timed_out;
+
+        let exit_kind = if timed_out {
+            ExitKind::Timeout
+        } else {
+            exit_kind_from_wait_status(status as libc::c_int)
+        };
+
+        Ok(ExecutionResult {
+            exit_kind,
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+            duration,
+            input_truncated: false,
+        })
+    }
+
+    fn write_input(&mut self, input: &[u8]) -> io::Result<()> {
+        match self.delivery {
+            InputDelivery::Stdin => {
+                // the target's stdin shares a file offset with our handle, so it must be rewound
+                // after writing the input
+                let handle = self
+                    .input_handle
+                    .as_mut()
+                    .expect("stdin handle was not created");
+                handle.set_len(0)?;
+                handle.seek(SeekFrom::Start(0))?;
+                handle.write_all(input)?;
+                handle.seek(SeekFrom::Start(0))?;
+            }
+            InputDelivery::File => std::fs::write(&self.input_file, input)?,
+            InputDelivery::Argv => unreachable!(),
+        }
+
+        Ok(())
+    }
+
+    fn spawn(&mut self) -> io::Result<Forkserver> {
+        let mut command = Command::new(&self.program);
+        command.stdout(Stdio::null()).stderr(Stdio::null());
+
+        match self.delivery {
+            InputDelivery::Stdin => {
+                let handle = std::fs::OpenOptions::new()
+                    .read(true)
+                    .write(true)
+                    .create(true)
+                    .truncate(true)
+                    .open(&self.input_file)?;
+
+                command.args(&self.args);
+                command.stdin(Stdio::from(handle.try_clone()?));
+                self.input_handle = Some(handle);
+            }
+            InputDelivery::File => {
+                let mut substituted = false;
+                for arg in &self.args {
+                    if arg == INPUT_PLACEHOLDER {
+                        command.arg(&self.input_file);
+                        substituted = true;
+                    } else {
+                        command.arg(arg);
+                    }
+                }
+
+                if !substituted {
+                    command.arg(&self.input_file);
+                }
+
+                command.stdin(Stdio::null());
+            }
+            InputDelivery::Argv => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "the fork server executor does not support argv input delivery",
+                ));
+            }
+        }
+
+        let (control_read, control_write) = pipe()?;
+        let (status_read, status_write) = pipe()?;
+
+        let control_fd = control_read.as_raw_fd();
+        let status_fd = status_write.as_raw_fd();
+
+        unsafe {
+            command.pre_exec(move || {
+                // dup2() clears FD_CLOEXEC on the new descriptors so these survive the exec
+                if libc::dup2(control_fd, FORKSRV_FD) < 0
+                    || libc::dup2(status_fd, FORKSRV_FD + 1) < 0
+                {
+                    return Err(io::Error::last_os_error());
+                }
+
+                Ok(())
+            });
+        }
+
+        let process = command.spawn()?;
+
+        // close the target's ends so that we see EOF if it dies
+        drop(control_read);
+        drop(status_write);
+
+        let mut server = Forkserver {
+            process,
+            control: control_write,
+            status: status_read,
+            last_run_timed_out: false,
+        };
+
+        match read_u32(&mut server.status, Some(FORKSERVER_INIT_TIMEOUT)) {
+            Ok(Some(_hello)) => Ok(server),
+            Ok(None) => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "timed out waiting for the fork server handshake",
+            )),
+            Err(_) => Err(io::Error::other("target did not start a fork server")),
+        }
+    }
+}
+
+impl Drop for ForkserverExecutor {
+    fn drop(&mut self) {
+        self.server = None;
+
+        if self.remove_input_file && self.input_file.exists() {
+            let _ = std::fs::remove_file(&self.input_file);
+        }
+    }
+}
+
+fn exit_kind_from_wait_status(status: libc::c_int) -> ExitKind {
+    unsafe {
+        if libc::WIFSIGNALED(status) {
+            ExitKind::Crash(libc::WTERMSIG(status))
+        } else {
+            match libc::WEXITSTATUS(status) {
+                0 => ExitKind::Ok,
+                code => ExitKind::NonZeroExit(code),
+            }
+        }
+    }
+}
+
+/// Creates a pipe with both ends marked close-on-exec. Returns `(read, write)`.
+///
+/// The flag is set atomically where `pipe2()` is available, otherwise another thread's
+/// `fork()` between creating the pipe and setting the flag would leak both ends to its child.
+fn pipe() -> io::Result<(File, File)> {
+    let mut fds = [0 as libc::c_int; 2];
+
+    unsafe {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            if libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        {
+            if libc::pipe(fds.as_mut_ptr()) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            for fd in &fds {
+                libc::fcntl(*fd, libc::F_SETFD, libc::FD_CLOEXEC);
+            }
+        }
+
+        Ok((File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])))
+    }
+}
+
+/// Reads a native-endian u32 from `file`. Returns `None` if no data arrived within `timeout`.
+fn read_u32(file: &mut File, timeout: Option<Duration>) -> io::Result<Option<u32>> {
+    if let Some(timeout) = timeout {
+        let mut pollfd = libc::pollfd {
+            fd: file.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            // a signal interrupting the poll doesn't extend the timeout
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let ret = unsafe { libc::poll(&mut pollfd, 1, poll_timeout_ms(remaining)) };
+            if ret == 0 {
+                return Ok(None);
+            }
+
+            if ret < 0 {
+                let err = io::Error::last_os_error();
+                if err.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+
+                return Err(err);
+            }
+
+            break;
+        }
+    }
+
+    let mut value = [0u8; 4];
+    file.read_exact(&mut value)?;
+
+    Ok(Some(u32::from_ne_bytes(value)))
+}
+
+/// Converts `timeout` to whole milliseconds for `poll`, rounding up so that a timeout shorter
+/// than a millisecond doesn't turn into an immediate timeout
+fn poll_timeout_ms(timeout: Duration) -> libc::c_int {
+    let millis = timeout.as_nanos().div_ceil(1_000_000);
+
+    std::cmp::min(millis, libc::c_int::MAX as u128) as libc::c_int
+}
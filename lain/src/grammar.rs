@@ -0,0 +1,934 @@
+//! Grammar-based generation for text formats.
+//!
+//! A [Grammar] is a context-free grammar which can either be built in Rust with a
+//! [GrammarBuilder] or parsed from a BNF/ABNF-style description. Inputs are generated as
+//! [GrammarTree]s: derivation trees that implement [NewFuzzed], [Mutatable], and
+//! [BinarySerialize] so that they can be used like any other lain type.
+//!
+//! The supported grammar syntax is:
+//!
+//! ```text
+//! # comments start with '#' or ';'
+//! <expr>   ::= <term> | <term> "+" <expr>
+//! <term>   ::= <number> | "(" <expr> ")"
+//! <number> ::= <digit> | <digit> <number>
+//! digit     = %x30-39        ; ABNF-style names, definitions, and byte ranges also work
+//! ```
+//!
+//! Rules are defined with `::=`, `=`, or `=/`, and alternatives are separated by `|` or `/`.
+//! Defining a rule multiple times adds alternatives to it. Terminals are quoted with `"` or `'`
+//! and support the `\n`, `\r`, `\t`, `\0`, `\\`, `\"`, `\'`, and `\xHH` escapes. ABNF numeric
+//! terminals (`%x41`, `%x0D.0A`, `%x30-39`, and their `%d` equivalents) are supported as well.
+//! The first rule is the start rule unless [GrammarBuilder::start] says otherwise.
+use crate::mutator::Mutator;
+use crate::rand::seq::SliceRandom;
+use crate::rand::Rng;
+use crate::traits::*;
+use crate::types::*;
+use crate::NewFuzzed;
+use byteorder::ByteOrder;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Write;
+use std::marker::PhantomData;
+use std::path::Path;
+
+/// The max output size used when no size constraint is given
+const DEFAULT_MAX_SIZE: usize = 0x400;
+/// The max derivation depth used when generating trees
+const DEFAULT_MAX_DEPTH: usize = 32;
+
+/// An error encountered while building or parsing a grammar.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GrammarError {
+    /// The grammar text could not be parsed
+    Parse { line: usize, message: String },
+    /// A rule references a rule which is never defined
+    UndefinedRule(String),
+    /// A rule can never derive a finite string
+    UnproductiveRule(String),
+    /// The grammar has no rules
+    Empty,
+    /// The grammar file could not be read
+    Io(String),
+}
+
+impl fmt::Display for GrammarError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GrammarError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+            GrammarError::UndefinedRule(name) => write!(f, "rule `{}` is not defined", name),
+            GrammarError::UnproductiveRule(name) => {
+                write!(f, "rule `{}` never derives a finite string", name)
+            }
+            GrammarError::Empty => write!(f, "grammar has no rules"),
+            GrammarError::Io(message) => write!(f, "failed to read grammar: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for GrammarError {}
+
+/// A symbol on the right-hand side of a rule.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Symbol {
+    /// Bytes which are emitted verbatim
+    Terminal(Vec<u8>),
+    /// A reference to another rule by name
+    Rule(String),
+}
+
+impl Symbol {
+    pub fn terminal<B: AsRef<[u8]>>(bytes: B) -> Symbol {
+        Symbol::Terminal(bytes.as_ref().to_vec())
+    }
+
+    pub fn rule<S: Into<String>>(name: S) -> Symbol {
+        Symbol::Rule(name.into())
+    }
+}
+
+/// Builds a [Grammar] from rules defined in Rust.
+///
+/// ```
+/// # use lain::grammar::{GrammarBuilder, Symbol};
+/// let grammar = GrammarBuilder::new()
+///     .rule("list", vec![Symbol::rule("item")])
+///     .rule("list", vec![Symbol::rule("item"), Symbol::terminal(","), Symbol::rule("list")])
+///     .rule("item", vec![Symbol::terminal("a")])
+///     .rule("item", vec![Symbol::terminal("b")])
+///     .build()
+///     .unwrap();
+///
+/// assert_eq!(grammar.min_size(), 1);
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct GrammarBuilder {
+    rules: Vec<(String, Vec<Vec<Symbol>>)>,
+    start: Option<String>,
+}
+
+impl GrammarBuilder {
+    pub fn new() -> GrammarBuilder {
+        Default::default()
+    }
+
+    /// Adds an alternative to the rule `name`, defining the rule if it doesn't exist yet
+    pub fn rule<S: Into<String>>(
+        &mut self,
+        name: S,
+        alternative: Vec<Symbol>,
+    ) -> &mut GrammarBuilder {
+        let name = name.into();
+
+        match self.rules.iter_mut().find(|(rule, _)| *rule == name) {
+            Some((_, alternatives)) => alternatives.push(alternative),
+            None => self.rules.push((name, vec![alternative])),
+        }
+
+        self
+    }
+
+    /// Sets the rule that generation starts from. Defaults to the first rule.
+    pub fn start<S: Into<String>>(&mut self, name: S) -> &mut GrammarBuilder {
+        self.start = Some(name.into());
+        self
+    }
+
+    /// Validates the rules and builds the grammar
+    pub fn build(&self) -> Result<Grammar, GrammarError> {
+        if self.rules.is_empty() {
+            return Err(GrammarError::Empty);
+        }
+
+        let indices: HashMap<&str, usize> = self
+            .rules
+            .iter()
+            .enumerate()
+            .map(|(i, (name, _))| (name.as_str(), i))
+            .collect();
+
+        let lookup = |name: &str| {
+            indices
+                .get(name)
+                .cloned()
+                .ok_or_else(|| GrammarError::UndefinedRule(name.to_string()))
+        };
+
+        let mut rules = Vec::with_capacity(self.rules.len());
+        for (_, alternatives) in &self.rules {
+            let mut compiled_alternatives = Vec::with_capacity(alternatives.len());
+            for alternative in alternatives {
+                let mut compiled = Vec::with_capacity(alternative.len());
+                for symbol in alternative {
+                    compiled.push(match symbol {
+                        Symbol::Terminal(bytes) => CompiledSymbol::Terminal(bytes.clone()),
+                        Symbol::Rule(name) => CompiledSymbol::Rule(lookup(name)?),
+                    });
+                }
+
+                compiled_alternatives.push(compiled);
+            }
+
+            rules.push(compiled_alternatives);
+        }
+
+        let start = match self.start {
+            Some(ref name) => lookup(name)?,
+            None => 0,
+        };
+
+        let mut grammar = Grammar {
+            names: self.rules.iter().map(|(name, _)| name.clone()).collect(),
+            rules,
+            start,
+            min_sizes: vec![],
+            alternative_min_sizes: vec![],
+            min_heights: vec![],
+            alternative_min_heights: vec![],
+        };
+
+        grammar.compute_costs();
+
+        if let Some(rule) = grammar
+            .min_sizes
+            .iter()
+            .position(|&size| size == usize::MAX)
+        {
+            return Err(GrammarError::UnproductiveRule(grammar.names[rule].clone()));
+        }
+
+        Ok(grammar)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum CompiledSymbol {
+    Terminal(Vec<u8>),
+    Rule(usize),
+}
+
+/// A validated context-free grammar.
+#[derive(Debug, Clone)]
+pub struct Grammar {
+    names: Vec<String>,
+    rules: Vec<Vec<Vec<CompiledSymbol>>>,
+    start: usize,
+    min_sizes: Vec<usize>,
+    alternative_min_sizes: Vec<Vec<usize>>,
+    min_heights: Vec<usize>,
+    alternative_min_heights: Vec<Vec<usize>>,
+}
+
+impl Grammar {
+    /// Parses a grammar from its BNF/ABNF-style description. See the [module docs](self) for
+    /// the syntax.
+    pub fn parse(text: &str) -> Result<Grammar, GrammarError> {
+        parse_grammar(text)
+    }
+
+    /// Reads and parses a grammar file
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Grammar, GrammarError> {
+        let text = std::fs::read_to_string(path).map_err(|e| GrammarError::Io(e.to_string()))?;
+
+        Grammar::parse(&text)
+    }
+
+    /// The name of the rule that generation starts from
+    pub fn start_rule(&self) -> &str {
+        &self.names[self.start]
+    }
+
+    /// The names of all rules in the grammar
+    pub fn rule_names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// The size in bytes of the smallest string this grammar can derive
+    pub fn min_size(&self) -> usize {
+        self.min_sizes[self.start]
+    }
+
+    /// Computes the smallest output size and derivation height for every rule and alternative by
+    /// iterating until a fixpoint is reached. Rules which can't derive a finite string are left at
+    /// `usize::MAX`.
+    fn compute_costs(&mut self) {
+        let num_rules = self.rules.len();
+        self.min_sizes = vec![usize::MAX; num_rules];
+        self.min_heights = vec![usize::MAX; num_rules];
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+
+            for rule in 0..num_rules {
+                for alternative in &self.rules[rule] {
+                    let (size, height) = self.alternative_cost(alternative);
+
+                    if size < self.min_sizes[rule] {
+                        self.min_sizes[rule] = size;
+                        changed = true;
+                    }
+
+                    if height < self.min_heights[rule] {
+                        self.min_heights[rule] = height;
+                        changed = true;
+                    }
+                }
+            }
+        }
+
+        self.alternative_min_sizes = Vec::with_capacity(num_rules);
+        self.alternative_min_heights = Vec::with_capacity(num_rules);
+        for rule in 0..num_rules {
+            let (sizes, heights) = self.rules[rule]
+                .iter()
+                .map(|alternative| self.alternative_cost(alternative))
+                .unzip();
+
+            self.alternative_min_sizes.push(sizes);
+            self.alternative_min_heights.push(heights);
+        }
+    }
+
+    /// Returns the min size and min height of an alternative based off of the current rule costs
+    fn alternative_cost(&self, alternative: &[CompiledSymbol]) -> (usize, usize) {
+        let mut size = 0usize;
+        let mut height = 1usize;
+
+        for symbol in alternative {
+            match *symbol {
+                CompiledSymbol::Terminal(ref bytes) => size = size.saturating_add(bytes.len()),
+                CompiledSymbol::Rule(rule) => {
+                    if self.min_sizes[rule] == usize::MAX {
+                        return (usize::MAX, usize::MAX);
+                    }
+
+                    size = size.saturating_add(self.min_sizes[rule]);
+                    height = std::cmp::max(height, self.min_heights[rule].saturating_add(1));
+                }
+            }
+        }
+
+        (size, height)
+    }
+
+    /// Returns the derivation depth limit for the given size budget
+    fn max_depth(&self, max_size: usize) -> usize {
+        // deeper derivations than this can only be reached through rules which produce no
+        // output, so there is no point in going further
+        std::cmp::min(
+            DEFAULT_MAX_DEPTH,
+            self.min_heights[self.start].saturating_add(max_size),
+        )
+    }
+
+    /// Randomly derives `rule`, trying to stay within `budget` bytes. Returns the new node and
+    /// its serialized size.
+    fn generate<R: Rng>(
+        &self,
+        mutator: &mut Mutator<R>,
+        rule: usize,
+        depth: usize,
+        max_depth: usize,
+        budget: usize,
+    ) -> (DerivationNode, usize) {
+        let sizes = &self.alternative_min_sizes[rule];
+        let heights = &self.alternative_min_heights[rule];
+
+        let candidates: Vec<usize> = (0..sizes.len())
+            .filter(|&i| sizes[i] <= budget && depth + heights[i] <= max_depth)
+            .collect();
+
+        let alternative = match candidates.choose(&mut mutator.rng) {
+            Some(&alternative) => alternative,
+            // nothing fits, so take the alternative that terminates the soonest
+            None => (0..heights.len())
+                .min_by_key(|&i| (heights[i], sizes[i]))
+                .unwrap(),
+        };
+
+        let symbols = &self.rules[rule][alternative];
+        let num_children = symbols
+            .iter()
+            .filter(|symbol| matches!(symbol, CompiledSymbol::Rule(_)))
+            .count();
+
+        let mut size = 0;
+        let mut slack = budget.saturating_sub(sizes[alternative]);
+        let mut children = Vec::with_capacity(num_children);
+
+        for symbol in symbols {
+            match *symbol {
+                CompiledSymbol::Terminal(ref bytes) => size += bytes.len(),
+                CompiledSymbol::Rule(child_rule) => {
+                    // the last child may use all of the remaining slack
+                    let child_slack = if children.len() + 1 == num_children {
+                        slack
+                    } else {
+                        mutator.gen_range(0, slack + 1)
+                    };
+
+                    let child_min_size = self.min_sizes[child_rule];
+                    let (child, child_size) = self.generate(
+                        mutator,
+                        child_rule,
+                        depth + 1,
+                        max_depth,
+                        child_min_size + child_slack,
+                    );
+
+                    slack = slack.saturating_sub(child_size.saturating_sub(child_min_size));
+                    size += child_size;
+                    children.push(child);
+                }
+            }
+        }
+
+        (
+            DerivationNode {
+                rule,
+                alternative,
+                children,
+            },
+            size,
+        )
+    }
+
+    fn node_size(&self, node: &DerivationNode) -> usize {
+        let mut children = node.children.iter();
+
+        self.rules[node.rule][node.alternative]
+            .iter()
+            .map(|symbol| match *symbol {
+                CompiledSymbol::Terminal(ref bytes) => bytes.len(),
+                CompiledSymbol::Rule(_) => self.node_size(children.next().unwrap()),
+            })
+            .sum()
+    }
+
+    fn write_node<W: Write>(&self, node: &DerivationNode, buffer: &mut W) -> usize {
+        let mut children = node.children.iter();
+        let mut written = 0;
+
+        for symbol in &self.rules[node.rule][node.alternative] {
+            match *symbol {
+                CompiledSymbol::Terminal(ref bytes) => {
+                    buffer.write_all(bytes).unwrap();
+                    written += bytes.len();
+                }
+                CompiledSymbol::Rule(_) => {
+                    written += self.write_node(children.next().unwrap(), buffer);
+                }
+            }
+        }
+
+        written
+    }
+
+    /// Collects the rule and serialized size of every node in pre-order
+    fn collect_nodes(&self, node: &DerivationNode, nodes: &mut Vec<(usize, usize)>) -> usize {
+        let index = nodes.len();
+        nodes.push((node.rule, 0));
+
+        let mut children = node.children.iter();
+        let mut size = 0;
+        for symbol in &self.rules[node.rule][node.alternative] {
+            size += match *symbol {
+                CompiledSymbol::Terminal(ref bytes) => bytes.len(),
+                CompiledSymbol::Rule(_) => self.collect_nodes(children.next().unwrap(), nodes),
+            };
+        }
+
+        nodes[index].1 = size;
+
+        size
+    }
+}
+
+/// A node in a derivation tree: which alternative of which rule was picked, plus one child per
+/// rule referenced by that alternative.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DerivationNode {
+    rule: usize,
+    alternative: usize,
+    children: Vec<DerivationNode>,
+}
+
+impl DerivationNode {
+    /// The index of the rule this node derives
+    pub fn rule(&self) -> usize {
+        self.rule
+    }
+
+    /// The index of the alternative that was picked for the rule
+    pub fn alternative(&self) -> usize {
+        self.alternative
+    }
+
+    pub fn children(&self) -> &[DerivationNode] {
+        &self.children
+    }
+
+    fn count(&self) -> usize {
+        1 + self
+            .children
+            .iter()
+            .map(DerivationNode::count)
+            .sum::<usize>()
+    }
+
+    /// Finds the node at `index` in pre-order along with its depth
+    fn find(&self, index: &mut usize, depth: usize) -> Option<(&DerivationNode, usize)> {
+        if *index == 0 {
+            return Some((self, depth));
+        }
+
+        *index -= 1;
+        self.children
+            .iter()
+            .find_map(|child| child.find(index, depth + 1))
+    }
+
+    fn find_mut(
+        &mut self,
+        index: &mut usize,
+        depth: usize,
+    ) -> Option<(&mut DerivationNode, usize)> {
+        if *index == 0 {
+            return Some((self, depth));
+        }
+
+        *index -= 1;
+        self.children
+            .iter_mut()
+            .find_map(|child| child.find_mut(index, depth + 1))
+    }
+}
+
+/// Provides the grammar used by a [GrammarTree]. This is typically implemented on a unit struct
+/// which lazily builds the grammar:
+///
+/// ```
+/// # use lain::grammar::{Grammar, GrammarDefinition};
+/// # use lain::lazy_static::lazy_static;
+/// struct Numbers;
+///
+/// impl GrammarDefinition for Numbers {
+///     fn grammar() -> &'static Grammar {
+///         lazy_static! {
+///             static ref GRAMMAR: Grammar = Grammar::parse(r#"
+///                 <number> ::= <digit> | <digit> <number>
+///                 <digit> ::= %x30-39
+///             "#).unwrap();
+///         }
+///
+///         &GRAMMAR
+///     }
+/// }
+/// ```
+pub trait GrammarDefinition {
+    fn grammar() -> &'static Grammar;
+}
+
+#[derive(Copy, Clone, PartialEq, NewFuzzed)]
+enum GrammarMutation {
+    Regenerate,
+    Splice,
+}
+
+/// A string derived from the grammar `G`.
+///
+/// The output size is bounded by [Constraints::max_size] (or 1KiB if no constraint is given)
+/// unless the grammar's smallest string is larger than that.
+pub struct GrammarTree<G: GrammarDefinition> {
+    root: DerivationNode,
+    _grammar: PhantomData<G>,
+}
+
+impl<G: GrammarDefinition> GrammarTree<G> {
+    pub fn root(&self) -> &DerivationNode {
+        &self.root
+    }
+
+    /// Returns the derived string
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buffer = Vec::with_capacity(self.serialized_size());
+        G::grammar().write_node(&self.root, &mut buffer);
+
+        buffer
+    }
+
+    /// Replaces a random subtree with a subtree of the same rule taken from `donor`
+    pub fn splice<R: Rng>(
+        &mut self,
+        donor: &GrammarTree<G>,
+        mutator: &mut Mutator<R>,
+        max_size: Option<usize>,
+    ) -> bool {
+        let grammar = G::grammar();
+        let max_size = max_size.unwrap_or(DEFAULT_MAX_SIZE);
+
+        let mut nodes = vec![];
+        let total_size = grammar.collect_nodes(&self.root, &mut nodes);
+        let mut donor_nodes = vec![];
+        grammar.collect_nodes(&donor.root, &mut donor_nodes);
+
+        let target = mutator.gen_range(0, nodes.len());
+        let (target_rule, target_size) = nodes[target];
+
+        let candidates: Vec<usize> = donor_nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, &(rule, size))| {
+                rule == target_rule && total_size - target_size + size <= max_size
+            })
+            .map(|(i, _)| i)
+            .collect();
+
+        let mut donor_index = match candidates.choose(&mut mutator.rng) {
+            Some(&index) => index,
+            None => return false,
+        };
+
+        let mut target_index = target;
+        let replacement = donor.root.find(&mut donor_index, 0).unwrap().0.clone();
+        *self.root.find_mut(&mut target_index, 0).unwrap().0 = replacement;
+
+        true
+    }
+}
+
+impl<G: GrammarDefinition> Clone for GrammarTree<G> {
+    fn clone(&self) -> Self {
+        GrammarTree {
+            root: self.root.clone(),
+            _grammar: PhantomData,
+        }
+    }
+}
+
+impl<G: GrammarDefinition> fmt::Debug for GrammarTree<G> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "GrammarTree({:?})",
+            String::from_utf8_lossy(&self.to_bytes())
+        )
+    }
+}
+
+impl<G: GrammarDefinition> NewFuzzed for GrammarTree<G> {
+    type RangeType = usize;
+
+    fn new_fuzzed<R: Rng>(
+        mutator: &mut Mutator<R>,
+        constraints: Option<&Constraints<Self::RangeType>>,
+    ) -> Self {
+        let grammar = G::grammar();
+        let max_size = constraints
+            .and_then(|c| c.max_size)
+            .unwrap_or(DEFAULT_MAX_SIZE);
+
+        trace!("generating grammar tree with max size {}", max_size);
+
+        let (root, _size) = grammar.generate(
+            mutator,
+            grammar.start,
+            0,
+            grammar.max_depth(max_size),
+            max_size,
+        );
+
+        GrammarTree {
+            root,
+            _grammar: PhantomData,
+        }
+    }
+}
+
+impl<G: GrammarDefinition> Mutatable for GrammarTree<G> {
+    type RangeType = usize;
+
+    fn mutate<R: Rng>(
+        &mut self,
+        mutator: &mut Mutator<R>,
+        constraints: Option<&Constraints<Self::RangeType>>,
+    ) {
+        let grammar = G::grammar();
+        let max_size = constraints
+            .and_then(|c| c.max_size)
+            .unwrap_or(DEFAULT_MAX_SIZE);
+
+        if GrammarMutation::new_fuzzed(mutator, None) == GrammarMutation::Splice {
+            // splicing from ourselves lets recursive rules grow by duplicating subtrees
+            let donor = self.clone();
+            if self.splice(&donor, mutator, Some(max_size)) {
+                return;
+            }
+        }
+
+        let total_size = self.serialized_size();
+        let mut index = mutator.gen_range(0, self.root.count());
+        let (node, depth) = self.root.find_mut(&mut index, 0).unwrap();
+
+        let budget = grammar.node_size(node) + max_size.saturating_sub(total_size);
+        let (replacement, _size) = grammar.generate(
+            mutator,
+            node.rule,
+            depth,
+            grammar.max_depth(max_size),
+            budget,
+        );
+
+        *node = replacement;
+    }
+}
+
+impl<G: GrammarDefinition> SerializedSize for GrammarTree<G> {
+    fn serialized_size(&self) -> usize {
+        G::grammar().node_size(&self.root)
+    }
+
+    fn min_nonzero_elements_size() -> usize {
+        G::grammar().min_size()
+    }
+}
+
+impl<G: GrammarDefinition> BinarySerialize for GrammarTree<G> {
+    fn binary_serialize<W: Write, E: ByteOrder>(&self, buffer: &mut W) -> usize {
+        G::grammar().write_node(&self.root, buffer)
+    }
+}
+
+impl<G: GrammarDefinition> VariableSizeObject for GrammarTree<G> {
+    fn is_variable_size() -> bool {
+        true
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Name(String),
+    Define,
+    Alternative,
+    Terminal(Vec<u8>),
+    ByteRange(u8, u8),
+}
+
+fn parse_grammar(text: &str) -> Result<Grammar, GrammarError> {
+    let tokens = tokenize(text)?;
+    let mut builder = GrammarBuilder::new();
+
+    let is_definition = |i: usize| {
+        matches!(tokens.get(i), Some((Token::Name(_), _)))
+            && matches!(tokens.get(i + 1), Some((Token::Define, _)))
+    };
+
+    let mut i = 0;
+    while i < tokens.len() {
+        let name = match tokens[i] {
+            (Token::Name(ref name), _) if is_definition(i) => name.clone(),
+            (_, line) => {
+                return Err(GrammarError::Parse {
+                    line,
+                    message: "expected a rule definition".to_string(),
+                })
+            }
+        };
+
+        i += 2;
+
+        let mut alternatives = vec![vec![]];
+        while i < tokens.len() && !is_definition(i) {
+            let (ref token, line) = tokens[i];
+            let current = alternatives.last_mut().unwrap();
+
+            match *token {
+                Token::Alternative => alternatives.push(vec![]),
+                Token::Name(ref name) => current.push(Symbol::Rule(name.clone())),
+                Token::Terminal(ref bytes) => current.push(Symbol::Terminal(bytes.clone())),
+                Token::ByteRange(low, high) => {
+                    let range_name = format!("%x{:02X}-{:02X}", low, high);
+                    if !builder.rules.iter().any(|(rule, _)| *rule == range_name) {
+                        for byte in low..=high {
+                            builder.rule(range_name.clone(), vec![Symbol::Terminal(vec![byte])]);
+                        }
+                    }
+
+                    current.push(Symbol::Rule(range_name));
+                }
+                Token::Define => {
+                    return Err(GrammarError::Parse {
+                        line,
+                        message: "unexpected rule definition".to_string(),
+                    })
+                }
+            }
+
+            i += 1;
+        }
+
+        if builder.start.is_none() {
+            builder.start(name.clone());
+        }
+
+        for alternative in alternatives {
+            builder.rule(name.clone(), alternative);
+        }
+    }
+
+    builder.build()
+}
+
+fn tokenize(text: &str) -> Result<Vec<(Token, usize)>, GrammarError> {
+    let mut tokens = vec![];
+    let mut chars = text.chars().peekable();
+    let mut line = 1;
+
+    let error = |line, message: &str| GrammarError::Parse {
+        line,
+        message: message.to_string(),
+    };
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' => line += 1,
+            c if c.is_whitespace() => {}
+            '#' | ';' => {
+                while let Some(&c) = chars.peek() {
+                    if c == '\n' {
+                        break;
+                    }
+                    chars.next();
+                }
+            }
+            '|' => tokens.push((Token::Alternative, line)),
+            '/' => tokens.push((Token::Alternative, line)),
+            ':' => {
+                if chars.next() != Some(':') || chars.next() != Some('=') {
+                    return Err(error(line, "expected `::=`"));
+                }
+
+                tokens.push((Token::Define, line));
+            }
+            '=' => {
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                }
+
+                tokens.push((Token::Define, line));
+            }
+            '<' => {
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some('>') => break,
+                        Some('\n') | None => return Err(error(line, "unterminated rule name")),
+                        Some(c) => name.push(c),
+                    }
+                }
+
+                tokens.push((Token::Name(name), line));
+            }
+            '"' | '\'' => {
+                let quote = c;
+                let mut bytes = vec![];
+                loop {
+                    let c = match chars.next() {
+                        Some('\n') | None => return Err(error(line, "unterminated terminal")),
+                        Some(c) => c,
+                    };
+
+                    if c == quote {
+                        break;
+                    }
+
+                    if c != '\\' {
+                        let mut encoded = [0u8; 4];
+                        bytes.extend_from_slice(c.encode_utf8(&mut encoded).as_bytes());
+                        continue;
+                    }
+
+                    match chars.next() {
+                        Some('n') => bytes.push(b'\n'),
+                        Some('r') => bytes.push(b'\r'),
+                        Some('t') => bytes.push(b'\t'),
+                        Some('0') => bytes.push(0),
+                        Some('\\') => bytes.push(b'\\'),
+                        Some('"') => bytes.push(b'"'),
+                        Some('\'') => bytes.push(b'\''),
+                        Some('x') => {
+                            let digits: String = (0..2).filter_map(|_| chars.next()).collect();
+                            let byte = u8::from_str_radix(&digits, 16)
+                                .map_err(|_| error(line, "invalid `\\x` escape"))?;
+                            bytes.push(byte);
+                        }
+                        _ => return Err(error(line, "invalid escape sequence")),
+                    }
+                }
+
+                tokens.push((Token::Terminal(bytes), line));
+            }
+            '%' => {
+                let radix = match chars.next() {
+                    Some('x') | Some('X') => 16,
+                    Some('d') | Some('D') => 10,
+                    _ => return Err(error(line, "expected `%x` or `%d`")),
+                };
+
+                let read_number = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+                    let mut digits = String::new();
+                    while let Some(&c) = chars.peek() {
+                        if !c.is_digit(radix) {
+                            break;
+                        }
+                        digits.push(c);
+                        chars.next();
+                    }
+
+                    u8::from_str_radix(&digits, radix)
+                        .map_err(|_| error(line, "invalid numeric terminal"))
+                };
+
+                let first = read_number(&mut chars)?;
+                match chars.peek() {
+                    Some('-') => {
+                        chars.next();
+                        let last = read_number(&mut chars)?;
+                        if last < first {
+                            return Err(error(line, "invalid byte range"));
+                        }
+
+                        tokens.push((Token::ByteRange(first, last), line));
+                    }
+                    Some('.') => {
+                        let mut bytes = vec![first];
+                        while chars.peek() == Some(&'.') {
+                            chars.next();
+                            bytes.push(read_number(&mut chars)?);
+                        }
+
+                        tokens.push((Token::Terminal(bytes), line));
+                    }
+                    _ => tokens.push((Token::Terminal(vec![first]), line)),
+                }
+            }
+            c if c.is_alphanumeric() || c == '_' => {
+                let mut name = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if !(c.is_alphanumeric() || c == '_' || c == '-') {
+                        break;
+                    }
+                    name.push(c);
+                    chars.next();
+                }
+
+                tokens.push((Token::Name(name), line));
+            }
+            _ => return Err(error(line, &format!("unexpected character `{}`", c))),
+        }
+    }
+
+    Ok(tokens)
+}
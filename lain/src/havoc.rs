@@ -0,0 +1,236 @@
+//! Byte-level mutation of serialized objects. Structure-aware mutation only ever produces inputs
+//! which can be described by the object's fields, so it will never generate a truncated buffer or
+//! garbage in the middle of a struct. [Havoc] takes the output of `binary_serialize` and, with a
+//! configurable probability, applies a stack of classic byte-level mutations to it so that a
+//! fuzzer sends a mix of well-formed and subtly malformed inputs.
+//!
+//! ```
+//! use lain::havoc::Havoc;
+//! use lain::prelude::*;
+//! use lain::rand;
+//!
+//! #[derive(NewFuzzed, Mutatable, BinarySerialize)]
+//! struct Message {
+//!     kind: u8,
+//!     len: u32,
+//! }
+//!
+//! let mut mutator = Mutator::new(rand::thread_rng());
+//! let havoc = Havoc::new(0.25);
+//!
+//! let message = Message::new_fuzzed(&mut mutator, None);
+//! let data = havoc.serialize::<_, BigEndian, _>(&message, &mut mutator);
+//! ```
+
+use crate::mutator::Mutator;
+use crate::rand::Rng;
+use crate::traits::{BinarySerialize, DangerousNumber, SerializedSize};
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+
+/// The chance of havoc being applied to a serialized object when using [Havoc::default]
+pub const DEFAULT_CHANCE_TO_HAVOC: f64 = 0.10;
+
+/// A single byte-level mutation
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum HavocOperation {
+    /// Cuts the data off at a random offset
+    Truncate,
+    /// Inserts a block of random bytes at a random offset
+    InsertRandomBytes,
+    /// Copies a block of the data to a random offset
+    DuplicateBlock,
+    /// Swaps two non-overlapping blocks of the same size
+    SwapBlocks,
+    /// Overwrites 1, 2, 4, or 8 bytes at a random offset with a dangerous number
+    OverwriteDangerousNumber,
+}
+
+impl HavocOperation {
+    /// Every operation, in the order they're picked from
+    pub const ALL: [HavocOperation; 5] = [
+        HavocOperation::Truncate,
+        HavocOperation::InsertRandomBytes,
+        HavocOperation::DuplicateBlock,
+        HavocOperation::SwapBlocks,
+        HavocOperation::OverwriteDangerousNumber,
+    ];
+}
+
+/// Configuration for byte-level mutation of serialized data
+#[derive(Debug, Clone)]
+pub struct Havoc {
+    /// The chance of havoc being applied by [Havoc::serialize] or [Havoc::maybe_mutate]. 0.0
+    /// disables havoc entirely and 1.0 mutates every input.
+    pub chance: f64,
+    /// The most operations stacked on a single input
+    pub max_stacked_operations: usize,
+    /// The largest block inserted, duplicated, or swapped
+    pub max_block_size: usize,
+    /// Operations which may be picked. Defaults to [HavocOperation::ALL].
+    pub operations: Vec<HavocOperation>,
+}
+
+impl Default for Havoc {
+    fn default() -> Self {
+        Havoc::new(DEFAULT_CHANCE_TO_HAVOC)
+    }
+}
+
+impl Havoc {
+    /// Creates a new configuration which applies havoc with the given chance
+    pub fn new(chance: f64) -> Havoc {
+        Havoc {
+            chance,
+            max_stacked_operations: 8,
+            max_block_size: 32,
+            operations: HavocOperation::ALL.to_vec(),
+        }
+    }
+
+    /// Serializes `object` and then applies havoc to the output with [Havoc::chance]
+    pub fn serialize<T, E, R>(&self, object: &T, mutator: &mut Mutator<R>) -> Vec<u8>
+    where
+        T: BinarySerialize + SerializedSize + ?Sized,
+        E: ByteOrder,
+        R: Rng,
+    {
+        let mut data = Vec::with_capacity(object.serialized_size());
+        object.binary_serialize::<_, E>(&mut data);
+
+        self.maybe_mutate(mutator, &mut data);
+
+        data
+    }
+
+    /// Applies havoc to `data` with [Havoc::chance]. Returns whether the data was mutated.
+    pub fn maybe_mutate<R: Rng>(&self, mutator: &mut Mutator<R>, data: &mut Vec<u8>) -> bool {
+        if !mutator.gen_chance(self.chance) {
+            return false;
+        }
+
+        self.mutate(mutator, data);
+
+        true
+    }
+
+    /// Unconditionally applies between 1 and [Havoc::max_stacked_operations] random operations
+    /// to `data`
+    pub fn mutate<R: Rng>(&self, mutator: &mut Mutator<R>, data: &mut Vec<u8>) {
+        if self.operations.is_empty() {
+            return;
+        }
+
+        let count = mutator.gen_range(1, self.max_stacked_operations.max(1) + 1);
+        for _i in 0..count {
+            let operation = self.operations[mutator.gen_range(0, self.operations.len())];
+            self.apply(operation, mutator, data);
+        }
+    }
+
+    /// Applies a single operation to `data`. Operations which need existing bytes insert random
+    /// bytes instead when `data` is empty.
+    pub fn apply<R: Rng>(
+        &self,
+        operation: HavocOperation,
+        mutator: &mut Mutator<R>,
+        data: &mut Vec<u8>,
+    ) {
+        let max_block_size = self.max_block_size.max(1);
+
+        if data.is_empty() && operation != HavocOperation::Truncate {
+            insert_random_bytes(mutator, data, max_block_size);
+            return;
+        }
+
+        match operation {
+            HavocOperation::Truncate => {
+                if !data.is_empty() {
+                    let len = mutator.gen_range(0, data.len());
+                    data.truncate(len);
+                }
+            }
+            HavocOperation::InsertRandomBytes => {
+                insert_random_bytes(mutator, data, max_block_size);
+            }
+            HavocOperation::DuplicateBlock => {
+                let (start, len) = random_block(mutator, data.len(), max_block_size);
+                let block = data[start..start + len].to_vec();
+                let offset = mutator.gen_range(0, data.len() + 1);
+
+                data.splice(offset..offset, block);
+            }
+            HavocOperation::SwapBlocks => {
+                if data.len() < 2 {
+                    insert_random_bytes(mutator, data, max_block_size);
+                    return;
+                }
+
+                let (_, len) = random_block(mutator, data.len() / 2, max_block_size);
+                let first = mutator.gen_range(0, data.len() - 2 * len + 1);
+                let second = mutator.gen_range(first + len, data.len() - len + 1);
+
+                let (head, tail) = data.split_at_mut(second);
+                head[first..first + len].swap_with_slice(&mut tail[..len]);
+            }
+            HavocOperation::OverwriteDangerousNumber => {
+                overwrite_dangerous_number(mutator, data);
+            }
+        }
+    }
+}
+
+/// Picks a block of up to `max_block_size` bytes within `len` bytes, returning its start and
+/// length
+fn random_block<R: Rng>(
+    mutator: &mut Mutator<R>,
+    len: usize,
+    max_block_size: usize,
+) -> (usize, usize) {
+    let block_len = mutator.gen_range(1, len.min(max_block_size) + 1);
+    let start = mutator.gen_range(0, len - block_len + 1);
+
+    (start, block_len)
+}
+
+fn insert_random_bytes<R: Rng>(
+    mutator: &mut Mutator<R>,
+    data: &mut Vec<u8>,
+    max_block_size: usize,
+) {
+    let len = mutator.gen_range(1, max_block_size + 1);
+    let offset = mutator.gen_range(0, data.len() + 1);
+    let bytes: Vec<u8> = (0..len).map(|_| mutator.rng.gen()).collect();
+
+    data.splice(offset..offset, bytes);
+}
+
+fn overwrite_dangerous_number<R: Rng>(mutator: &mut Mutator<R>, data: &mut [u8]) {
+    let widths: Vec<usize> = [1, 2, 4, 8]
+        .iter()
+        .cloned()
+        .filter(|width| *width <= data.len())
+        .collect();
+
+    let width = widths[mutator.gen_range(0, widths.len())];
+    let offset = mutator.gen_range(0, data.len() - width + 1);
+    let big_endian = mutator.rng.gen::<bool>();
+    let dest = &mut data[offset..offset + width];
+
+    macro_rules! write_dangerous {
+        ($ty:ident, $write:ident) => {{
+            let value = <$ty>::select_dangerous_number(&mut mutator.rng);
+            if big_endian {
+                BigEndian::$write(dest, value);
+            } else {
+                LittleEndian::$write(dest, value);
+            }
+        }};
+    }
+
+    match width {
+        1 => dest[0] = u8::select_dangerous_number(&mut mutator.rng),
+        2 => write_dangerous!(u16, write_u16),
+        4 => write_dangerous!(u32, write_u32),
+        _ => write_dangerous!(u64, write_u64),
+    }
+}
@@ -0,0 +1,57 @@
+//! Padding inserted between fields. This backs the `#[lain(align = 8)]`, `#[lain(pad = 4)]` and
+//! `#[lain(offset = 0x40)]` field attributes and the `#[lain(repr_c)]` and `#[lain(pack = N)]`
+//! container attributes of the `BinarySerialize` derive.
+//!
+//! Padding is zeroed unless the container has a [PaddingSeed][crate::types::PaddingSeed] field
+//! marked `#[lain(fuzz_padding)]`. In that case the padding bytes are generated from the seed, so
+//! they change when the seed is generated or mutated but are the same every time the object (or a
+//! copy of it) is serialized.
+
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+use std::io::Write;
+
+/// Returns the number of padding bytes needed before a field that would otherwise start at
+/// `offset`. `pad` bytes are always inserted, then the field is moved forward to
+/// `absolute_offset` (if it isn't already past it), and finally aligned up to a multiple of
+/// `align`.
+pub fn padding_before(
+    offset: usize,
+    align: usize,
+    pad: usize,
+    absolute_offset: Option<usize>,
+) -> usize {
+    let mut start = offset + pad;
+
+    if let Some(absolute_offset) = absolute_offset {
+        start = std::cmp::max(start, absolute_offset);
+    }
+
+    if align > 1 && !start.is_multiple_of(align) {
+        start += align - start % align;
+    }
+
+    start - offset
+}
+
+/// Writes `len` bytes of padding and returns the number of bytes written. The padding is zeroed if
+/// `seed` is `None`. `field_id` identifies the padding so that fuzzed padding differs between
+/// fields.
+pub fn write_padding<W: Write>(
+    len: usize,
+    seed: Option<u64>,
+    field_id: u64,
+    buffer: &mut W,
+) -> usize {
+    if len == 0 {
+        return 0;
+    }
+
+    let mut padding = vec![0u8; len];
+
+    if let Some(seed) = seed {
+        SmallRng::seed_from_u64(seed ^ field_id).fill(&mut padding[..]);
+    }
+
+    buffer.write(&padding).unwrap()
+}
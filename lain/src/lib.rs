@@ -70,11 +70,27 @@
 //! //
 //! // MyStruct { field_1: 95, field_2: 5, field_3: 14, field_4: 8383, ignored_field: 0 }
 //! // hex representation:
-//! // ------00 01 02 03 04 05 06 07 08 09 0A 0B 0C 0D 0E 0F
-//! // 0000: 5F 75 00 00 20 BF 00 00 00 00 00 00 00 00         _u...¿........
+//! //       00 01 02 03 04 05 06 07 08 09 0A 0B 0C 0D 0E 0F
+//! // 0000: 5F 75 00 00 20 BF 00 00 00 00 00 00 00 00        _u.. .........
 //! // MyStruct { field_1: 160, field_2: 5, field_3: 14, field_4: 8383, ignored_field: 0 }
 //! ```
 //!
+//! ## Saving Inputs
+//!
+//! With the `serde_support` feature enabled, lain's types implement serde's `Serialize` and
+//! `Deserialize` traits, and the derives are re-exported through the prelude. Deriving them on
+//! your own types lets corpus entries be stored in a human-readable format such as JSON next to
+//! their binary form, edited by hand, and loaded back for `binary_serialize`:
+//!
+//! ```ignore
+//! #[derive(NewFuzzed, Mutatable, BinarySerialize, Serialize, Deserialize)]
+//! #[serde(crate = "lain::serde")]
+//! struct MyStruct {
+//!     field_1: u8,
+//!     name: Utf8String,
+//! }
+//! ```
+//!
 //! A complete example of a fuzzer and its target can be found in the [examples](examples/)
 //! directory. The server is written in C and takes data over a TCP socket, parses a message, and
 //! mutates some state. The fuzzer has Rust definitions of the C data structure and will send fully
@@ -111,57 +127,71 @@ pub extern crate field_offset;
 pub extern crate lain_derive;
 pub extern crate lazy_static;
 pub extern crate rand;
+#[cfg(feature = "serde_support")]
+pub extern crate serde;
 
 pub use lain_derive::*;
 
 #[macro_use]
 pub extern crate log;
 
+pub mod bitstream;
 #[doc(hidden)]
 pub mod buffer;
+pub mod corpus_sync;
 #[doc(hidden)]
 pub mod dangerous_numbers;
 pub mod driver;
+pub mod dump;
+pub mod encoding;
+pub mod executor;
+#[cfg(unix)]
+pub mod forkserver;
+pub mod grammar;
+pub mod havoc;
+pub mod layout;
 #[doc(hidden)]
 pub mod mutatable;
 pub mod mutator;
 #[doc(hidden)]
 pub mod new_fuzzed;
 pub mod prelude;
+pub mod scheduler;
 pub mod traits;
+pub mod transport;
 pub mod types;
 
+/// Formats `data` as rows of 16 hex bytes prefixed with their offset and followed by their
+/// printable ASCII characters. See [dump::Dump] for a dump annotated with the fields of an object.
 pub fn hexdump(data: &[u8]) -> String {
-    let mut ret = "------".to_string();
+    // the offset column is 4 hex digits followed by a colon
+    let mut ret = "     ".to_string();
     for i in 0..16 {
-        ret += &format!("{:02X} ", i);
+        ret += &format!(" {:02X}", i);
     }
 
-    let mut ascii = String::new();
-    for (i, b) in data.iter().enumerate() {
-        if i % 16 == 0 {
-            ret += &format!("\t{}", ascii);
-            ascii.clear();
-            ret += &format!("\n{:04X}:", i);
+    for (row, chunk) in data.chunks(16).enumerate() {
+        ret += &format!("\n{:04X}:", row * 16);
+
+        for b in chunk {
+            ret += &format!(" {:02X}", b);
         }
 
-        ret += &format!(" {:02X}", b);
-        // this is the printable ASCII range
-        if *b >= 0x20 && *b <= 0x7f {
-            ascii.push(*b as char);
-        } else {
-            ascii.push('.');
+        // pad short rows so that the ASCII column lines up
+        for _i in chunk.len()..16 {
+            ret += "   ";
         }
-    }
 
-    if data.len() % 16 != 0 {
-        for _i in 0..16 - (data.len() % 16) {
-            ret += " ";
+        ret += "  ";
+        for b in chunk {
+            // this is the printable ASCII range
+            if (0x20..=0x7e).contains(b) {
+                ret.push(*b as char);
+            } else {
+                ret.push('.');
+            }
         }
     }
 
-    ret += &format!("\t{}", ascii);
-    ascii.clear();
-
     ret
 }
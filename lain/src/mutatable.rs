@@ -1,5 +1,7 @@
-use crate::mutator::Mutator;
+use crate::encoding::{fit_to_prefix, mismatched_prefix, EncodedSequence, Encoding, LengthPrefix};
+use crate::mutator::{Mutator, CHANCE_TO_MISMATCH_ENUM_TAG, CHANCE_TO_MISMATCH_LENGTH_PREFIX};
 use crate::rand::seq::index;
+use crate::rand::seq::SliceRandom;
 use crate::rand::Rng;
 use crate::traits::*;
 use crate::types::*;
@@ -8,6 +10,8 @@ use crate::NewFuzzed;
 use num_traits::{Bounded, NumCast};
 use num_traits::{WrappingAdd, WrappingSub};
 use std::cmp::min;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::hash::Hash;
 use std::ops::BitXor;
 
 // we'll shrink by a factor of 1/4, 1/2, 3/4, or down to [0, 8] bytes
@@ -32,6 +36,67 @@ enum VecResizeType {
     Shrink,
 }
 
+#[derive(Copy, Clone, PartialEq, NewFuzzed)]
+enum SequenceMutation {
+    Insert,
+    Delete,
+    Duplicate,
+    Reorder,
+    #[lain(weight = 4)]
+    MutateMessage,
+}
+
+#[derive(Copy, Clone, PartialEq, NewFuzzed)]
+enum StringMutation {
+    #[lain(weight = 4)]
+    ReplaceChar,
+    InsertChar,
+    InsertSpecialChar,
+    RemoveRange,
+    DuplicateRange,
+    InsertLongRun,
+}
+
+#[derive(Copy, Clone, PartialEq, NewFuzzed)]
+enum CollectionMutation {
+    Insert,
+    Remove,
+    #[lain(weight = 4)]
+    MutateEntry,
+}
+
+#[derive(Copy, Clone, PartialEq, NewFuzzed)]
+enum VarIntMutation {
+    #[lain(weight = 4)]
+    MutateValue,
+    Overlong,
+    Truncate,
+    Canonicalize,
+}
+
+#[derive(Copy, Clone, PartialEq, NewFuzzed)]
+enum UnsafeEnumMutation {
+    SwitchVariant,
+    GoInvalid,
+    SnapToValid,
+}
+
+/// Returns how many bytes an object of `current_size` may grow by under the given constraints.
+/// If the base object size hasn't been accounted for, the max size covers the whole object.
+fn extra_size_budget<T: Bounded + std::fmt::Debug>(
+    constraints: Option<&Constraints<T>>,
+    current_size: usize,
+) -> Option<usize> {
+    constraints.and_then(|c| {
+        if c.base_object_size_accounted_for {
+            c.max_size
+        } else {
+            c.max_size
+                .map(|max_size| max_size.saturating_sub(current_size))
+        }
+    })
+}
+
 /// Grows a `Vec`.
 /// This will randomly select to grow by a factor of 1/4, 1/2, 3/4, or a fixed number of bytes
 /// in the range of [1, 8]. Elements may be added randomly to the beginning or end of the the vec
@@ -409,6 +474,202 @@ where
     }
 }
 
+/// Returns whether the message at `index` is allowed to follow the one before it
+fn is_valid_at<T>(messages: &[T], index: usize) -> bool {
+    if index >= messages.len() {
+        return true;
+    }
+
+    match index.checked_sub(1) {
+        Some(previous) => messages[index].is_valid_successor(&messages[previous]),
+        None => messages[index].is_valid_start(),
+    }
+}
+
+impl<T> Mutatable for Sequence<T>
+where
+    T: Mutatable + NewFuzzed + SerializedSize + Clone,
+{
+    type RangeType = usize;
+
+    fn mutate<R: Rng>(
+        &mut self,
+        mutator: &mut Mutator<R>,
+        constraints: Option<&Constraints<Self::RangeType>>,
+    ) {
+        const MAX_ATTEMPTS: usize = 8;
+
+        let mut min_messages = constraints.and_then(|c| c.min).unwrap_or(0);
+        let mut max_messages = constraints.and_then(|c| c.max);
+        let max_size = constraints.and_then(|c| c.max_size);
+
+        if mutator.gen_chance(crate::mutator::CHANCE_TO_IGNORE_MIN_MAX) {
+            min_messages = 0;
+            max_messages = None;
+        }
+
+        let ignore_state_machine =
+            mutator.gen_chance(crate::mutator::CHANCE_TO_VIOLATE_STATE_MACHINE);
+
+        let len = self.messages.len();
+        let can_grow = max_messages.map(|max| len + 1 < max).unwrap_or(true);
+        let remaining_size =
+            max_size.map(|max_size| max_size.saturating_sub(self.serialized_size()));
+
+        let operation = if len == 0 {
+            SequenceMutation::Insert
+        } else {
+            SequenceMutation::new_fuzzed(mutator, None)
+        };
+
+        trace!(
+            "performing sequence mutation on a sequence of {} messages",
+            len
+        );
+
+        match operation {
+            SequenceMutation::Insert => {
+                if !can_grow {
+                    return;
+                }
+
+                let index = mutator.gen_range(0, len + 1);
+                let (previous, next) = if ignore_state_machine {
+                    (None, None)
+                } else {
+                    (
+                        index.checked_sub(1).map(|i| &self.messages[i]),
+                        self.messages.get(index),
+                    )
+                };
+
+                let message = match crate::new_fuzzed::new_fuzzed_successor(
+                    mutator,
+                    previous,
+                    next,
+                    remaining_size,
+                ) {
+                    Some(message) => message,
+                    None => return,
+                };
+
+                if remaining_size
+                    .map(|remaining| message.serialized_size() > remaining)
+                    .unwrap_or(false)
+                {
+                    return;
+                }
+
+                self.messages.insert(index, message);
+            }
+            SequenceMutation::Delete => {
+                if len <= min_messages {
+                    return;
+                }
+
+                for _i in 0..MAX_ATTEMPTS {
+                    let index = mutator.gen_range(0, len);
+                    let removed = self.messages.remove(index);
+
+                    if ignore_state_machine || is_valid_at(&self.messages, index) {
+                        return;
+                    }
+
+                    self.messages.insert(index, removed);
+                }
+            }
+            SequenceMutation::Duplicate => {
+                if !can_grow {
+                    return;
+                }
+
+                for _i in 0..MAX_ATTEMPTS {
+                    let index = mutator.gen_range(0, len);
+                    let message = self.messages[index].clone();
+
+                    if remaining_size
+                        .map(|remaining| message.serialized_size() > remaining)
+                        .unwrap_or(false)
+                    {
+                        continue;
+                    }
+
+                    self.messages.insert(index + 1, message);
+
+                    if ignore_state_machine
+                        || (is_valid_at(&self.messages, index + 1)
+                            && is_valid_at(&self.messages, index + 2))
+                    {
+                        return;
+                    }
+
+                    self.messages.remove(index + 1);
+                }
+            }
+            SequenceMutation::Reorder => {
+                if len < 2 {
+                    return;
+                }
+
+                for _i in 0..MAX_ATTEMPTS {
+                    let from = mutator.gen_range(0, len);
+                    let to = mutator.gen_range(0, len);
+                    if from == to {
+                        continue;
+                    }
+
+                    let message = self.messages.remove(from);
+                    self.messages.insert(to, message);
+
+                    let start = min(from, to);
+                    let end = std::cmp::max(from, to) + 1;
+                    if ignore_state_machine
+                        || (start..=end).all(|index| is_valid_at(&self.messages, index))
+                    {
+                        return;
+                    }
+
+                    let message = self.messages.remove(to);
+                    self.messages.insert(from, message);
+                }
+            }
+            SequenceMutation::MutateMessage => {
+                for _i in 0..MAX_ATTEMPTS {
+                    let index = mutator.gen_range(0, len);
+                    let message_max_size = remaining_size
+                        .map(|remaining| remaining + self.messages[index].serialized_size());
+                    let message_constraints = message_max_size.map(|max_size| {
+                        let mut constraints = Constraints::new();
+                        constraints.max_size(max_size);
+
+                        constraints
+                    });
+
+                    let mut message = self.messages[index].clone();
+                    message.mutate(mutator, message_constraints.as_ref());
+
+                    if message_max_size
+                        .map(|max_size| message.serialized_size() > max_size)
+                        .unwrap_or(false)
+                    {
+                        continue;
+                    }
+
+                    let original = std::mem::replace(&mut self.messages[index], message);
+                    if ignore_state_machine
+                        || (is_valid_at(&self.messages, index)
+                            && is_valid_at(&self.messages, index + 1))
+                    {
+                        return;
+                    }
+
+                    self.messages[index] = original;
+                }
+            }
+        }
+    }
+}
+
 impl Mutatable for bool {
     type RangeType = u8;
 
@@ -421,6 +682,8 @@ impl Mutatable for bool {
     }
 }
 
+/// Enums which only implement [ToPrimitive] can't be mapped back from a discriminant, so their
+/// value is converted to `Invalid` and its bits are mutated
 impl<T, I> Mutatable for UnsafeEnum<T, I>
 where
     T: ToPrimitive<Output = I>,
@@ -437,24 +700,334 @@ where
 {
     type RangeType = I;
 
+    default fn mutate<R: Rng>(
+        &mut self,
+        mutator: &mut Mutator<R>,
+        _constraints: Option<&Constraints<Self::RangeType>>,
+    ) {
+        let mut value = self.to_primitive();
+        mutator.mutate(&mut value);
+        *self = UnsafeEnum::Invalid(value);
+    }
+}
+
+/// Enums which implement [FromPrimitive] switch between variants, go invalid, and snap back to
+/// valid variants
+impl<T, I> Mutatable for UnsafeEnum<T, I>
+where
+    T: FromPrimitive<Output = I>,
+    I: BitXor<Output = I>
+        + NumCast
+        + Bounded
+        + Copy
+        + PartialEq
+        + std::fmt::Debug
+        + Default
+        + DangerousNumber<I>
+        + std::fmt::Display
+        + WrappingAdd
+        + WrappingSub
+        + 'static,
+{
     fn mutate<R: Rng>(
         &mut self,
         mutator: &mut Mutator<R>,
         _constraints: Option<&Constraints<Self::RangeType>>,
     ) {
-        if let UnsafeEnum::Valid(ref value) = *self {
-            *self = UnsafeEnum::Invalid(value.to_primitive());
+        self.normalize();
+
+        let valid_values = T::primitive_values();
+        if valid_values.is_empty() {
+            let mut value = self.to_primitive();
+            mutator.mutate(&mut value);
+            *self = UnsafeEnum::Invalid(value);
+
+            return;
         }
 
-        match *self {
-            UnsafeEnum::Invalid(ref mut value) => {
-                mutator.mutate(value);
+        // valid values usually switch to another variant (unless there is no other variant), but
+        // invalid values are equally likely to be corrupted further as they are to be brought back
+        let mutation = match *self {
+            UnsafeEnum::Valid(_)
+                if valid_values.len() < 2
+                    || mutator.gen_chance(crate::mutator::CHANCE_TO_PICK_INVALID_ENUM) =>
+            {
+                UnsafeEnumMutation::GoInvalid
+            }
+            UnsafeEnum::Valid(_) => UnsafeEnumMutation::SwitchVariant,
+            UnsafeEnum::Invalid(_) => UnsafeEnumMutation::new_fuzzed(mutator, None),
+        };
+
+        let current = self.to_primitive();
+
+        let value = match mutation {
+            UnsafeEnumMutation::SwitchVariant => {
+                let others: Vec<I> = valid_values
+                    .iter()
+                    .cloned()
+                    .filter(|value| *value != current)
+                    .collect();
+
+                *others.choose(&mut mutator.rng).unwrap_or(&valid_values[0])
+            }
+            UnsafeEnumMutation::GoInvalid => invalid_discriminant(mutator, current, valid_values),
+            UnsafeEnumMutation::SnapToValid => nearest_discriminant(current, valid_values),
+        };
+
+        *self = UnsafeEnum::from_primitive(value);
+    }
+}
+
+/// Picks a discriminant which is next to a valid one, is a dangerous number, or is a corrupted
+/// version of `current`. This may still return a valid discriminant if every attempt happens to
+/// land on one.
+fn invalid_discriminant<R: Rng, I>(mutator: &mut Mutator<R>, current: I, valid_values: &[I]) -> I
+where
+    I: BitXor<Output = I>
+        + NumCast
+        + Bounded
+        + Copy
+        + PartialEq
+        + std::fmt::Debug
+        + Default
+        + DangerousNumber<I>
+        + std::fmt::Display
+        + WrappingAdd
+        + WrappingSub,
+{
+    let one: I = num_traits::cast(1u8).unwrap();
+    let mut value = current;
+
+    for _ in 0..5 {
+        value = match mutator.gen_range(0, 3) {
+            0 => {
+                let neighbor = *valid_values.choose(&mut mutator.rng).unwrap();
+                if mutator.gen_chance(0.5) {
+                    neighbor.wrapping_add(&one)
+                } else {
+                    neighbor.wrapping_sub(&one)
+                }
+            }
+            1 => I::select_dangerous_number(&mut mutator.rng),
+            _ => {
+                let mut value = current;
+                mutator.mutate(&mut value);
+                value
+            }
+        };
+
+        if !valid_values.contains(&value) {
+            break;
+        }
+    }
+
+    value
+}
+
+/// Returns the valid discriminant closest to `value`
+fn nearest_discriminant<I: NumCast + Copy>(value: I, valid_values: &[I]) -> I {
+    let distance = |other: I| {
+        let value: f64 = num_traits::cast(value).unwrap_or(0.0);
+        let other: f64 = num_traits::cast(other).unwrap_or(0.0);
+
+        (value - other).abs()
+    };
+
+    valid_values
+        .iter()
+        .cloned()
+        .min_by(|a, b| distance(*a).partial_cmp(&distance(*b)).unwrap())
+        .unwrap()
+}
+
+/// The most redundant continuation bytes that mutation will pad a [VarInt] with
+const MAX_VARINT_OVERLONG_BYTES: usize = 8;
+
+impl<T> Mutatable for VarInt<T>
+where
+    T: VarIntValue + Mutatable<RangeType = T> + Bounded + Default + std::fmt::Debug,
+{
+    type RangeType = T;
+
+    fn mutate<R: Rng>(
+        &mut self,
+        mutator: &mut Mutator<R>,
+        constraints: Option<&Constraints<Self::RangeType>>,
+    ) {
+        let max_padding = extra_size_budget(constraints, self.serialized_size())
+            .map_or(MAX_VARINT_OVERLONG_BYTES, |budget| {
+                min(budget, MAX_VARINT_OVERLONG_BYTES)
+            });
+
+        match VarIntMutation::new_fuzzed(mutator, None) {
+            VarIntMutation::Overlong if max_padding > 0 => {
+                self.overlong_bytes = mutator.gen_range(1, max_padding + 1) as u8;
+            }
+            VarIntMutation::Truncate => {
+                self.truncated = !self.truncated;
+            }
+            VarIntMutation::Canonicalize => {
+                self.canonicalize();
+            }
+            _ => {
+                self.value.mutate(mutator, constraints);
             }
-            _ => unreachable!(),
         }
     }
 }
 
+/// Characters which are likely to trip up text handling: NULs, combining characters, bidi
+/// controls, zero-width characters, and byte order marks
+static SPECIAL_CHARS: &[char] = &[
+    '\u{0000}', '\u{0300}', '\u{0301}', '\u{0336}', '\u{20dd}', '\u{200b}', '\u{200c}', '\u{200d}',
+    '\u{200e}', '\u{200f}', '\u{202a}', '\u{202b}', '\u{202c}', '\u{202d}', '\u{202e}', '\u{2066}',
+    '\u{2067}', '\u{2068}', '\u{2069}', '\u{feff}', '\u{fffd}',
+];
+
+/// Byte sequences which are not valid UTF-8
+static INVALID_UTF8_SEQUENCES: &[&[u8]] = &[
+    &[0xc0, 0xaf],             // overlong '/'
+    &[0xe0, 0x80, 0xaf],       // overlong '/'
+    &[0xf0, 0x80, 0x80, 0xaf], // overlong '/'
+    &[0xc0, 0x80],             // overlong NUL
+    &[0x80],                   // lone continuation byte
+    &[0xbf],                   // lone continuation byte
+    &[0xc3],                   // truncated 2-byte sequence
+    &[0xe2, 0x82],             // truncated 3-byte sequence
+    &[0xed, 0xa0, 0x80],       // encoded high surrogate
+    &[0xed, 0xbf, 0xbf],       // encoded low surrogate
+    &[0xf4, 0x90, 0x80, 0x80], // codepoint above U+10FFFF
+    &[0xfe],
+    &[0xff],
+];
+
+/// Returns the offsets of every character in `bytes` as well as the end of the buffer. Bytes
+/// which aren't continuation bytes are considered to start a character, which is correct for valid
+/// UTF-8 and good enough for malformed data.
+fn char_boundaries(bytes: &[u8]) -> Vec<usize> {
+    bytes
+        .iter()
+        .enumerate()
+        .filter(|(_, b)| (**b & 0xc0) != 0x80)
+        .map(|(i, _)| i)
+        .chain(std::iter::once(bytes.len()))
+        .collect()
+}
+
+/// Picks a random non-empty range of characters. `boundaries` must contain at least 2 entries.
+fn random_char_range<R: Rng>(boundaries: &[usize], mutator: &mut Mutator<R>) -> (usize, usize) {
+    let start = mutator.gen_range(0, boundaries.len() - 1);
+    let end = mutator.gen_range(start + 1, boundaries.len());
+
+    (boundaries[start], boundaries[end])
+}
+
+/// Performs a single character-level mutation on UTF-8 encoded `bytes`. Valid UTF-8 stays valid
+/// unless `allow_invalid` is set, in which case malformed sequences may be inserted. The string
+/// won't grow by more than `max_size` bytes.
+fn mutate_utf8_bytes<R: Rng>(
+    bytes: &mut Vec<u8>,
+    mutator: &mut Mutator<R>,
+    max_size: Option<usize>,
+    allow_invalid: bool,
+) {
+    const CHANCE_TO_INSERT_INVALID_SEQUENCE: f64 = 0.10;
+    const MAX_RUN_LENGTH: usize = 0x400;
+
+    let original_len = bytes.len();
+    let boundaries = char_boundaries(bytes);
+    let position = boundaries[mutator.gen_range(0, boundaries.len())];
+    let mut encoded = [0u8; 4];
+
+    if allow_invalid && mutator.gen_chance(CHANCE_TO_INSERT_INVALID_SEQUENCE) {
+        let sequence = INVALID_UTF8_SEQUENCES.choose(&mut mutator.rng).unwrap();
+        bytes.splice(position..position, sequence.iter().cloned());
+    } else {
+        let mut mutation = StringMutation::new_fuzzed(mutator, None);
+        if boundaries.len() < 2 {
+            // there's nothing to replace, remove, or duplicate in an empty string
+            mutation = StringMutation::InsertChar;
+        }
+
+        match mutation {
+            StringMutation::ReplaceChar => {
+                let idx = mutator.gen_range(0, boundaries.len() - 1);
+                let c = Utf8Char::new_fuzzed(mutator, None).0;
+                bytes.splice(
+                    boundaries[idx]..boundaries[idx + 1],
+                    c.encode_utf8(&mut encoded).bytes(),
+                );
+            }
+            StringMutation::InsertChar => {
+                let c = Utf8Char::new_fuzzed(mutator, None).0;
+                bytes.splice(position..position, c.encode_utf8(&mut encoded).bytes());
+            }
+            StringMutation::InsertSpecialChar => {
+                let c = *SPECIAL_CHARS.choose(&mut mutator.rng).unwrap();
+                bytes.splice(position..position, c.encode_utf8(&mut encoded).bytes());
+            }
+            StringMutation::RemoveRange => {
+                let (start, end) = random_char_range(&boundaries, mutator);
+                bytes.drain(start..end);
+            }
+            StringMutation::DuplicateRange => {
+                let (start, end) = random_char_range(&boundaries, mutator);
+                let duplicate = bytes[start..end].to_vec();
+                bytes.splice(end..end, duplicate);
+            }
+            StringMutation::InsertLongRun => {
+                let c = if mutator.gen_chance(0.5) {
+                    *SPECIAL_CHARS.choose(&mut mutator.rng).unwrap()
+                } else {
+                    Utf8Char::new_fuzzed(mutator, None).0
+                };
+
+                let run_length = mutator.gen_range(1, MAX_RUN_LENGTH);
+                let encoded = c.encode_utf8(&mut encoded).as_bytes();
+                let run: Vec<u8> = encoded
+                    .iter()
+                    .cycle()
+                    .take(encoded.len() * run_length)
+                    .cloned()
+                    .collect();
+                bytes.splice(position..position, run);
+            }
+        }
+    }
+
+    // respect the size constraint by trimming whole characters off of the end
+    if let Some(max_size) = max_size {
+        let limit = original_len + max_size;
+        if bytes.len() > limit {
+            let end = char_boundaries(bytes)
+                .into_iter()
+                .rev()
+                .find(|boundary| *boundary <= limit)
+                .unwrap_or(0);
+            bytes.truncate(end);
+        }
+    }
+}
+
+impl Mutatable for String {
+    type RangeType = usize;
+
+    fn mutate<R: Rng>(
+        &mut self,
+        mutator: &mut Mutator<R>,
+        constraints: Option<&Constraints<Self::RangeType>>,
+    ) {
+        trace!("performing mutation on a String");
+
+        let max_size = extra_size_budget(constraints, self.len());
+
+        let mut bytes = std::mem::take(self).into_bytes();
+        mutate_utf8_bytes(&mut bytes, mutator, max_size, false);
+
+        *self = String::from_utf8(bytes).expect("String mutations should preserve valid UTF-8");
+    }
+}
+
 impl Mutatable for AsciiString {
     type RangeType = u8;
 
@@ -465,15 +1038,49 @@ impl Mutatable for AsciiString {
     ) {
         trace!("performing mutation on an AsciiString");
 
+        if self.inner.is_empty() {
+            return;
+        }
+
         // TODO: Implement logic for resizing?
-        let num_mutations = mutator.gen_range(1, self.inner.len());
-        for idx in index::sample(&mut mutator.rng, self.inner.len(), num_mutations).iter() {
-            self.inner[idx] = AsciiChar::new_fuzzed(mutator, None);
+        let mut chars: Vec<char> = self.inner.chars().collect();
+        let num_mutations = mutator.gen_range(1, chars.len() + 1);
+        for idx in index::sample(&mut mutator.rng, chars.len(), num_mutations).iter() {
+            chars[idx] = AsciiChar::new_fuzzed(mutator, None).0;
         }
+
+        self.inner = chars.into_iter().collect();
     }
 }
 
-impl Mutatable for Utf8String {
+/// Occasionally desynchronizes the length prefix from the data. Otherwise the value is mutated,
+/// truncated to fit in the prefix, and the prefix is made exact again.
+impl<P, T> Mutatable for LengthPrefixed<P, T>
+where
+    P: LengthPrefix + NumCast + Bounded + Copy + WrappingAdd + WrappingSub + DangerousNumber<P>,
+    T: Mutatable + EncodedSequence,
+{
+    type RangeType = T::RangeType;
+
+    fn mutate<R: Rng>(
+        &mut self,
+        mutator: &mut Mutator<R>,
+        constraints: Option<&Constraints<Self::RangeType>>,
+    ) {
+        if mutator.gen_chance(CHANCE_TO_MISMATCH_LENGTH_PREFIX) {
+            let len = self.value.encoded_len(Encoding::default());
+            self.len_override = Some(mismatched_prefix(len, &mut mutator.rng));
+            return;
+        }
+
+        self.value.mutate(mutator, constraints);
+        fit_to_prefix::<P, T>(&mut self.value, Encoding::default());
+        self.len_override = None;
+    }
+}
+
+/// Picks a new seed, which changes every padding byte of the container
+impl Mutatable for PaddingSeed {
     type RangeType = u8;
 
     fn mutate<R: Rng>(
@@ -481,13 +1088,50 @@ impl Mutatable for Utf8String {
         mutator: &mut Mutator<R>,
         _constraints: Option<&Constraints<Self::RangeType>>,
     ) {
-        trace!("performing mutation on a Utf8String");
+        self.seed = Some(mutator.rng.gen());
+    }
+}
 
-        // TODO: Implement logic for resizing?
-        let num_mutations = mutator.gen_range(1, self.inner.len());
-        for idx in index::sample(&mut mutator.rng, self.inner.len(), num_mutations).iter() {
-            self.inner[idx] = Utf8Char::new_fuzzed(mutator, None);
+/// Occasionally replaces the tag with another variant's tag or an arbitrary value. Otherwise the
+/// value is mutated and the tag is made to match it again.
+impl<T> Mutatable for TagOverride<T>
+where
+    T: Mutatable + TaggedEnum,
+{
+    type RangeType = T::RangeType;
+
+    fn mutate<R: Rng>(
+        &mut self,
+        mutator: &mut Mutator<R>,
+        constraints: Option<&Constraints<Self::RangeType>>,
+    ) {
+        if mutator.gen_chance(CHANCE_TO_MISMATCH_ENUM_TAG) {
+            let tag = match T::tags().choose(&mut mutator.rng) {
+                Some(tag) if mutator.gen_chance(0.5) => *tag,
+                _ => T::arbitrary_tag(mutator),
+            };
+
+            self.tag_override = Some(tag);
+            return;
         }
+
+        self.value.mutate(mutator, constraints);
+        self.tag_override = None;
+    }
+}
+
+impl Mutatable for Utf8String {
+    type RangeType = u8;
+
+    fn mutate<R: Rng>(
+        &mut self,
+        mutator: &mut Mutator<R>,
+        constraints: Option<&Constraints<Self::RangeType>>,
+    ) {
+        trace!("performing mutation on a Utf8String");
+
+        let max_size = extra_size_budget(constraints, self.inner.len());
+        mutate_utf8_bytes(&mut self.inner, mutator, max_size, true);
     }
 }
 
@@ -506,7 +1150,7 @@ macro_rules! impl_mutatable {
     }
 }
 
-impl_mutatable!(u64, u32, u16, u8);
+impl_mutatable!(u128, u64, u32, u16, u8);
 
 impl Mutatable for i8 {
     type RangeType = i8;
@@ -568,18 +1212,45 @@ impl Mutatable for i64 {
     }
 }
 
-impl<T> Mutatable for [T; 0]
-where
-    T: Mutatable,
-{
-    type RangeType = u8;
+impl Mutatable for i128 {
+    type RangeType = i128;
 
+    #[inline(always)]
     fn mutate<R: Rng>(
         &mut self,
-        _mutator: &mut Mutator<R>,
+        mutator: &mut Mutator<R>,
         _constraints: Option<&Constraints<Self::RangeType>>,
     ) {
-        // nop
+        let mut val = *self as u128;
+        mutator.mutate(&mut val);
+        *self = val as i128;
+    }
+}
+
+impl Mutatable for char {
+    type RangeType = u32;
+
+    /// Mutates the codepoint as a `u32` and then snaps the result back to a valid `char`.
+    /// Surrogates are moved below the surrogate range and values above `char::MAX` wrap around.
+    fn mutate<R: Rng>(
+        &mut self,
+        mutator: &mut Mutator<R>,
+        _constraints: Option<&Constraints<Self::RangeType>>,
+    ) {
+        if mutator.gen_chance(crate::mutator::CHANCE_TO_PICK_DANGEROUS_CHAR) {
+            *self = char::select_dangerous_number(&mut mutator.rng);
+            return;
+        }
+
+        let mut val = *self as u32;
+        mutator.mutate(&mut val);
+
+        let mut val = val % (char::MAX as u32 + 1);
+        if (0xD800..=0xDFFF).contains(&val) {
+            val -= 0x800;
+        }
+
+        *self = std::char::from_u32(val).expect("codepoint should be valid after snapping");
     }
 }
 
@@ -654,28 +1325,331 @@ where
     }
 }
 
-macro_rules! impl_mutatable_array {
-    ( $($size:expr),* ) => {
+impl<T, const N: usize> Mutatable for [T; N]
+where
+    T: Mutatable + SerializedSize,
+    T::RangeType: Clone,
+{
+    type RangeType = T::RangeType;
+
+    #[inline(always)]
+    default fn mutate<R: Rng>(
+        &mut self,
+        mutator: &mut Mutator<R>,
+        constraints: Option<&Constraints<Self::RangeType>>,
+    ) {
+        // Treat this as a slice
+        self[..].mutate(mutator, constraints);
+    }
+}
+
+impl<const N: usize> Mutatable for [u8; N] {
+    fn mutate<R: Rng>(
+        &mut self,
+        mutator: &mut Mutator<R>,
+        _constraints: Option<&Constraints<Self::RangeType>>,
+    ) {
+        if N == 0 {
+            return;
+        }
+
+        // rather than visiting every byte of potentially large buffers, only mutate a handful of
+        // randomly selected bytes
+        let num_mutations = mutator.gen_weighted_range(1, N + 1, Weighted::Min);
+        for idx in index::sample(&mut mutator.rng, N, num_mutations).iter() {
+            mutator.mutate(&mut self[idx]);
+
+            if mutator.should_early_bail_mutation() {
+                return;
+            }
+        }
+    }
+}
+
+macro_rules! impl_mutatable_tuple {
+    ( $( ( $($name:ident . $idx:tt),+ ) ),* ) => {
         $(
-            impl<T> Mutatable for [T; $size]
+            impl<$($name),+> Mutatable for ($($name,)+)
+            where $($name: Mutatable + SerializedSize),+ {
+                type RangeType = u8;
+
+                fn mutate<R: Rng>(&mut self, mutator: &mut Mutator<R>, constraints: Option<&Constraints<Self::RangeType>>) {
+                    // like derived structs, the max size given to each element is only the extra data it may
+                    // consume on top of its current size
+                    let mut max_size = extra_size_budget(constraints, self.serialized_size());
+
+                    $(
+                        let previous_size = self.$idx.serialized_size();
+                        let constraints = max_size.map(|max_size| {
+                            let mut c = Constraints::new();
+                            c.max_size(max_size);
+                            c.base_object_size_accounted_for = true;
+
+                            c
+                        });
+
+                        self.$idx.mutate(mutator, constraints.as_ref());
+
+                        if let Some(ref mut max_size) = max_size {
+                            *max_size = (*max_size + previous_size).saturating_sub(self.$idx.serialized_size());
+                        }
+                    )+
+                }
+            }
+        )*
+    }
+}
+
+impl_mutatable_tuple!(
+    (T0.0),
+    (T0.0, T1.1),
+    (T0.0, T1.1, T2.2),
+    (T0.0, T1.1, T2.2, T3.3),
+    (T0.0, T1.1, T2.2, T3.3, T4.4),
+    (T0.0, T1.1, T2.2, T3.3, T4.4, T5.5),
+    (T0.0, T1.1, T2.2, T3.3, T4.4, T5.5, T6.6),
+    (T0.0, T1.1, T2.2, T3.3, T4.4, T5.5, T6.6, T7.7),
+    (T0.0, T1.1, T2.2, T3.3, T4.4, T5.5, T6.6, T7.7, T8.8),
+    (T0.0, T1.1, T2.2, T3.3, T4.4, T5.5, T6.6, T7.7, T8.8, T9.9),
+    (T0.0, T1.1, T2.2, T3.3, T4.4, T5.5, T6.6, T7.7, T8.8, T9.9, T10.10),
+    (T0.0, T1.1, T2.2, T3.3, T4.4, T5.5, T6.6, T7.7, T8.8, T9.9, T10.10, T11.11)
+);
+
+/// A collection whose entries can't be mutated in place (e.g. map keys or set elements). Entries
+/// are instead taken out of the collection, mutated, and inserted back in.
+///
+/// Entries are indexed in sorted order so that the same mutator picks the same entry on every
+/// run, which `HashMap` and `HashSet` iteration order alone wouldn't guarantee.
+trait EntryCollection {
+    type Entry;
+
+    fn entry_count(&self) -> usize;
+
+    fn take_entry(&mut self, idx: usize) -> Option<Self::Entry>;
+
+    fn insert_entry(&mut self, entry: Self::Entry);
+}
+
+impl<K, V> EntryCollection for HashMap<K, V>
+where
+    K: Eq + Hash + Ord + Clone,
+{
+    type Entry = (K, V);
+
+    fn entry_count(&self) -> usize {
+        self.len()
+    }
+
+    fn take_entry(&mut self, idx: usize) -> Option<(K, V)> {
+        let mut keys: Vec<&K> = self.keys().collect();
+        keys.sort_unstable();
+
+        let key = (*keys.get(idx)?).clone();
+        self.remove_entry(&key)
+    }
+
+    fn insert_entry(&mut self, (key, value): (K, V)) {
+        self.insert(key, value);
+    }
+}
+
+impl<K, V> EntryCollection for BTreeMap<K, V>
+where
+    K: Ord + Clone,
+{
+    type Entry = (K, V);
+
+    fn entry_count(&self) -> usize {
+        self.len()
+    }
+
+    fn take_entry(&mut self, idx: usize) -> Option<(K, V)> {
+        let key = self.keys().nth(idx)?.clone();
+        self.remove_entry(&key)
+    }
+
+    fn insert_entry(&mut self, (key, value): (K, V)) {
+        self.insert(key, value);
+    }
+}
+
+impl<T> EntryCollection for HashSet<T>
+where
+    T: Eq + Hash + Ord + Clone,
+{
+    type Entry = T;
+
+    fn entry_count(&self) -> usize {
+        self.len()
+    }
+
+    fn take_entry(&mut self, idx: usize) -> Option<T> {
+        let mut elements: Vec<&T> = self.iter().collect();
+        elements.sort_unstable();
+
+        let element = (*elements.get(idx)?).clone();
+        self.take(&element)
+    }
+
+    fn insert_entry(&mut self, element: T) {
+        self.insert(element);
+    }
+}
+
+impl<T> EntryCollection for BTreeSet<T>
+where
+    T: Ord + Clone,
+{
+    type Entry = T;
+
+    fn entry_count(&self) -> usize {
+        self.len()
+    }
+
+    fn take_entry(&mut self, idx: usize) -> Option<T> {
+        let element = self.iter().nth(idx)?.clone();
+        self.take(&element)
+    }
+
+    fn insert_entry(&mut self, element: T) {
+        self.insert(element);
+    }
+}
+
+/// Inserts, removes, or mutates an entry of a map or set. A mutated key which collides with an
+/// existing key replaces that entry.
+fn mutate_collection<C, R>(
+    collection: &mut C,
+    mutator: &mut Mutator<R>,
+    constraints: Option<&Constraints<usize>>,
+) where
+    C: EntryCollection + SerializedSize,
+    C::Entry: Mutatable + NewFuzzed + SerializedSize,
+    R: Rng,
+{
+    // the extra space that entries may consume on top of the collection's current size
+    let max_size = extra_size_budget(constraints, collection.serialized_size());
+
+    let can_grow = max_size
+        .map(|s| s >= C::Entry::max_default_object_size())
+        .unwrap_or(true);
+
+    let mutation = if collection.entry_count() == 0 {
+        CollectionMutation::Insert
+    } else {
+        CollectionMutation::new_fuzzed(mutator, None)
+    };
+
+    match mutation {
+        CollectionMutation::Insert => {
+            if !can_grow {
+                return;
+            }
+
+            let constraints = max_size.map(|max_size| {
+                let mut c = Constraints::new();
+                c.max_size(max_size);
+                c.base_object_size_accounted_for = true;
+
+                c
+            });
+
+            let entry = C::Entry::new_fuzzed(mutator, constraints.as_ref());
+            if max_size
+                .map(|max_size| entry.serialized_size() <= max_size)
+                .unwrap_or(true)
+            {
+                collection.insert_entry(entry);
+            }
+        }
+        CollectionMutation::Remove => {
+            let idx = mutator.gen_range(0, collection.entry_count());
+            collection.take_entry(idx);
+        }
+        CollectionMutation::MutateEntry => {
+            let idx = mutator.gen_range(0, collection.entry_count());
+            if let Some(mut entry) = collection.take_entry(idx) {
+                let constraints = max_size.map(|max_size| {
+                    let mut c = Constraints::new();
+                    c.max_size(max_size);
+                    c.base_object_size_accounted_for = true;
+
+                    c
+                });
+
+                entry.mutate(mutator, constraints.as_ref());
+                collection.insert_entry(entry);
+            }
+        }
+    }
+}
+
+macro_rules! impl_mutatable_map {
+    ( $( $map:ident < K: $($bound:path),+ > ),* ) => {
+        $(
+            impl<K, V> Mutatable for $map<K, V>
             where
-                T: Mutatable + SerializedSize,
-                T::RangeType: Clone,
+                K: Mutatable + NewFuzzed + SerializedSize + Clone $(+ $bound)+,
+                V: Mutatable + NewFuzzed + SerializedSize,
             {
-                type RangeType = T::RangeType;
+                type RangeType = usize;
 
-                #[inline(always)]
                 fn mutate<R: Rng>(&mut self, mutator: &mut Mutator<R>, constraints: Option<&Constraints<Self::RangeType>>) {
-                    // Treat this as a slice
-                    self[..].mutate(mutator, constraints);
+                    mutate_collection(self, mutator, constraints);
                 }
             }
         )*
     }
 }
 
-impl_mutatable_array!(
-    1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26,
-    27, 28, 29, 30, 31, 32, 33, 34, 35, 36, 37, 38, 39, 40, 41, 42, 43, 44, 45, 46, 47, 48, 49, 50,
-    51, 52, 53, 54, 55, 56, 57, 58, 59, 60
-);
+impl_mutatable_map!(HashMap<K: Eq, Hash, Ord>, BTreeMap<K: Ord>);
+
+macro_rules! impl_mutatable_set {
+    ( $( $set:ident < T: $($bound:path),+ > ),* ) => {
+        $(
+            impl<T> Mutatable for $set<T>
+            where
+                T: Mutatable + NewFuzzed + SerializedSize + Clone $(+ $bound)+,
+            {
+                type RangeType = usize;
+
+                fn mutate<R: Rng>(&mut self, mutator: &mut Mutator<R>, constraints: Option<&Constraints<Self::RangeType>>) {
+                    mutate_collection(self, mutator, constraints);
+                }
+            }
+        )*
+    }
+}
+
+impl_mutatable_set!(HashSet<T: Eq, Hash, Ord>, BTreeSet<T: Ord>);
+
+impl<T> Mutatable for VecDeque<T>
+where
+    T: Mutatable + NewFuzzed + SerializedSize,
+    <T as Mutatable>::RangeType: Clone,
+{
+    type RangeType = usize;
+
+    fn mutate<R: Rng>(
+        &mut self,
+        mutator: &mut Mutator<R>,
+        constraints: Option<&Constraints<Self::RangeType>>,
+    ) {
+        // mutate the elements as a contiguous Vec so that we get the same resizing behavior
+        let mut vec: Vec<T> = std::mem::take(self).into();
+
+        let constraints = constraints.and_then(|c| {
+            c.max_size.map(|max_size| {
+                let mut new_constraints = Constraints::new();
+                new_constraints.base_object_size_accounted_for = c.base_object_size_accounted_for;
+                new_constraints.max_size = Some(max_size);
+
+                new_constraints
+            })
+        });
+
+        vec.mutate(mutator, constraints.as_ref());
+
+        *self = vec.into();
+    }
+}
@@ -18,6 +18,10 @@ use serde::{Deserialize, Serialize};
 pub const CHANCE_TO_REPEAT_ARRAY_VALUE: f64 = 0.05;
 pub const CHANCE_TO_PICK_INVALID_ENUM: f64 = 0.10;
 pub const CHANCE_TO_IGNORE_MIN_MAX: f64 = 0.05;
+pub const CHANCE_TO_VIOLATE_STATE_MACHINE: f64 = 0.05;
+pub const CHANCE_TO_PICK_DANGEROUS_CHAR: f64 = 0.10;
+pub const CHANCE_TO_MISMATCH_LENGTH_PREFIX: f64 = 0.05;
+pub const CHANCE_TO_MISMATCH_ENUM_TAG: f64 = 0.05;
 
 #[repr(u8)]
 #[derive(Debug, Copy, Clone, NewFuzzed)]
@@ -137,7 +141,7 @@ impl<R: Rng> Mutator<R> {
 
         trace!("xoring bit {}", idx);
 
-        *num = (*num) ^ num::cast(1u64 << idx).unwrap();
+        *num = (*num) ^ num::cast(1u128 << idx).unwrap();
     }
 
     /// Flip more than 1 bit in this number. This is a flip potentially up to
@@ -149,10 +153,10 @@ impl<R: Rng> Mutator<R> {
         let num_bits = (std::mem::size_of::<T>() * 8) as u8;
         let bits_to_flip = self.rng.gen_range(1, num_bits + 1) as usize;
 
-        // 64 is chosen here as it's the the max primitive size (in bits) that we support
+        // 128 is chosen here as it's the the max primitive size (in bits) that we support
         // we choose to do this approach over a vec to avoid an allocation
-        assert!(num_bits <= 64);
-        let mut potential_bit_indices = [0u8; 64];
+        assert!(num_bits <= 128);
+        let mut potential_bit_indices = [0u8; 128];
         for i in 0..num_bits {
             potential_bit_indices[i as usize] = i;
         }
@@ -162,7 +166,7 @@ impl<R: Rng> Mutator<R> {
             .partial_shuffle(&mut self.rng, num_bits as usize);
 
         for idx in bit_indices {
-            *num = (*num) ^ num::cast(1u64 << *idx).unwrap()
+            *num = (*num) ^ num::cast(1u128 << *idx).unwrap()
         }
     }
 
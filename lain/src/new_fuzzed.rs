@@ -1,3 +1,4 @@
+use crate::encoding::{fit_to_prefix, EncodedSequence, Encoding, LengthPrefix};
 use crate::mutator::Mutator;
 
 use crate::rand::seq::SliceRandom;
@@ -5,8 +6,9 @@ use crate::rand::Rng;
 use crate::traits::*;
 use crate::types::*;
 use num_traits::Bounded;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 use std::fmt::Debug;
-use std::mem::MaybeUninit;
+use std::hash::Hash;
 use std::{char, cmp};
 
 impl<T> NewFuzzed for Option<T>
@@ -294,45 +296,120 @@ where
     }
 }
 
-// TODO: Uncomment once const generics are more stable
-// impl<T, const SIZE: usize> NewFuzzed for [T; SIZE]
-// where T: NewFuzzed + Clone {
-//     type RangeType = usize;
+/// Generates a message which may follow `previous` (or start a sequence if there is no previous
+/// message) and which `next` may follow. Returns `None` if no such message was generated within
+/// a reasonable number of attempts.
+pub(crate) fn new_fuzzed_successor<T, R>(
+    mutator: &mut Mutator<R>,
+    previous: Option<&T>,
+    next: Option<&T>,
+    max_size: Option<usize>,
+) -> Option<T>
+where
+    T: NewFuzzed,
+    R: Rng,
+{
+    const MAX_ATTEMPTS: usize = 32;
+
+    for _i in 0..MAX_ATTEMPTS {
+        let constraints = max_size.map(|max_size| {
+            let mut c = Constraints::new();
+            c.max_size(max_size);
+            c.base_object_size_accounted_for = true;
+
+            c
+        });
+
+        let message = T::new_fuzzed(mutator, constraints.as_ref());
+
+        let valid_after_previous = match previous {
+            Some(previous) => message.is_valid_successor(previous),
+            None => message.is_valid_start(),
+        };
+
+        let valid_before_next = next
+            .map(|next| next.is_valid_successor(&message))
+            .unwrap_or(true);
+
+        if valid_after_previous && valid_before_next {
+            return Some(message);
+        }
+    }
+
+    None
+}
+
+impl<T> NewFuzzed for Sequence<T>
+where
+    T: NewFuzzed + SerializedSize,
+{
+    type RangeType = usize;
 
-//     fn new_fuzzed<R: Rng>(mutator: &mut Mutator<R>, constraints: Option<&Constraints<Self::RangeType>>) -> [T; SIZE] {
-//         if constraints.is_some() {
-//             warn!("Constraints passed to new_fuzzed on fixed-size array do nothing");
-//         }
+    fn new_fuzzed<R: Rng>(
+        mutator: &mut Mutator<R>,
+        constraints: Option<&Constraints<Self::RangeType>>,
+    ) -> Sequence<T> {
+        const MAX_NUM_MESSAGES: usize = 16;
+
+        trace!(
+            "Generating random Sequence with constraints: {:#X?}",
+            constraints
+        );
+
+        let (mut min, mut max, weight, max_size) = match constraints {
+            Some(constraints) => (
+                constraints.min.unwrap_or(1),
+                constraints.max.unwrap_or(MAX_NUM_MESSAGES),
+                constraints.weighted,
+                constraints.max_size,
+            ),
+            None => (1, MAX_NUM_MESSAGES, Weighted::None, None),
+        };
+
+        if let Some(max_size) = max_size {
+            if T::max_default_object_size() != 0 {
+                max = cmp::min(max, max_size / T::max_default_object_size());
+            }
+        }
+
+        if min > max {
+            min = max;
+        }
+
+        let num_messages = if min == max {
+            min
+        } else {
+            mutator.gen_weighted_range(min, max, weight)
+        };
 
-//         let mut output: MaybeUninit<[T; SIZE]> = MaybeUninit::uninit();
-//         let arr_ptr = output.as_mut_ptr() as *mut T;
+        let mut messages: Vec<T> = Vec::with_capacity(num_messages);
+        let mut used_size = 0;
+
+        // each message is generated based off of the previous one, so if the state machine has a
+        // dead end the sequence just ends early
+        for _i in 0..num_messages {
+            let remaining_size = max_size.map(|max_size| max_size - used_size);
+            let message = match new_fuzzed_successor(mutator, messages.last(), None, remaining_size)
+            {
+                Some(message) => message,
+                None => break,
+            };
 
-//         let mut idx = 0;
-//         let mut element: T = T::new_fuzzed(mutator, None);
-//         while idx < SIZE {
-//             arr_ptr.add(idx).write(element.clone());
+            if let Some(max_size) = max_size {
+                let message_size = message.serialized_size();
+                if used_size + message_size > max_size {
+                    break;
+                }
 
-//             idx += 1;
-//             if SIZE - idx > 0 {
-//                 if mutator.gen_chance(crate::mutator::CHANCE_TO_REPEAT_ARRAY_VALUE) {
-//                     let repeat_end_idx = mutator.gen_range(idx, SIZE);
-//                     while idx < repeat_end_idx {
-//                         arr_ptr.add(idx).write(element.clone());
-//                         idx += 1;
-//                     }
+                used_size += message_size;
+            }
 
-//                     if SIZE - idx > 0 {
-//                         element = T::new_fuzzed(mutator, None);
-//                     }
-//                 } else {
-//                     element = T::new_fuzzed(mutator, None);
-//                 }
-//             }
-//         }
+            messages.push(message);
+        }
 
-//         unsafe { output.assume_init() }
-//     }
-// }
+        Sequence { messages }
+    }
+}
 
 impl<T, I> NewFuzzed for UnsafeEnum<T, I>
 where
@@ -351,7 +428,7 @@ where
         );
 
         if mutator.gen_chance(crate::mutator::CHANCE_TO_PICK_INVALID_ENUM) {
-            UnsafeEnum::Invalid(I::new_fuzzed(mutator, constraints))
+            UnsafeEnum::from_raw_discriminant(I::new_fuzzed(mutator, constraints))
         } else {
             // TODO/BUG: We should be passing on the constraints, but all
             // objects are generated with RangeType = u8, which causes
@@ -361,131 +438,184 @@ where
     }
 }
 
-impl NewFuzzed for Utf8String {
-    type RangeType = usize;
+/// Builds an [UnsafeEnum] from a random discriminant
+trait FromRawDiscriminant<I> {
+    fn from_raw_discriminant(value: I) -> Self;
+}
+
+impl<T, I> FromRawDiscriminant<I> for UnsafeEnum<T, I> {
+    default fn from_raw_discriminant(value: I) -> Self {
+        UnsafeEnum::Invalid(value)
+    }
+}
+
+/// The random value may happen to be a real discriminant, which is only detectable for enums that
+/// implement [FromPrimitive]
+impl<T, I> FromRawDiscriminant<I> for UnsafeEnum<T, I>
+where
+    T: FromPrimitive<Output = I>,
+    I: Copy,
+{
+    fn from_raw_discriminant(value: I) -> Self {
+        UnsafeEnum::from_primitive(value)
+    }
+}
+
+impl<T> NewFuzzed for VarInt<T>
+where
+    T: VarIntValue + NewFuzzed<RangeType = T> + Bounded + Debug + Default,
+{
+    type RangeType = T;
 
     fn new_fuzzed<R: Rng>(
         mutator: &mut Mutator<R>,
         constraints: Option<&Constraints<Self::RangeType>>,
     ) -> Self {
-        let min: Self::RangeType;
-        let max: Self::RangeType;
-        let weight: Weighted;
-        let mut output: Utf8String;
-
-        trace!(
-            "Generating random UtfString with constraints: {:#?}",
-            constraints
-        );
+        VarInt::new(T::new_fuzzed(mutator, constraints))
+    }
+}
 
-        // if no min/max were supplied, we'll take a conservative approach
-        match constraints {
-            Some(ref constraints) => {
-                min = constraints.min.unwrap_or(0);
-                max = constraints.max.unwrap_or(256);
-                weight = constraints.weighted;
-            }
-            None => {
-                min = 0;
-                max = 256;
-                weight = Weighted::None;
+/// Generates a string whose length in characters honors the min/max constraints and whose length
+/// in bytes honors the max size constraint. Characters are occasionally repeated.
+fn new_fuzzed_string<R, F>(
+    mutator: &mut Mutator<R>,
+    constraints: Option<&Constraints<usize>>,
+    mut gen_char: F,
+) -> String
+where
+    R: Rng,
+    F: FnMut(&mut Mutator<R>) -> char,
+{
+    const MAX_NUM_CHARS: usize = 256;
+
+    trace!(
+        "Generating random string with constraints: {:#?}",
+        constraints
+    );
+
+    // if no min/max were supplied, we'll take a conservative approach
+    let (min, max, weight, max_size) = match constraints {
+        Some(constraints) => (
+            constraints.min.unwrap_or(0),
+            constraints.max.unwrap_or(MAX_NUM_CHARS),
+            constraints.weighted,
+            constraints.max_size,
+        ),
+        None => (0, MAX_NUM_CHARS, Weighted::None, None),
+    };
+
+    let string_length = if min >= max {
+        min
+    } else {
+        mutator.gen_weighted_range(min, max, weight)
+    };
+
+    let mut output = String::with_capacity(string_length);
+    let mut chr = gen_char(mutator);
+    let mut repeats_left: usize = 0;
+
+    for idx in 0..string_length {
+        if let Some(max_size) = max_size {
+            if output.len() + chr.len_utf8() > max_size {
+                break;
             }
         }
 
-        let string_length = mutator.gen_weighted_range(min, max, weight);
-
-        output = Utf8String {
-            inner: Vec::with_capacity(string_length),
-        };
+        output.push(chr);
 
-        let mut idx = 0;
-        let mut chr = Utf8Char::new_fuzzed(mutator, None);
-
-        while idx < string_length {
-            output.inner.push(chr.clone());
+        if repeats_left > 0 {
+            repeats_left -= 1;
+        } else {
+            if mutator.gen_chance(crate::mutator::CHANCE_TO_REPEAT_ARRAY_VALUE) {
+                repeats_left = mutator.gen_range(0, string_length - idx);
+            }
 
-            idx += 1;
-            if string_length - idx > 0 {
-                if mutator.gen_chance(crate::mutator::CHANCE_TO_REPEAT_ARRAY_VALUE) {
-                    let repeat_end_idx = mutator.gen_range(idx, string_length);
-                    while idx < repeat_end_idx {
-                        output.inner.push(chr.clone());
-                        idx += 1;
-                    }
-                    if string_length - idx > 0 {
-                        chr = Utf8Char::new_fuzzed(mutator, None);
-                    }
-                } else {
-                    chr = Utf8Char::new_fuzzed(mutator, None);
-                }
+            if repeats_left == 0 {
+                chr = gen_char(mutator);
             }
         }
-
-        output
     }
+
+    output
 }
 
-impl NewFuzzed for AsciiString {
+impl NewFuzzed for String {
     type RangeType = usize;
 
     fn new_fuzzed<R: Rng>(
         mutator: &mut Mutator<R>,
         constraints: Option<&Constraints<Self::RangeType>>,
     ) -> Self {
-        let min: Self::RangeType;
-        let max: Self::RangeType;
-        let weight: Weighted;
-        let mut output: AsciiString;
+        new_fuzzed_string(mutator, constraints, |mutator| {
+            Utf8Char::new_fuzzed(mutator, None).0
+        })
+    }
+}
 
-        trace!(
-            "Generating random AsciiString with constraints: {:#?}",
-            constraints
-        );
+/// The value is generated as usual and then truncated to fit in the prefix, which is exact
+impl<P, T> NewFuzzed for LengthPrefixed<P, T>
+where
+    P: LengthPrefix,
+    T: NewFuzzed + EncodedSequence,
+{
+    type RangeType = T::RangeType;
 
-        // if no min/max were supplied, we'll take a conservative approach
-        match constraints {
-            Some(ref constraints) => {
-                min = constraints.min.unwrap_or(0);
-                max = constraints.max.unwrap_or(256);
-                weight = constraints.weighted;
-            }
-            None => {
-                min = 0;
-                max = 256;
-                weight = Weighted::None;
-            }
-        }
+    fn new_fuzzed<R: Rng>(
+        mutator: &mut Mutator<R>,
+        constraints: Option<&Constraints<Self::RangeType>>,
+    ) -> Self {
+        let mut value = T::new_fuzzed(mutator, constraints);
+        fit_to_prefix::<P, T>(&mut value, Encoding::default());
 
-        let string_length = mutator.gen_weighted_range(min, max, weight);
+        LengthPrefixed::new(value)
+    }
+}
 
-        output = AsciiString {
-            inner: Vec::with_capacity(string_length),
-        };
+impl NewFuzzed for PaddingSeed {
+    type RangeType = u8;
 
-        let mut idx = 0;
-        let mut chr = AsciiChar::new_fuzzed(mutator, None);
+    fn new_fuzzed<R: Rng>(
+        mutator: &mut Mutator<R>,
+        _constraints: Option<&Constraints<Self::RangeType>>,
+    ) -> Self {
+        PaddingSeed::new(Some(mutator.rng.gen()))
+    }
+}
 
-        while idx < string_length {
-            output.inner.push(chr.clone());
+impl<T: NewFuzzed> NewFuzzed for TagOverride<T> {
+    type RangeType = T::RangeType;
 
-            idx += 1;
-            if string_length - idx > 0 {
-                if mutator.gen_chance(crate::mutator::CHANCE_TO_REPEAT_ARRAY_VALUE) {
-                    let repeat_end_idx = mutator.gen_range(idx, string_length);
-                    while idx < repeat_end_idx {
-                        output.inner.push(chr.clone());
-                        idx += 1;
-                    }
-                    if string_length - idx > 0 {
-                        chr = AsciiChar::new_fuzzed(mutator, None);
-                    }
-                } else {
-                    chr = AsciiChar::new_fuzzed(mutator, None);
-                }
-            }
-        }
+    fn new_fuzzed<R: Rng>(
+        mutator: &mut Mutator<R>,
+        constraints: Option<&Constraints<Self::RangeType>>,
+    ) -> Self {
+        TagOverride::new(T::new_fuzzed(mutator, constraints))
+    }
+}
 
-        output
+impl NewFuzzed for Utf8String {
+    type RangeType = usize;
+
+    fn new_fuzzed<R: Rng>(
+        mutator: &mut Mutator<R>,
+        constraints: Option<&Constraints<Self::RangeType>>,
+    ) -> Self {
+        String::new_fuzzed(mutator, constraints).into()
+    }
+}
+
+impl NewFuzzed for AsciiString {
+    type RangeType = usize;
+
+    fn new_fuzzed<R: Rng>(
+        mutator: &mut Mutator<R>,
+        constraints: Option<&Constraints<Self::RangeType>>,
+    ) -> Self {
+        AsciiString {
+            inner: new_fuzzed_string(mutator, constraints, |mutator| {
+                AsciiChar::new_fuzzed(mutator, None).0
+            }),
+        }
     }
 }
 
@@ -668,6 +798,12 @@ impl NewFuzzed for char {
         mutator: &mut crate::mutator::Mutator<R>,
         constraints: Option<&Constraints<Self::RangeType>>,
     ) -> Self {
+        if constraints.is_none()
+            && mutator.gen_chance(crate::mutator::CHANCE_TO_PICK_DANGEROUS_CHAR)
+        {
+            return char::select_dangerous_number(&mut mutator.rng);
+        }
+
         Utf8Char::new_fuzzed(mutator, constraints).0
     }
 }
@@ -749,136 +885,341 @@ macro_rules! impl_new_fuzzed {
 
 // BUG: f32/f64 generate a number between 0/1 when no constraints are supplied,
 // otherwise they generate an *integer* between min/max.
-impl_new_fuzzed!(u8, i8, u16, i16, u32, i32, u64, i64, f32, f64);
+impl_new_fuzzed!(u8, i8, u16, i16, u32, i32, u64, i64, u128, i128, f32, f64);
+
+impl<T, const N: usize> NewFuzzed for [T; N]
+where
+    T: NewFuzzed + SerializedSize,
+{
+    default type RangeType = usize;
+
+    default fn new_fuzzed<R: Rng>(
+        mutator: &mut Mutator<R>,
+        constraints: Option<&Constraints<Self::RangeType>>,
+    ) -> [T; N] {
+        let constraints =
+            array_item_constraints::<T::RangeType, N>(constraints.and_then(|c| c.max_size));
 
-impl<T> NewFuzzed for [T; 0]
+        std::array::from_fn(|_| T::new_fuzzed(mutator, constraints.as_ref()))
+    }
+}
+
+impl<T, const N: usize> NewFuzzed for [T; N]
 where
-    T: NewFuzzed + Clone,
+    T: NewFuzzed + Clone + SerializedSize,
 {
+    default type RangeType = usize;
+
+    default fn new_fuzzed<R: Rng>(
+        mutator: &mut Mutator<R>,
+        constraints: Option<&Constraints<Self::RangeType>>,
+    ) -> [T; N] {
+        let constraints =
+            array_item_constraints::<T::RangeType, N>(constraints.and_then(|c| c.max_size));
+
+        let mut element: Option<T> = None;
+        let mut repeats_left: usize = 0;
+
+        std::array::from_fn(|idx| match element {
+            Some(ref element) if repeats_left > 0 => {
+                repeats_left -= 1;
+                element.clone()
+            }
+            _ => {
+                let new_element = T::new_fuzzed(mutator, constraints.as_ref());
+                if mutator.gen_chance(crate::mutator::CHANCE_TO_REPEAT_ARRAY_VALUE) {
+                    repeats_left = mutator.gen_range(0, N - idx);
+                }
+
+                element = Some(new_element.clone());
+                new_element
+            }
+        })
+    }
+}
+
+impl<const N: usize> NewFuzzed for [u8; N] {
     type RangeType = usize;
 
     fn new_fuzzed<R: Rng>(
-        _mutator: &mut Mutator<R>,
+        mutator: &mut Mutator<R>,
         _constraints: Option<&Constraints<Self::RangeType>>,
-    ) -> [T; 0] {
-        // no-op
-        []
+    ) -> [u8; N] {
+        if N == 0 {
+            return [0u8; N];
+        }
+
+        if mutator.gen_chance(crate::mutator::CHANCE_TO_REPEAT_ARRAY_VALUE) {
+            return [u8::new_fuzzed(mutator, None); N];
+        }
+
+        let mut output = [0u8; N];
+        mutator.rng.fill(&mut output[..]);
+
+        // filling the buffer directly skips the dangerous numbers u8 generation would pick, so
+        // sprinkle a few in
+        let num_dangerous_bytes = mutator.gen_range(0, N / 4 + 1);
+        for _i in 0..num_dangerous_bytes {
+            let idx = mutator.gen_range(0, N);
+            output[idx] = u8::select_dangerous_number(&mut mutator.rng);
+        }
+
+        output
     }
 }
 
-macro_rules! impl_new_fuzzed_array {
-    ( $($size:expr),* ) => {
-        $(
-            impl<T> NewFuzzed for [T; $size]
-            where T: NewFuzzed + Clone + SerializedSize {
-                type RangeType = usize;
+/// Splits the array's size constraint evenly between its items
+fn array_item_constraints<T: Bounded + Debug, const N: usize>(
+    max_size: Option<usize>,
+) -> Option<Constraints<T>> {
+    if N == 0 {
+        return None;
+    }
 
-                fn new_fuzzed<R: Rng>(mutator: &mut Mutator<R>, constraints: Option<&Constraints<Self::RangeType>>) -> [T; $size] {
-                    let mut per_item_max_size: Option<usize> = constraints.and_then(|c| c.max_size.as_ref().and_then(|size| Some(*size / $size)));
+    max_size.map(|max_size| {
+        let mut constraints = Constraints::new();
+        constraints.max_size(max_size / N);
+        constraints.set_base_size_accounted_for();
 
-                    let mut output: MaybeUninit<[T; $size]> = MaybeUninit::uninit();
-                    let arr_ptr = output.as_mut_ptr() as *mut T;
+        constraints
+    })
+}
 
-                    let constraints = per_item_max_size.as_ref().map(|size| {
-                        let mut constraints = Constraints::new();
-                        constraints.max_size(*size);
-                        constraints.set_base_size_accounted_for();
+impl NewFuzzed for *mut std::ffi::c_void {
+    type RangeType = usize;
 
-                        constraints
-                    });
+    fn new_fuzzed<R: Rng>(
+        _mutator: &mut Mutator<R>,
+        _constraints: Option<&Constraints<Self::RangeType>>,
+    ) -> Self {
+        std::ptr::null_mut()
+    }
+}
 
-                    let mut idx = 0;
-                    let mut element: T = T::new_fuzzed(mutator, constraints.as_ref());
+impl NewFuzzed for *const std::ffi::c_void {
+    type RangeType = usize;
 
-                    while idx < $size {
-                        unsafe {
-                            arr_ptr.add(idx).write(element.clone());
-                        }
+    fn new_fuzzed<R: Rng>(
+        _mutator: &mut Mutator<R>,
+        _constraints: Option<&Constraints<Self::RangeType>>,
+    ) -> Self {
+        std::ptr::null()
+    }
+}
 
-                        idx += 1;
-                        if $size - idx > 0 {
-                            if mutator.gen_chance(crate::mutator::CHANCE_TO_REPEAT_ARRAY_VALUE) {
-                                let repeat_end_idx = mutator.gen_range(idx, $size);
-                                while idx < repeat_end_idx {
-                                    unsafe {
-                                        arr_ptr.add(idx).write(element.clone());
-                                    }
-                                    idx += 1;
-                                }
-                            } else {
-                                let constraints = per_item_max_size.as_ref().map(|size| {
-                                    let mut constraints = Constraints::new();
-                                    constraints.max_size(*size);
-                                    constraints.set_base_size_accounted_for();
+macro_rules! impl_new_fuzzed_tuple {
+    ( $( ( $($name:ident),+ ) ),* ) => {
+        $(
+            impl<$($name),+> NewFuzzed for ($($name,)+)
+            where $($name: NewFuzzed + SerializedSize),+ {
+                type RangeType = u8;
 
-                                    constraints
-                                });
+                fn new_fuzzed<R: Rng>(mutator: &mut Mutator<R>, constraints: Option<&Constraints<Self::RangeType>>) -> Self {
+                    // like derived structs, the max size given to each element is only the extra data it may
+                    // consume on top of its default size
+                    let mut max_size = constraints.and_then(|c| {
+                        let mut c = c.clone();
+                        c.account_for_base_object_size::<Self>();
+                        c.max_size
+                    });
+
+                    ($(
+                        {
+                            let constraints = max_size.map(|max_size| {
+                                let mut c = Constraints::new();
+                                c.max_size(max_size);
+                                c.base_object_size_accounted_for = true;
 
-                                element = T::new_fuzzed(mutator, constraints.as_ref());
+                                c
+                            });
+
+                            let element = <$name>::new_fuzzed(mutator, constraints.as_ref());
+                            if let Some(ref mut max_size) = max_size {
+                                let size_delta = element.serialized_size().saturating_sub(<$name>::max_default_object_size());
+                                *max_size = max_size.saturating_sub(size_delta);
                             }
-                        }
-                    }
 
-                    unsafe { output.assume_init() }
+                            element
+                        },
+                    )+)
                 }
             }
+        )*
+    }
+}
 
-            impl<T> NewFuzzed for [T; $size]
-            where T: NewFuzzed + SerializedSize {
-                default type RangeType = usize;
+impl_new_fuzzed_tuple!(
+    (T0),
+    (T0, T1),
+    (T0, T1, T2),
+    (T0, T1, T2, T3),
+    (T0, T1, T2, T3, T4),
+    (T0, T1, T2, T3, T4, T5),
+    (T0, T1, T2, T3, T4, T5, T6),
+    (T0, T1, T2, T3, T4, T5, T6, T7),
+    (T0, T1, T2, T3, T4, T5, T6, T7, T8),
+    (T0, T1, T2, T3, T4, T5, T6, T7, T8, T9),
+    (T0, T1, T2, T3, T4, T5, T6, T7, T8, T9, T10),
+    (T0, T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11)
+);
 
-                default fn new_fuzzed<R: Rng>(mutator: &mut Mutator<R>, constraints: Option<&Constraints<Self::RangeType>>) -> [T; $size] {
-                    let mut per_item_max_size: Option<usize> = constraints.and_then(|c| c.max_size.as_ref().and_then(|size| Some(*size / $size)));
+/// Generates the entries of a collection which is serialized with a `u32` count prefix (e.g.
+/// maps and sets). Entries with duplicate keys are collapsed by the collection, so the result may
+/// contain fewer entries than were generated.
+fn new_fuzzed_collection<T, C, R>(
+    mutator: &mut Mutator<R>,
+    constraints: Option<&Constraints<usize>>,
+) -> C
+where
+    T: NewFuzzed + SerializedSize,
+    C: Default + Extend<T>,
+    R: Rng,
+{
+    const MAX_NUM_ELEMENTS: usize = 0x40;
 
-                    let mut output: MaybeUninit<[T; $size]> = MaybeUninit::uninit();
-                    let arr_ptr = output.as_mut_ptr() as *mut T;
+    let mut output = C::default();
 
-                    for i in 0..$size {
-                        let constraints = per_item_max_size.as_ref().map(|size| {
-                            let mut constraints = Constraints::new();
-                            constraints.max_size(*size);
-                            constraints.set_base_size_accounted_for();
+    if T::max_default_object_size() == 0 {
+        warn!("Size of element in collection is 0... returning early");
+        return output;
+    }
 
-                            constraints
-                        });
-                        let element = T::new_fuzzed(mutator, constraints.as_ref());
+    let (mut min, mut max, weight, mut max_size) = match constraints {
+        Some(constraints) => (
+            constraints.min.unwrap_or(0),
+            constraints.max.unwrap_or(MAX_NUM_ELEMENTS),
+            constraints.weighted,
+            constraints.max_size.map(|max_size| {
+                if constraints.base_object_size_accounted_for {
+                    max_size
+                } else {
+                    max_size.saturating_sub(std::mem::size_of::<u32>())
+                }
+            }),
+        ),
+        None => (0, MAX_NUM_ELEMENTS, Weighted::None, None),
+    };
 
-                        unsafe {
-                            arr_ptr.offset(i).write(element);
-                        }
-                    }
+    if let Some(max_size) = max_size {
+        max = cmp::min(max, max_size / T::max_default_object_size());
+    }
 
-                    unsafe { output.assume_init() }
-                }
+    if max == 0 {
+        return output;
+    }
+
+    if min > max {
+        min = 0;
+    }
+
+    let num_elements = if min == max {
+        min
+    } else {
+        mutator.gen_weighted_range(min, max, weight)
+    };
+
+    for _i in 0..num_elements {
+        let constraints = max_size.map(|max_size| {
+            let mut c = Constraints::new();
+            c.max_size(max_size);
+            c.base_object_size_accounted_for = true;
+
+            c
+        });
+
+        let element = T::new_fuzzed(mutator, constraints.as_ref());
+
+        if let Some(ref mut max_size) = max_size {
+            let element_size = element.serialized_size();
+            if element_size > *max_size {
+                break;
             }
-        )*
+
+            *max_size -= element_size;
+        }
+
+        output.extend(Some(element));
     }
+
+    output
 }
 
-impl_new_fuzzed_array!(
-    1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26,
-    27, 28, 29, 30, 31, 32, 33, 34, 35, 36, 37, 38, 39, 40, 41, 42, 43, 44, 45, 46, 47, 48, 49, 50,
-    51, 52, 53, 54, 55, 56, 57, 58, 59, 60
-);
+impl<K, V> NewFuzzed for HashMap<K, V>
+where
+    K: NewFuzzed + SerializedSize + Eq + Hash,
+    V: NewFuzzed + SerializedSize,
+{
+    type RangeType = usize;
 
-impl NewFuzzed for *mut std::ffi::c_void {
+    fn new_fuzzed<R: Rng>(
+        mutator: &mut Mutator<R>,
+        constraints: Option<&Constraints<Self::RangeType>>,
+    ) -> Self {
+        trace!("Generating random HashMap");
+
+        new_fuzzed_collection::<(K, V), _, _>(mutator, constraints)
+    }
+}
+
+impl<K, V> NewFuzzed for BTreeMap<K, V>
+where
+    K: NewFuzzed + SerializedSize + Ord,
+    V: NewFuzzed + SerializedSize,
+{
     type RangeType = usize;
 
     fn new_fuzzed<R: Rng>(
-        _mutator: &mut Mutator<R>,
-        _constraints: Option<&Constraints<Self::RangeType>>,
+        mutator: &mut Mutator<R>,
+        constraints: Option<&Constraints<Self::RangeType>>,
     ) -> Self {
-        std::ptr::null_mut()
+        trace!("Generating random BTreeMap");
+
+        new_fuzzed_collection::<(K, V), _, _>(mutator, constraints)
     }
 }
 
-impl NewFuzzed for *const std::ffi::c_void {
+impl<T> NewFuzzed for HashSet<T>
+where
+    T: NewFuzzed + SerializedSize + Eq + Hash,
+{
     type RangeType = usize;
 
     fn new_fuzzed<R: Rng>(
-        _mutator: &mut Mutator<R>,
-        _constraints: Option<&Constraints<Self::RangeType>>,
+        mutator: &mut Mutator<R>,
+        constraints: Option<&Constraints<Self::RangeType>>,
     ) -> Self {
-        std::ptr::null()
+        trace!("Generating random HashSet");
+
+        new_fuzzed_collection::<T, _, _>(mutator, constraints)
+    }
+}
+
+impl<T> NewFuzzed for BTreeSet<T>
+where
+    T: NewFuzzed + SerializedSize + Ord,
+{
+    type RangeType = usize;
+
+    fn new_fuzzed<R: Rng>(
+        mutator: &mut Mutator<R>,
+        constraints: Option<&Constraints<Self::RangeType>>,
+    ) -> Self {
+        trace!("Generating random BTreeSet");
+
+        new_fuzzed_collection::<T, _, _>(mutator, constraints)
+    }
+}
+
+impl<T> NewFuzzed for VecDeque<T>
+where
+    T: NewFuzzed + SerializedSize,
+{
+    type RangeType = usize;
+
+    fn new_fuzzed<R: Rng>(
+        mutator: &mut Mutator<R>,
+        constraints: Option<&Constraints<Self::RangeType>>,
+    ) -> Self {
+        Vec::<T>::new_fuzzed(mutator, constraints).into()
     }
 }
@@ -1,7 +1,7 @@
 #[doc(no_inline)]
 pub use lain_derive::{
-    BinarySerialize, FuzzerObject, Mutatable, NewFuzzed, ToPrimitiveU16, ToPrimitiveU32,
-    ToPrimitiveU64, ToPrimitiveU8, VariableSizeObject,
+    BinarySerialize, FuzzerObject, Mutatable, NewFuzzed, ToPrimitiveU128, ToPrimitiveU16,
+    ToPrimitiveU32, ToPrimitiveU64, ToPrimitiveU8, VariableSizeObject,
 };
 
 #[doc(no_inline)]
@@ -15,6 +15,10 @@ pub use crate::traits::*;
 #[doc(no_inline)]
 pub use crate::types::*;
 
+#[cfg(feature = "serde_support")]
+#[doc(no_inline)]
+pub use crate::serde::{Deserialize, Serialize};
+
 #[doc(no_inline)]
 pub use crate::rand::distributions::Distribution;
 #[doc(no_inline)]
@@ -0,0 +1,629 @@
+//! Corpus scheduling. A [Scheduler] holds the inputs which a fuzzer decided to keep along with
+//! metadata about how they've performed, and picks which one should be mutated next. How much
+//! time each entry gets is decided by its *energy*, which is computed by a pluggable
+//! [PowerSchedule]:
+//!
+//! - [Uniform] gives every entry the same energy
+//! - [SmallFast] favours entries which are smaller and faster to execute than average. This is
+//!   AFL's performance score, which AFLFast's "explore" and "exploit" schedules are built on; they
+//!   only differ in the absolute energy they assign, which the scheduler normalises away.
+//! - [Recent] favours entries which were added recently
+//! - [Fast] is AFLFast's "fast" schedule and favours entries exercising rarely seen paths
+//! - [Productive] favours entries which have produced finds
+//! - [Rare] favours entries which hit the least frequently hit coverage
+//!
+//! Entries are picked with a probability proportional to their energy and are then mutated for a
+//! number of iterations which is also proportional to their energy.
+//!
+//! The scheduler is thread-safe and is meant to be shared between [FuzzerDriver](crate::driver::FuzzerDriver)
+//! threads in an `Arc`, while each thread keeps a [ScheduledInput] in its thread context to track
+//! the entry it's currently working on:
+//!
+//! ```
+//! use lain::prelude::*;
+//! use lain::scheduler::{Fast, ScheduledInput, Scheduler};
+//! use std::time::Duration;
+//!
+//! #[derive(Debug, Default, Clone, NewFuzzed, Mutatable, BinarySerialize)]
+//! struct Packet {
+//!     kind: u8,
+//!     len: u16,
+//! }
+//!
+//! # fn execute(packet: &Packet) -> Vec<usize> { vec![packet.kind as usize] }
+//! let scheduler = Scheduler::new(Fast::default());
+//! scheduler.add(Packet::default(), Duration::from_micros(10), &[0]);
+//!
+//! let mut mutator = Mutator::new(lain::rand::thread_rng());
+//! let mut scheduled = ScheduledInput::default();
+//!
+//! for _ in 0..100 {
+//!     let packet = scheduled.next(&scheduler, &mut mutator).unwrap();
+//!     packet.mutate(&mut mutator, None);
+//!
+//!     let coverage = execute(packet);
+//!     scheduled.report(&scheduler, Duration::from_micros(10), &coverage);
+//! }
+//! ```
+//!
+//! **Note:** the entry picked depends on the results of every thread's previous iterations, so
+//! iterations can't be reproduced from the driver's seed alone once a scheduler is involved.
+
+use crate::mutator::Mutator;
+use crate::rand::Rng;
+use crate::traits::SerializedSize;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+use std::time::Duration;
+
+#[cfg(feature = "serde_support")]
+use serde::{Deserialize, Serialize};
+
+/// Identifies an entry in a [Scheduler]. Entries are numbered in the order they were added.
+pub type EntryId = usize;
+
+/// The largest energy an entry is given by the [Fast] schedule
+pub const MAX_ENERGY: f64 = 1024.0;
+
+/// What the scheduler knows about a corpus entry
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub struct EntryMetadata {
+    pub id: EntryId,
+    /// The serialized size of the entry
+    pub size: usize,
+    /// How long the entry took to execute when it was added
+    pub exec_time: Duration,
+    /// How many mutations of this entry have been executed
+    pub exec_count: u64,
+    /// How many times this entry has been picked by the scheduler
+    pub times_selected: u64,
+    /// How many new entries or crashes were found by mutating this entry
+    pub finds: u64,
+    /// The coverage (e.g. edge IDs) the entry hit, sorted and deduplicated
+    pub coverage: Vec<usize>,
+}
+
+impl EntryMetadata {
+    /// Identifies the path taken by the entry
+    pub fn path_hash(&self) -> u64 {
+        path_hash(&self.coverage)
+    }
+}
+
+/// Corpus-wide statistics passed to a [PowerSchedule]
+pub struct ScheduleContext<'a> {
+    entries: &'a [EntryMetadata],
+    paths: &'a [u64],
+    total_execs: u64,
+    edge_hits: &'a HashMap<usize, u64>,
+    path_hits: &'a HashMap<u64, u64>,
+}
+
+impl<'a> ScheduleContext<'a> {
+    /// The metadata of every entry in the corpus
+    pub fn entries(&self) -> &[EntryMetadata] {
+        self.entries
+    }
+
+    /// The total number of executions recorded
+    pub fn total_execs(&self) -> u64 {
+        self.total_execs
+    }
+
+    /// The ID of the most recently added entry
+    pub fn newest(&self) -> EntryId {
+        self.entries.len().saturating_sub(1)
+    }
+
+    /// The average serialized size of the entries
+    pub fn mean_size(&self) -> f64 {
+        mean(self.entries.iter().map(|entry| entry.size as f64))
+    }
+
+    /// The average execution time of the entries, in seconds
+    pub fn mean_exec_time(&self) -> f64 {
+        mean(
+            self.entries
+                .iter()
+                .map(|entry| entry.exec_time.as_secs_f64()),
+        )
+    }
+
+    /// The number of executions which took the same path as `entry`
+    pub fn path_frequency(&self, entry: &EntryMetadata) -> u64 {
+        let path = match self.paths.get(entry.id) {
+            Some(path) => *path,
+            None => entry.path_hash(),
+        };
+
+        self.path_hits.get(&path).cloned().unwrap_or(0)
+    }
+
+    /// The number of executions which hit the least frequently hit coverage of `entry`, or `None`
+    /// if the entry has no coverage
+    pub fn rarest_hits(&self, entry: &EntryMetadata) -> Option<u64> {
+        entry
+            .coverage
+            .iter()
+            .map(|edge| self.edge_hits.get(edge).cloned().unwrap_or(0))
+            .min()
+    }
+}
+
+/// Decides how much time is spent on each corpus entry
+pub trait PowerSchedule: Send + Sync {
+    /// Returns the entry's energy. Only the ratio between entries matters.
+    fn energy(&self, entry: &EntryMetadata, context: &ScheduleContext) -> f64;
+}
+
+/// Gives every entry the same energy
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Uniform;
+
+impl PowerSchedule for Uniform {
+    fn energy(&self, _entry: &EntryMetadata, _context: &ScheduleContext) -> f64 {
+        1.0
+    }
+}
+
+/// Favours entries which are smaller and faster than the corpus average, similar to AFL's
+/// performance score
+#[derive(Debug, Default, Copy, Clone)]
+pub struct SmallFast;
+
+impl PowerSchedule for SmallFast {
+    fn energy(&self, entry: &EntryMetadata, context: &ScheduleContext) -> f64 {
+        let speed = ratio(context.mean_exec_time(), entry.exec_time.as_secs_f64());
+        let size = ratio(context.mean_size(), entry.size as f64);
+
+        speed * size
+    }
+}
+
+/// Favours recently added entries. An entry's energy halves for every `half_life` entries added
+/// after it.
+#[derive(Debug, Copy, Clone)]
+pub struct Recent {
+    pub half_life: usize,
+}
+
+impl Default for Recent {
+    fn default() -> Self {
+        Recent { half_life: 16 }
+    }
+}
+
+impl PowerSchedule for Recent {
+    fn energy(&self, entry: &EntryMetadata, context: &ScheduleContext) -> f64 {
+        let age = context.newest().saturating_sub(entry.id) as f64;
+
+        0.5f64.powf(age / self.half_life.max(1) as f64)
+    }
+}
+
+/// AFLFast's "fast" schedule, which spends energy on entries whose paths are rarely exercised. An
+/// entry's energy is `2^s / f`, where `s` is the number of times it was picked and `f` is the
+/// number of executions which took its path, so an entry keeps gaining energy for as long as its
+/// path stays rare.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Fast;
+
+impl PowerSchedule for Fast {
+    fn energy(&self, entry: &EntryMetadata, context: &ScheduleContext) -> f64 {
+        let picked = 2f64.powi(entry.times_selected.min(10) as i32);
+        let frequency = context.path_frequency(entry).max(1) as f64;
+
+        (picked / frequency).min(MAX_ENERGY)
+    }
+}
+
+/// Favours entries which have produced finds. An entry's energy is the number of finds it has
+/// produced per time it was picked, so entries which haven't been picked yet still get a turn.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Productive;
+
+impl PowerSchedule for Productive {
+    fn energy(&self, entry: &EntryMetadata, _context: &ScheduleContext) -> f64 {
+        (1 + entry.finds) as f64 / (1 + entry.times_selected) as f64
+    }
+}
+
+/// Favours entries which hit rarely hit coverage. An entry's energy is inversely proportional to
+/// the number of executions which hit its rarest coverage.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Rare;
+
+impl PowerSchedule for Rare {
+    fn energy(&self, entry: &EntryMetadata, context: &ScheduleContext) -> f64 {
+        let hits = context
+            .rarest_hits(entry)
+            .unwrap_or_else(|| context.total_execs());
+
+        1.0 / hits.max(1) as f64
+    }
+}
+
+/// An entry picked by the scheduler
+#[derive(Debug, Clone)]
+pub struct Assignment<I> {
+    pub id: EntryId,
+    /// A copy of the entry's input
+    pub input: I,
+    /// How many mutations of the input should be executed before picking another entry
+    pub iterations: usize,
+}
+
+struct SchedulerState<I> {
+    inputs: Vec<I>,
+    entries: Vec<EntryMetadata>,
+    /// The path hash of every entry, so that it isn't recomputed every time an entry is picked
+    paths: Vec<u64>,
+    total_execs: u64,
+    edge_hits: HashMap<usize, u64>,
+    path_hits: HashMap<u64, u64>,
+}
+
+impl<I> SchedulerState<I> {
+    /// Counts an execution's coverage, returning whether any of it was new
+    fn record_coverage(&mut self, coverage: &PathCoverage) -> bool {
+        let mut is_new = false;
+        for edge in &coverage.edges {
+            let hits = self.edge_hits.entry(*edge).or_insert(0);
+            is_new |= *hits == 0;
+            *hits += 1;
+        }
+
+        *self.path_hits.entry(coverage.hash).or_insert(0) += 1;
+        self.total_execs += 1;
+
+        is_new
+    }
+}
+
+/// An execution's coverage, sorted and deduplicated along with the hash of its path. This is
+/// computed before taking the scheduler's lock so that only the counter updates happen under it.
+struct PathCoverage {
+    edges: Vec<usize>,
+    hash: u64,
+}
+
+impl PathCoverage {
+    fn new(coverage: &[usize]) -> PathCoverage {
+        let mut edges = coverage.to_vec();
+        edges.sort_unstable();
+        edges.dedup();
+
+        let hash = path_hash(&edges);
+
+        PathCoverage { edges, hash }
+    }
+}
+
+/// Holds the corpus and picks entries to mutate according to a [PowerSchedule]
+pub struct Scheduler<I> {
+    schedule: Box<dyn PowerSchedule>,
+    base_iterations: usize,
+    max_iterations: usize,
+    state: Mutex<SchedulerState<I>>,
+}
+
+impl<I: Clone> Scheduler<I> {
+    /// Creates an empty corpus. An entry of average energy is mutated for 32 iterations.
+    pub fn new<S: PowerSchedule + 'static>(schedule: S) -> Scheduler<I> {
+        Scheduler {
+            schedule: Box::new(schedule),
+            base_iterations: 32,
+            max_iterations: 1024,
+            state: Mutex::new(SchedulerState {
+                inputs: vec![],
+                entries: vec![],
+                paths: vec![],
+                total_execs: 0,
+                edge_hits: HashMap::new(),
+                path_hits: HashMap::new(),
+            }),
+        }
+    }
+
+    /// Sets how many iterations an entry of average energy gets, and the most iterations any entry
+    /// gets at once
+    pub fn set_iterations(&mut self, base_iterations: usize, max_iterations: usize) {
+        self.base_iterations = base_iterations.max(1);
+        self.max_iterations = max_iterations.max(1);
+    }
+
+    /// Adds an input to the corpus, such as an initial seed, and counts its execution. Returns the
+    /// new entry's ID.
+    pub fn add(&self, input: I, exec_time: Duration, coverage: &[usize]) -> EntryId
+    where
+        I: SerializedSize,
+    {
+        let coverage = PathCoverage::new(coverage);
+        let size = input.serialized_size();
+
+        let mut state = self.state.lock().unwrap();
+        state.record_coverage(&coverage);
+
+        Self::push_entry(&mut state, input, size, exec_time, coverage)
+    }
+
+    fn push_entry(
+        state: &mut SchedulerState<I>,
+        input: I,
+        size: usize,
+        exec_time: Duration,
+        coverage: PathCoverage,
+    ) -> EntryId {
+        let id = state.entries.len();
+        state.paths.push(coverage.hash);
+        state.entries.push(EntryMetadata {
+            id,
+            size,
+            exec_time,
+            coverage: coverage.edges,
+            ..Default::default()
+        });
+        state.inputs.push(input);
+
+        id
+    }
+
+    /// Records an execution of a mutation of entry `parent`. Returns whether the execution hit any
+    /// coverage which hadn't been hit before, in which case the input should be kept with
+    /// [Scheduler::add_find].
+    pub fn record_execution(&self, parent: EntryId, coverage: &[usize]) -> bool {
+        let coverage = PathCoverage::new(coverage);
+
+        let mut state = self.state.lock().unwrap();
+        if let Some(entry) = state.entries.get_mut(parent) {
+            entry.exec_count += 1;
+        }
+
+        state.record_coverage(&coverage)
+    }
+
+    /// Credits entry `parent` with a find that isn't kept in the corpus, such as a crash
+    pub fn record_find(&self, parent: EntryId) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(entry) = state.entries.get_mut(parent) {
+            entry.finds += 1;
+        }
+    }
+
+    /// Adds an input found by mutating entry `parent` to the corpus and credits the parent with
+    /// the find. The input's execution should already have been recorded with
+    /// [Scheduler::record_execution].
+    pub fn add_find(
+        &self,
+        parent: EntryId,
+        input: I,
+        exec_time: Duration,
+        coverage: &[usize],
+    ) -> EntryId
+    where
+        I: SerializedSize,
+    {
+        let coverage = PathCoverage::new(coverage);
+        let size = input.serialized_size();
+
+        let mut state = self.state.lock().unwrap();
+        if let Some(entry) = state.entries.get_mut(parent) {
+            entry.finds += 1;
+        }
+
+        Self::push_entry(&mut state, input, size, exec_time, coverage)
+    }
+
+    /// Picks the next entry to mutate. Returns `None` if the corpus is empty.
+    pub fn next<R: Rng>(&self, mutator: &mut Mutator<R>) -> Option<Assignment<I>> {
+        let mut state = self.state.lock().unwrap();
+        if state.entries.is_empty() {
+            return None;
+        }
+
+        let energies = self.energies(&state);
+        let total: f64 = energies.iter().sum();
+
+        let (id, energy) = if total > 0.0 && total.is_finite() {
+            let mut target = mutator.rng.gen::<f64>() * total;
+            let mut picked = energies.len() - 1;
+            for (i, energy) in energies.iter().enumerate() {
+                if target < *energy {
+                    picked = i;
+                    break;
+                }
+
+                target -= energy;
+            }
+
+            (picked, energies[picked] / (total / energies.len() as f64))
+        } else {
+            (mutator.gen_range(0, energies.len()), 1.0)
+        };
+
+        let iterations = (energy * self.base_iterations as f64).round() as usize;
+        let iterations = iterations.max(1).min(self.max_iterations);
+
+        state.entries[id].times_selected += 1;
+
+        Some(Assignment {
+            id,
+            input: state.inputs[id].clone(),
+            iterations,
+        })
+    }
+
+    fn energies(&self, state: &SchedulerState<I>) -> Vec<f64> {
+        let context = ScheduleContext {
+            entries: &state.entries,
+            paths: &state.paths,
+            total_execs: state.total_execs,
+            edge_hits: &state.edge_hits,
+            path_hits: &state.path_hits,
+        };
+
+        state
+            .entries
+            .iter()
+            .map(|entry| {
+                let energy = self.schedule.energy(entry, &context);
+                if energy.is_finite() && energy > 0.0 {
+                    energy
+                } else {
+                    0.0
+                }
+            })
+            .collect()
+    }
+
+    /// Returns a copy of the input of entry `id`
+    pub fn input(&self, id: EntryId) -> Option<I> {
+        self.state.lock().unwrap().inputs.get(id).cloned()
+    }
+
+    /// Returns the metadata of entry `id`
+    pub fn entry(&self, id: EntryId) -> Option<EntryMetadata> {
+        self.state.lock().unwrap().entries.get(id).cloned()
+    }
+
+    /// Returns the metadata of every entry so that it can be saved alongside the inputs
+    pub fn metadata(&self) -> Vec<EntryMetadata> {
+        self.state.lock().unwrap().entries.clone()
+    }
+
+    /// Restores the counters of previously saved entries after their inputs have been added
+    /// again. Metadata for entries which don't exist is ignored.
+    pub fn restore_metadata(&self, metadata: &[EntryMetadata]) {
+        let mut state = self.state.lock().unwrap();
+        for saved in metadata {
+            if let Some(entry) = state.entries.get_mut(saved.id) {
+                entry.exec_count = saved.exec_count;
+                entry.times_selected = saved.times_selected;
+                entry.finds = saved.finds;
+            }
+        }
+    }
+
+    /// The number of entries in the corpus
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().entries.len()
+    }
+
+    /// Returns whether the corpus is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The total number of executions recorded
+    pub fn total_execs(&self) -> u64 {
+        self.state.lock().unwrap().total_execs
+    }
+}
+
+/// Tracks the entry a fuzzer thread is currently mutating. This is intended to be kept in a
+/// [FuzzerDriver](crate::driver::FuzzerDriver) thread context.
+#[derive(Debug)]
+pub struct ScheduledInput<I> {
+    parent: Option<EntryId>,
+    parent_input: Option<I>,
+    current: Option<I>,
+    remaining: usize,
+}
+
+impl<I> Default for ScheduledInput<I> {
+    fn default() -> Self {
+        ScheduledInput {
+            parent: None,
+            parent_input: None,
+            current: None,
+            remaining: 0,
+        }
+    }
+}
+
+impl<I: Clone> ScheduledInput<I> {
+    /// Returns a fresh copy of the current entry's input for this iteration to mutate, picking a
+    /// new entry once the current one has used up its iterations. Returns `None` if the corpus is
+    /// empty.
+    pub fn next<R: Rng>(
+        &mut self,
+        scheduler: &Scheduler<I>,
+        mutator: &mut Mutator<R>,
+    ) -> Option<&mut I> {
+        if self.remaining == 0 || self.parent_input.is_none() {
+            let assignment = scheduler.next(mutator)?;
+
+            self.parent = Some(assignment.id);
+            self.parent_input = Some(assignment.input);
+            self.remaining = assignment.iterations;
+        }
+
+        self.remaining -= 1;
+        self.current = self.parent_input.clone();
+
+        self.current.as_mut()
+    }
+
+    /// The entry the current input was derived from
+    pub fn parent(&self) -> Option<EntryId> {
+        self.parent
+    }
+
+    /// Records the execution of the input returned by the last call to [ScheduledInput::next].
+    /// If it hit new coverage the input is added to the corpus and its ID is returned.
+    pub fn report(
+        &mut self,
+        scheduler: &Scheduler<I>,
+        exec_time: Duration,
+        coverage: &[usize],
+    ) -> Option<EntryId>
+    where
+        I: SerializedSize,
+    {
+        let parent = self.parent?;
+        if !scheduler.record_execution(parent, coverage) {
+            return None;
+        }
+
+        let input = self.current.take()?;
+
+        Some(scheduler.add_find(parent, input, exec_time, coverage))
+    }
+
+    /// Stops mutating the current entry so that the next call to [ScheduledInput::next] picks a
+    /// new one
+    pub fn skip(&mut self) {
+        self.remaining = 0;
+    }
+}
+
+/// Hashes coverage which has already been sorted and deduplicated
+fn path_hash(coverage: &[usize]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    coverage.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn mean<It: Iterator<Item = f64>>(values: It) -> f64 {
+    let (sum, count) = values.fold((0.0, 0usize), |(sum, count), value| {
+        (sum + value, count + 1)
+    });
+
+    if count == 0 {
+        0.0
+    } else {
+        sum / count as f64
+    }
+}
+
+/// `average / value`, clamped so that a single outlier doesn't starve the rest of the corpus
+fn ratio(average: f64, value: f64) -> f64 {
+    if value <= 0.0 || average <= 0.0 {
+        return 1.0;
+    }
+
+    (average / value).clamp(0.1, 10.0)
+}
@@ -5,6 +5,7 @@ use crate::rand::Rng;
 use crate::types::*;
 use byteorder::ByteOrder;
 use num_traits::Bounded;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 use std::fmt::Debug;
 use std::io::Write;
 
@@ -75,6 +76,30 @@ impl<T> Fixup for T {
     }
 }
 
+/// Trait used for restricting which messages in a [Sequence] may follow each other.
+///
+/// By default any message may start a sequence and follow any other message. Implement this for
+/// your message type to describe the protocol's state machine, e.g. that an `Auth` message may
+/// only follow a `Hello`. Generated sequences always respect these rules while mutations will
+/// occasionally violate them on purpose.
+pub trait StateMachine {
+    /// Returns whether `self` may be the first message of a sequence
+    fn is_valid_start(&self) -> bool;
+
+    /// Returns whether `self` may directly follow `previous`
+    fn is_valid_successor(&self, previous: &Self) -> bool;
+}
+
+impl<T> StateMachine for T {
+    default fn is_valid_start(&self) -> bool {
+        true
+    }
+
+    default fn is_valid_successor(&self, _previous: &Self) -> bool {
+        true
+    }
+}
+
 #[doc(hidden)]
 pub trait DangerousNumber<T> {
     fn select_dangerous_number<R: Rng>(rng: &mut R) -> T;
@@ -84,6 +109,26 @@ pub trait DangerousNumber<T> {
     fn dangerous_numbers_len() -> usize;
 }
 
+/// An enum serialized as a tag followed by the fields of the selected variant. The `BinarySerialize`
+/// derive implements this for enums with a `#[lain(tag = "u16")]` attribute so that
+/// [TagOverride] can write a tag which does not match the variant.
+///
+/// Tags are passed around as `u64` and converted to and from the tag type with `as`.
+pub trait TaggedEnum {
+    /// Every variant's tag, in declaration order
+    fn tags() -> &'static [u64];
+
+    /// Returns a random value of the tag type, which may not belong to any variant
+    fn arbitrary_tag<R: Rng>(mutator: &mut Mutator<R>) -> u64;
+
+    /// Serializes the enum, writing `tag` instead of the variant's tag if given
+    fn binary_serialize_with_tag<W: Write, E: ByteOrder>(
+        &self,
+        tag: Option<u64>,
+        buffer: &mut W,
+    ) -> usize;
+}
+
 /// Represents a type which can be converted to a primitive type. This should be used for enums
 /// so that the serializer can generically call `YourEnum::ToPrimitive()`
 pub trait ToPrimitive {
@@ -92,6 +137,68 @@ pub trait ToPrimitive {
     fn to_primitive(&self) -> Self::Output;
 }
 
+/// The inverse of [ToPrimitive] for enums. This is implemented by the `ToPrimitive*` derives so
+/// that a raw discriminant can be mapped back to the variant it represents.
+pub trait FromPrimitive: ToPrimitive + Sized {
+    /// The primitive value of every variant, in declaration order
+    fn primitive_values() -> &'static [Self::Output]
+    where
+        Self::Output: 'static;
+
+    /// Returns the variant whose primitive value is `value`, or `None` if there isn't one
+    fn from_primitive(value: Self::Output) -> Option<Self>;
+}
+
+/// Integer types which may be held by a [VarInt]. Signed types are ZigZag encoded.
+pub trait VarIntValue: Copy {
+    /// The number of bytes in the longest canonical encoding of this type
+    const MAX_ENCODED_LEN: usize;
+
+    /// Converts to the unsigned value that is LEB128 encoded
+    fn to_raw(self) -> u64;
+
+    /// Converts from the unsigned value that was LEB128 encoded, truncating any extra bits
+    fn from_raw(raw: u64) -> Self;
+}
+
+macro_rules! impl_var_int_value {
+    ( $($unsigned:ident, $signed:ident);* ) => {
+        $(
+            impl VarIntValue for $unsigned {
+                const MAX_ENCODED_LEN: usize = (std::mem::size_of::<$unsigned>() * 8).div_ceil(7);
+
+                #[inline]
+                fn to_raw(self) -> u64 {
+                    self as u64
+                }
+
+                #[inline]
+                fn from_raw(raw: u64) -> Self {
+                    raw as $unsigned
+                }
+            }
+
+            impl VarIntValue for $signed {
+                const MAX_ENCODED_LEN: usize = (std::mem::size_of::<$signed>() * 8).div_ceil(7);
+
+                #[inline]
+                fn to_raw(self) -> u64 {
+                    const BITS: u32 = std::mem::size_of::<$signed>() as u32 * 8;
+                    (((self << 1) ^ (self >> (BITS - 1))) as $unsigned) as u64
+                }
+
+                #[inline]
+                fn from_raw(raw: u64) -> Self {
+                    let raw = raw as $unsigned;
+                    ((raw >> 1) as $signed) ^ -((raw & 1) as $signed)
+                }
+            }
+        )*
+    }
+}
+
+impl_var_int_value!(u8, i8; u16, i16; u32, i32; u64, i64);
+
 /// Trait for objects to derive in order to specify whether or not they are variable-size.
 ///
 /// This trait does not strictly need to be implemented, however if your data structures
@@ -115,6 +222,54 @@ impl<T> VariableSizeObject for Vec<T> {
     }
 }
 
+impl<T> VariableSizeObject for VarInt<T> {
+    fn is_variable_size() -> bool {
+        true
+    }
+}
+
+impl<T> VariableSizeObject for VecDeque<T> {
+    fn is_variable_size() -> bool {
+        true
+    }
+}
+
+impl<K, V> VariableSizeObject for HashMap<K, V> {
+    fn is_variable_size() -> bool {
+        true
+    }
+}
+
+impl<K, V> VariableSizeObject for BTreeMap<K, V> {
+    fn is_variable_size() -> bool {
+        true
+    }
+}
+
+impl<T> VariableSizeObject for HashSet<T> {
+    fn is_variable_size() -> bool {
+        true
+    }
+}
+
+impl<T> VariableSizeObject for BTreeSet<T> {
+    fn is_variable_size() -> bool {
+        true
+    }
+}
+
+impl<T> VariableSizeObject for Sequence<T> {
+    fn is_variable_size() -> bool {
+        true
+    }
+}
+
+impl VariableSizeObject for String {
+    fn is_variable_size() -> bool {
+        true
+    }
+}
+
 impl VariableSizeObject for Utf8String {
     fn is_variable_size() -> bool {
         true
@@ -0,0 +1,594 @@
+//! Transports for delivering fuzzed inputs to network services.
+//!
+//! A [Transport] owns the connection to a target and takes care of reusing it across iterations,
+//! reconnecting when the target drops it, applying read timeouts, and capturing whatever the
+//! target sends back. [Transport::exchange] also classifies the target as unreachable when it
+//! goes away after receiving an input, which usually means the input crashed it.
+use crate::traits::{BinarySerialize, SerializedSize};
+use byteorder::ByteOrder;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs, UdpSocket};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[cfg(unix)]
+use std::os::unix::io::AsRawFd;
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+#[cfg(unix)]
+use std::path::{Path, PathBuf};
+
+const DEFAULT_READ_TIMEOUT: Duration = Duration::from_millis(100);
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+const DEFAULT_RECONNECT_ATTEMPTS: usize = 3;
+const DEFAULT_RECONNECT_DELAY: Duration = Duration::from_millis(50);
+const DEFAULT_MAX_RESPONSE_SIZE: usize = 0x10000;
+
+/// Settings shared by all transports.
+#[derive(Debug, Clone)]
+pub struct TransportConfig {
+    /// How long to wait for more response data before considering the response complete
+    pub read_timeout: Duration,
+    /// How long to wait when establishing a connection. Not supported by Unix domain sockets.
+    pub connect_timeout: Duration,
+    /// How many times to try connecting before the target is considered unreachable
+    pub reconnect_attempts: usize,
+    /// How long to wait between connection attempts
+    pub reconnect_delay: Duration,
+    /// Whether the connection is kept open between exchanges
+    pub reuse_connection: bool,
+    /// The max number of response bytes captured per exchange
+    pub max_response_size: usize,
+}
+
+impl Default for TransportConfig {
+    fn default() -> Self {
+        TransportConfig {
+            read_timeout: DEFAULT_READ_TIMEOUT,
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            reconnect_attempts: DEFAULT_RECONNECT_ATTEMPTS,
+            reconnect_delay: DEFAULT_RECONNECT_DELAY,
+            reuse_connection: true,
+            max_response_size: DEFAULT_MAX_RESPONSE_SIZE,
+        }
+    }
+}
+
+impl TransportConfig {
+    pub fn new() -> TransportConfig {
+        Default::default()
+    }
+
+    pub fn read_timeout(&mut self, read_timeout: Duration) -> &mut TransportConfig {
+        self.read_timeout = read_timeout;
+        self
+    }
+
+    pub fn connect_timeout(&mut self, connect_timeout: Duration) -> &mut TransportConfig {
+        self.connect_timeout = connect_timeout;
+        self
+    }
+
+    pub fn reconnect_attempts(&mut self, reconnect_attempts: usize) -> &mut TransportConfig {
+        self.reconnect_attempts = reconnect_attempts;
+        self
+    }
+
+    pub fn reconnect_delay(&mut self, reconnect_delay: Duration) -> &mut TransportConfig {
+        self.reconnect_delay = reconnect_delay;
+        self
+    }
+
+    pub fn reuse_connection(&mut self, reuse_connection: bool) -> &mut TransportConfig {
+        self.reuse_connection = reuse_connection;
+        self
+    }
+
+    pub fn max_response_size(&mut self, max_response_size: usize) -> &mut TransportConfig {
+        self.max_response_size = max_response_size;
+        self
+    }
+}
+
+/// Classification of a single [Transport::exchange].
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ExchangeKind {
+    /// The input was delivered and the target is still reachable
+    Ok,
+    /// The target could not be reached before the input was sent. This is often caused by the
+    /// previous input.
+    Unreachable,
+    /// The target stopped being reachable after the input was sent, which likely means that the
+    /// input crashed it
+    PossibleCrash,
+    /// The target closed the connection without responding but still accepts new connections.
+    /// This is normal for some targets, but may also mean that the worker handling the connection
+    /// crashed.
+    ClosedWithoutResponse,
+}
+
+impl ExchangeKind {
+    /// Returns whether the target could not be reached
+    pub fn is_failure(&self) -> bool {
+        match self {
+            ExchangeKind::Unreachable | ExchangeKind::PossibleCrash => true,
+            ExchangeKind::Ok | ExchangeKind::ClosedWithoutResponse => false,
+        }
+    }
+}
+
+/// The outcome of sending one input to the target.
+#[derive(Debug, Clone)]
+pub struct ExchangeResult {
+    pub kind: ExchangeKind,
+    /// Everything the target sent back before the read timeout elapsed or it closed the
+    /// connection
+    pub response: Vec<u8>,
+    pub duration: Duration,
+}
+
+impl ExchangeResult {
+    /// Converts this result into what a fuzzer callback should return: an unreachable target is
+    /// reported as an error so that the driver counts it as a failed iteration.
+    #[allow(clippy::result_unit_err)]
+    pub fn as_iteration_result(&self) -> Result<(), ()> {
+        if self.kind.is_failure() {
+            Err(())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// A connection to a target which inputs can be sent over.
+pub trait Transport {
+    /// Establishes a connection to the target
+    fn connect(&mut self) -> io::Result<()>;
+
+    /// Closes the current connection, if any
+    fn disconnect(&mut self);
+
+    /// Returns whether there is an open connection. This becomes false once the target closes
+    /// the connection.
+    fn is_connected(&self) -> bool;
+
+    /// Closes the current connection if the target has closed it since it was last used, without
+    /// waiting or consuming any data. Transports which can't tell keep the connection.
+    fn drop_stale_connection(&mut self) {}
+
+    /// Sends `data` over the current connection
+    fn send(&mut self, data: &[u8]) -> io::Result<()>;
+
+    /// Reads the target's response until the read timeout elapses, the target closes the
+    /// connection, or the max response size is reached
+    fn receive(&mut self) -> io::Result<Vec<u8>>;
+
+    fn config(&self) -> &TransportConfig;
+
+    /// Tries to connect up to [TransportConfig::reconnect_attempts] times
+    fn connect_with_retries(&mut self) -> io::Result<()> {
+        let attempts = std::cmp::max(self.config().reconnect_attempts, 1);
+        let delay = self.config().reconnect_delay;
+
+        let mut result = Ok(());
+        for attempt in 0..attempts {
+            if attempt > 0 {
+                thread::sleep(delay);
+            }
+
+            result = self.connect();
+            if result.is_ok() {
+                break;
+            }
+        }
+
+        result
+    }
+
+    /// Sends `data` to the target and captures its response, connecting or reconnecting as
+    /// needed. The target is considered to have possibly crashed if it closes the connection
+    /// without responding and then can't be reached.
+    ///
+    /// A reused connection which the target has closed since the last exchange is replaced before
+    /// sending. Once `data` has been sent it is never sent again, since the target may already
+    /// have acted on it.
+    fn exchange(&mut self, data: &[u8]) -> ExchangeResult {
+        let start = Instant::now();
+        let finish = |kind, response| ExchangeResult {
+            kind,
+            response,
+            duration: start.elapsed(),
+        };
+
+        self.drop_stale_connection();
+
+        let reused = self.is_connected();
+        if !reused && self.connect_with_retries().is_err() {
+            return finish(ExchangeKind::Unreachable, Vec::new());
+        }
+
+        if self.send(data).is_err() {
+            self.disconnect();
+
+            // the target may have closed a reused connection after it was checked, in which case
+            // the send fails without the target acting on the input
+            if !reused || self.connect_with_retries().is_err() {
+                return finish(ExchangeKind::PossibleCrash, Vec::new());
+            }
+
+            if self.send(data).is_err() {
+                self.disconnect();
+                return finish(ExchangeKind::PossibleCrash, Vec::new());
+            }
+        }
+
+        let (response, failed) = match self.receive() {
+            Ok(response) => (response, false),
+            Err(e) => {
+                self.disconnect();
+
+                // connected UDP sockets report an ICMP port unreachable this way
+                if e.kind() == io::ErrorKind::ConnectionRefused {
+                    return finish(ExchangeKind::PossibleCrash, Vec::new());
+                }
+
+                (Vec::new(), true)
+            }
+        };
+
+        // the connection closing isn't a problem by itself, but a target which closed it without
+        // responding should still accept new connections
+        let probe = !self.is_connected() && (failed || response.is_empty());
+
+        if !self.config().reuse_connection {
+            self.disconnect();
+        }
+
+        if probe {
+            if self.connect_with_retries().is_err() {
+                return finish(ExchangeKind::PossibleCrash, response);
+            }
+
+            if !self.config().reuse_connection {
+                self.disconnect();
+            }
+
+            if response.is_empty() {
+                return finish(ExchangeKind::ClosedWithoutResponse, response);
+            }
+        }
+
+        finish(ExchangeKind::Ok, response)
+    }
+
+    /// Serializes `object` and exchanges it with the target
+    fn exchange_object<T, E>(&mut self, object: &T) -> ExchangeResult
+    where
+        Self: Sized,
+        T: BinarySerialize + SerializedSize,
+        E: ByteOrder,
+    {
+        let mut data = Vec::with_capacity(object.serialized_size());
+        object.binary_serialize::<_, E>(&mut data);
+
+        self.exchange(&data)
+    }
+}
+
+/// Reads from a stream until the read timeout elapses or the peer closes the connection.
+/// Returns whether the connection was closed along with the data read.
+fn read_stream_response<S: Read>(stream: &mut S, max_size: usize) -> io::Result<(Vec<u8>, bool)> {
+    let mut response = Vec::new();
+    let mut buffer = [0u8; 0x1000];
+
+    while response.len() < max_size {
+        match stream.read(&mut buffer) {
+            Ok(0) => return Ok((response, true)),
+            Ok(read) => {
+                let read = std::cmp::min(read, max_size - response.len());
+                response.extend_from_slice(&buffer[..read]);
+            }
+            Err(ref e)
+                if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut =>
+            {
+                break;
+            }
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(ref e) if e.kind() == io::ErrorKind::ConnectionReset => {
+                return Ok((response, true))
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok((response, false))
+}
+
+/// Returns whether a non-blocking peek at a stream shows that the peer has closed it
+fn peek_shows_closed(peeked: io::Result<usize>) -> bool {
+    match peeked {
+        Ok(0) => true,
+        Ok(_) => false,
+        Err(ref e)
+            if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::Interrupted =>
+        {
+            false
+        }
+        Err(_) => true,
+    }
+}
+
+/// A TCP connection to a target.
+#[derive(Debug)]
+pub struct TcpTransport {
+    addr: SocketAddr,
+    stream: Option<TcpStream>,
+    config: TransportConfig,
+}
+
+impl TcpTransport {
+    /// Creates a transport for the target at `addr`. No connection is made until the first
+    /// exchange.
+    pub fn new<A: ToSocketAddrs>(addr: A, config: TransportConfig) -> io::Result<TcpTransport> {
+        let addr = addr.to_socket_addrs()?.next().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "no address to connect to")
+        })?;
+
+        Ok(TcpTransport {
+            addr,
+            stream: None,
+            config,
+        })
+    }
+}
+
+impl Transport for TcpTransport {
+    fn connect(&mut self) -> io::Result<()> {
+        let stream = TcpStream::connect_timeout(&self.addr, self.config.connect_timeout)?;
+        stream.set_read_timeout(Some(self.config.read_timeout))?;
+        stream.set_nodelay(true)?;
+
+        self.stream = Some(stream);
+
+        Ok(())
+    }
+
+    fn disconnect(&mut self) {
+        self.stream = None;
+    }
+
+    fn is_connected(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    fn drop_stale_connection(&mut self) {
+        let closed = match self.stream {
+            Some(ref stream) => {
+                let mut byte = [0u8];
+                let peeked = stream
+                    .set_nonblocking(true)
+                    .and_then(|_| stream.peek(&mut byte));
+                let restored = stream.set_nonblocking(false);
+
+                peek_shows_closed(peeked) || restored.is_err()
+            }
+            None => false,
+        };
+
+        if closed {
+            self.disconnect();
+        }
+    }
+
+    fn send(&mut self, data: &[u8]) -> io::Result<()> {
+        match self.stream {
+            Some(ref mut stream) => stream.write_all(data),
+            None => Err(io::ErrorKind::NotConnected.into()),
+        }
+    }
+
+    fn receive(&mut self) -> io::Result<Vec<u8>> {
+        let (response, closed) = match self.stream {
+            Some(ref mut stream) => read_stream_response(stream, self.config.max_response_size)?,
+            None => return Err(io::ErrorKind::NotConnected.into()),
+        };
+
+        if closed {
+            self.disconnect();
+        }
+
+        Ok(response)
+    }
+
+    fn config(&self) -> &TransportConfig {
+        &self.config
+    }
+}
+
+/// A Unix domain socket connection to a target.
+#[cfg(unix)]
+#[derive(Debug)]
+pub struct UnixTransport {
+    path: PathBuf,
+    stream: Option<UnixStream>,
+    config: TransportConfig,
+}
+
+#[cfg(unix)]
+impl UnixTransport {
+    /// Creates a transport for the target listening at `path`. No connection is made until the
+    /// first exchange.
+    pub fn new<P: AsRef<Path>>(path: P, config: TransportConfig) -> UnixTransport {
+        UnixTransport {
+            path: path.as_ref().to_owned(),
+            stream: None,
+            config,
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Transport for UnixTransport {
+    fn connect(&mut self) -> io::Result<()> {
+        let stream = UnixStream::connect(&self.path)?;
+        stream.set_read_timeout(Some(self.config.read_timeout))?;
+
+        self.stream = Some(stream);
+
+        Ok(())
+    }
+
+    fn disconnect(&mut self) {
+        self.stream = None;
+    }
+
+    fn is_connected(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    fn drop_stale_connection(&mut self) {
+        let closed = match self.stream {
+            Some(ref stream) => {
+                let mut byte = [0u8];
+                let peeked = unsafe {
+                    libc::recv(
+                        stream.as_raw_fd(),
+                        byte.as_mut_ptr() as *mut libc::c_void,
+                        byte.len(),
+                        libc::MSG_PEEK | libc::MSG_DONTWAIT,
+                    )
+                };
+
+                let peeked = if peeked < 0 {
+                    Err(io::Error::last_os_error())
+                } else {
+                    Ok(peeked as usize)
+                };
+
+                peek_shows_closed(peeked)
+            }
+            None => false,
+        };
+
+        if closed {
+            self.disconnect();
+        }
+    }
+
+    fn send(&mut self, data: &[u8]) -> io::Result<()> {
+        match self.stream {
+            Some(ref mut stream) => stream.write_all(data),
+            None => Err(io::ErrorKind::NotConnected.into()),
+        }
+    }
+
+    fn receive(&mut self) -> io::Result<Vec<u8>> {
+        let (response, closed) = match self.stream {
+            Some(ref mut stream) => read_stream_response(stream, self.config.max_response_size)?,
+            None => return Err(io::ErrorKind::NotConnected.into()),
+        };
+
+        if closed {
+            self.disconnect();
+        }
+
+        Ok(response)
+    }
+
+    fn config(&self) -> &TransportConfig {
+        &self.config
+    }
+}
+
+/// A UDP socket which sends each input as a single datagram.
+///
+/// Since UDP is connectionless the target can only be detected as unreachable if the OS reports
+/// an ICMP port unreachable message for it.
+#[derive(Debug)]
+pub struct UdpTransport {
+    addr: SocketAddr,
+    socket: Option<UdpSocket>,
+    config: TransportConfig,
+}
+
+impl UdpTransport {
+    /// Creates a transport for the target at `addr`
+    pub fn new<A: ToSocketAddrs>(addr: A, config: TransportConfig) -> io::Result<UdpTransport> {
+        let addr = addr
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no address to send to"))?;
+
+        Ok(UdpTransport {
+            addr,
+            socket: None,
+            config,
+        })
+    }
+}
+
+impl Transport for UdpTransport {
+    fn connect(&mut self) -> io::Result<()> {
+        let local_addr: SocketAddr = if self.addr.is_ipv4() {
+            ([0, 0, 0, 0], 0).into()
+        } else {
+            ([0u16; 8], 0).into()
+        };
+
+        let socket = UdpSocket::bind(local_addr)?;
+        socket.connect(self.addr)?;
+        socket.set_read_timeout(Some(self.config.read_timeout))?;
+
+        self.socket = Some(socket);
+
+        Ok(())
+    }
+
+    fn disconnect(&mut self) {
+        self.socket = None;
+    }
+
+    fn is_connected(&self) -> bool {
+        self.socket.is_some()
+    }
+
+    fn send(&mut self, data: &[u8]) -> io::Result<()> {
+        match self.socket {
+            Some(ref socket) => socket.send(data).map(|_| ()),
+            None => Err(io::ErrorKind::NotConnected.into()),
+        }
+    }
+
+    fn receive(&mut self) -> io::Result<Vec<u8>> {
+        let socket = match self.socket {
+            Some(ref socket) => socket,
+            None => return Err(io::ErrorKind::NotConnected.into()),
+        };
+
+        let mut response = Vec::new();
+        let mut buffer = vec![0u8; 0x10000];
+
+        while response.len() < self.config.max_response_size {
+            match socket.recv(&mut buffer) {
+                Ok(read) => {
+                    let read = std::cmp::min(read, self.config.max_response_size - response.len());
+                    response.extend_from_slice(&buffer[..read]);
+                }
+                Err(ref e)
+                    if e.kind() == io::ErrorKind::WouldBlock
+                        || e.kind() == io::ErrorKind::TimedOut =>
+                {
+                    break;
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(response)
+    }
+
+    fn config(&self) -> &TransportConfig {
+        &self.config
+    }
+}
@@ -1,8 +1,15 @@
+use crate::traits::{BinarySerialize, FromPrimitive, SerializedSize, StateMachine, VarIntValue};
+use byteorder::ByteOrder;
 use num_traits::Bounded;
-use std::fmt::Debug;
+use std::borrow::Cow;
+use std::convert::TryFrom;
+use std::fmt::{self, Debug};
+use std::ops::Deref;
 
 #[cfg(feature = "serde_support")]
-use serde::{Deserialize, Serialize};
+use serde::de::{self, SeqAccess, Visitor};
+#[cfg(feature = "serde_support")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 /// Represents an enum that can contain unsafe values.
 ///
@@ -56,6 +63,39 @@ where
     }
 }
 
+impl<T, I> UnsafeEnum<T, I> {
+    /// Returns `true` if this holds one of `T`'s variants
+    pub fn is_valid(&self) -> bool {
+        match *self {
+            UnsafeEnum::Valid(_) => true,
+            UnsafeEnum::Invalid(_) => false,
+        }
+    }
+}
+
+impl<T, I> UnsafeEnum<T, I>
+where
+    T: FromPrimitive<Output = I>,
+    I: Copy,
+{
+    /// Creates an `UnsafeEnum` from a raw discriminant. The result is `Valid` if `value` is the
+    /// discriminant of one of `T`'s variants.
+    pub fn from_primitive(value: I) -> Self {
+        match T::from_primitive(value) {
+            Some(variant) => UnsafeEnum::Valid(variant),
+            None => UnsafeEnum::Invalid(value),
+        }
+    }
+
+    /// Converts an `Invalid` value that is actually the discriminant of one of `T`'s variants
+    /// into that `Valid` variant
+    pub fn normalize(&mut self) {
+        if let UnsafeEnum::Invalid(value) = *self {
+            *self = Self::from_primitive(value);
+        }
+    }
+}
+
 impl<E, T> crate::traits::ToPrimitive for UnsafeEnum<E, T>
 where
     E: crate::traits::ToPrimitive<Output = T>,
@@ -71,35 +111,604 @@ where
     }
 }
 
-// TODO: Clean up this string interface. This isn't the cleanest
-/// Wrapper around `String` that provides mutation methods appropriate for UTF-8 encoded Strings
-#[derive(Debug, Default, Clone)]
+/// A UTF-8 encoded string which, unlike `String`, may contain malformed sequences (e.g. overlong
+/// encodings, lone continuation bytes, or encoded surrogates) once it has been mutated.
+#[derive(Default, Clone, PartialEq)]
 pub struct Utf8String {
-    pub(crate) inner: Vec<Utf8Char>,
+    pub(crate) inner: Vec<u8>,
 }
 
 impl Utf8String {
     pub fn new(s: &str) -> Self {
         Utf8String {
-            inner: s.chars().map(|c| Utf8Char(c)).collect(),
+            inner: s.as_bytes().to_vec(),
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.inner
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.inner
+    }
+
+    /// Returns the string if it is valid UTF-8
+    pub fn to_str(&self) -> Option<&str> {
+        std::str::from_utf8(&self.inner).ok()
+    }
+
+    /// Returns the string with any malformed sequences replaced by U+FFFD
+    pub fn to_string_lossy(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.inner)
+    }
+}
+
+impl Debug for Utf8String {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Utf8String")
+            .field(&self.to_string_lossy())
+            .finish()
+    }
+}
+
+impl Deref for Utf8String {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.inner
+    }
+}
+
+impl From<String> for Utf8String {
+    fn from(s: String) -> Self {
+        Utf8String {
+            inner: s.into_bytes(),
+        }
+    }
+}
+
+impl From<&str> for Utf8String {
+    fn from(s: &str) -> Self {
+        Utf8String::new(s)
+    }
+}
+
+impl From<Utf8String> for Vec<u8> {
+    fn from(s: Utf8String) -> Self {
+        s.inner
+    }
+}
+
+/// Valid UTF-8 is serialized as a string so that it stays readable (and editable) in formats like
+/// JSON. Malformed strings are serialized as raw bytes.
+#[cfg(feature = "serde_support")]
+impl Serialize for Utf8String {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.to_str() {
+            Some(s) => serializer.serialize_str(s),
+            None => serializer.serialize_bytes(&self.inner),
         }
     }
 }
 
+/// Accepts a string, a byte buffer, or a sequence of bytes. This requires a self-describing format.
+#[cfg(feature = "serde_support")]
+impl<'de> Deserialize<'de> for Utf8String {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(Utf8StringVisitor)
+    }
+}
+
+#[cfg(feature = "serde_support")]
+struct Utf8StringVisitor;
+
+#[cfg(feature = "serde_support")]
+impl<'de> Visitor<'de> for Utf8StringVisitor {
+    type Value = Utf8String;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("a string or a sequence of bytes")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Utf8String, E> {
+        Ok(Utf8String::new(v))
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Utf8String, E> {
+        Ok(Utf8String { inner: v.to_vec() })
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Utf8String, A::Error> {
+        let mut inner = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(byte) = seq.next_element()? {
+            inner.push(byte);
+        }
+
+        Ok(Utf8String { inner })
+    }
+}
+
 /// Wrapper around `String` that provides mutation methods appropriate for ASCII encoded Strings
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde_support", serde(try_from = "String", into = "String"))]
 pub struct AsciiString {
-    pub(crate) inner: Vec<AsciiChar>,
+    pub(crate) inner: String,
 }
 
 impl AsciiString {
+    /// Creates a new string from `s`, replacing any non-ASCII characters with `?`. Use
+    /// `AsciiString::try_from` to reject them instead.
     pub fn new(s: &str) -> Self {
         AsciiString {
-            inner: s.chars().map(|c| AsciiChar(c)).collect(),
+            inner: s
+                .chars()
+                .map(|c| if c.is_ascii() { c } else { '?' })
+                .collect(),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.inner
+    }
+}
+
+impl Deref for AsciiString {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.inner
+    }
+}
+
+impl TryFrom<String> for AsciiString {
+    type Error = NotAsciiError;
+
+    fn try_from(inner: String) -> Result<Self, NotAsciiError> {
+        match inner.bytes().position(|b| !b.is_ascii()) {
+            Some(position) => Err(NotAsciiError {
+                string: inner,
+                position,
+            }),
+            None => Ok(AsciiString { inner }),
         }
     }
 }
 
+impl TryFrom<&str> for AsciiString {
+    type Error = NotAsciiError;
+
+    fn try_from(s: &str) -> Result<Self, NotAsciiError> {
+        AsciiString::try_from(s.to_owned())
+    }
+}
+
+impl From<AsciiString> for String {
+    fn from(s: AsciiString) -> Self {
+        s.inner
+    }
+}
+
+/// The error returned when converting a string which contains non-ASCII characters into an
+/// [AsciiString]
+#[derive(Debug, Clone, PartialEq)]
+pub struct NotAsciiError {
+    string: String,
+    position: usize,
+}
+
+impl NotAsciiError {
+    /// The byte offset of the first non-ASCII character
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Returns the string which failed to convert
+    pub fn into_string(self) -> String {
+        self.string
+    }
+}
+
+impl fmt::Display for NotAsciiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "string contains a non-ASCII character at byte {}",
+            self.position
+        )
+    }
+}
+
+impl std::error::Error for NotAsciiError {}
+
+/// An integer serialized as a variable-length LEB128 sequence, the format used by protobuf varints,
+/// DWARF, and WebAssembly. Each byte holds 7 bits of the value starting with the least significant
+/// bits, and the high bit is set on every byte except the last.
+///
+/// Unsigned types are encoded directly. Signed types are ZigZag encoded first (as with protobuf's
+/// `sint32`/`sint64`) so that small negative numbers stay short. See [VarU32], [VarU64], [ZigZag32],
+/// and [ZigZag64].
+///
+/// Values are generated with their canonical (shortest) encoding. Mutation may make the encoding
+/// non-canonical by padding it with redundant continuation bytes (overlong) or by leaving the
+/// continuation bit set on the final byte (truncated).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub struct VarInt<T> {
+    pub(crate) value: T,
+    pub(crate) overlong_bytes: u8,
+    pub(crate) truncated: bool,
+}
+
+/// `u32` serialized as LEB128
+pub type VarU32 = VarInt<u32>;
+/// `u64` serialized as LEB128
+pub type VarU64 = VarInt<u64>;
+/// `i32` serialized as a ZigZag encoded LEB128
+pub type ZigZag32 = VarInt<i32>;
+/// `i64` serialized as a ZigZag encoded LEB128
+pub type ZigZag64 = VarInt<i64>;
+
+impl<T: VarIntValue> VarInt<T> {
+    /// Creates a new value with a canonical encoding
+    pub fn new(value: T) -> Self {
+        VarInt {
+            value,
+            overlong_bytes: 0,
+            truncated: false,
+        }
+    }
+
+    pub fn value(&self) -> T {
+        self.value
+    }
+
+    /// Replaces the value. The encoding of the previous value (if non-canonical) is kept.
+    pub fn set_value(&mut self, value: T) {
+        self.value = value;
+    }
+
+    /// Returns whether this value will be serialized using the shortest valid encoding
+    pub fn is_canonical(&self) -> bool {
+        self.overlong_bytes == 0 && !self.truncated
+    }
+
+    /// Resets the encoding to the shortest valid encoding
+    pub fn canonicalize(&mut self) {
+        self.overlong_bytes = 0;
+        self.truncated = false;
+    }
+
+    /// Returns the LEB128 encoding of this value, including any non-canonical padding or truncation
+    pub fn encode(&self) -> Vec<u8> {
+        let mut raw = self.value.to_raw();
+        let mut bytes = Vec::with_capacity(T::MAX_ENCODED_LEN + self.overlong_bytes as usize);
+
+        loop {
+            let byte = (raw & 0x7f) as u8;
+            raw >>= 7;
+
+            if raw == 0 {
+                bytes.push(byte);
+                break;
+            }
+
+            bytes.push(byte | 0x80);
+        }
+
+        for _ in 0..self.overlong_bytes {
+            *bytes.last_mut().unwrap() |= 0x80;
+            bytes.push(0);
+        }
+
+        if self.truncated {
+            *bytes.last_mut().unwrap() |= 0x80;
+        }
+
+        bytes
+    }
+
+    /// Length of [VarInt::encode] in bytes
+    pub fn encoded_len(&self) -> usize {
+        let significant_bits = 64 - self.value.to_raw().leading_zeros() as usize;
+
+        std::cmp::max(1, significant_bits.div_ceil(7)) + self.overlong_bytes as usize
+    }
+
+    /// Decodes a LEB128 value from the start of `bytes`, returning it along with the number of
+    /// bytes consumed. Overlong encodings are accepted and preserved. Returns `None` if the input
+    /// ends before the final byte.
+    pub fn decode(bytes: &[u8]) -> Option<(Self, usize)> {
+        let mut raw = 0u64;
+        let mut canonical_len = 1;
+
+        for (i, byte) in bytes.iter().enumerate() {
+            let bits = u64::from(byte & 0x7f);
+            if bits != 0 {
+                canonical_len = i + 1;
+            }
+
+            if i < 10 {
+                raw |= bits << (i * 7);
+            }
+
+            if byte & 0x80 == 0 {
+                let decoded = VarInt {
+                    value: T::from_raw(raw),
+                    overlong_bytes: std::cmp::min(i + 1 - canonical_len, u8::MAX as usize) as u8,
+                    truncated: false,
+                };
+
+                return Some((decoded, i + 1));
+            }
+        }
+
+        None
+    }
+}
+
+impl<T: VarIntValue> From<T> for VarInt<T> {
+    fn from(value: T) -> Self {
+        VarInt::new(value)
+    }
+}
+
+/// A sequence preceded by a length prefix of type `P` (one of `u8`, `u16`, `u32`, or `u64`) which
+/// mutation may desynchronize from the data. Prefixes written for the `#[lain(prefix = "u16")]`
+/// field attribute always match their data, so use this type for fields whose prefix should be
+/// fuzzed as well.
+///
+/// The prefix normally holds the exact length of the value (see
+/// [encoding][crate::encoding] for what is counted). Mutation occasionally replaces it with an
+/// off-by-one, zero, maximal, or dangerous value instead. This override is part of the object, so
+/// clones and serde round trips serialize identically.
+///
+/// Encoding attributes such as `#[lain(utf16le)]` on a field of this type apply to the value. Don't
+/// combine it with the `prefix` attribute.
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub struct LengthPrefixed<P, T> {
+    pub(crate) value: T,
+    pub(crate) len_override: Option<P>,
+}
+
+impl<P, T> LengthPrefixed<P, T> {
+    /// Creates a new value with an exact length prefix. Serialization panics if the length of
+    /// `value` does not fit in `P`.
+    pub fn new(value: T) -> Self {
+        LengthPrefixed {
+            value,
+            len_override: None,
+        }
+    }
+
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+
+    pub fn value_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+
+    /// Returns the value written in place of the length, if the prefix has been desynchronized
+    pub fn len_override(&self) -> Option<P>
+    where
+        P: Copy,
+    {
+        self.len_override
+    }
+
+    /// Writes `len` in place of the actual length, or the actual length if `None`
+    pub fn set_len_override(&mut self, len: Option<P>) {
+        self.len_override = len;
+    }
+
+    /// Returns whether the prefix will match the data
+    pub fn is_exact(&self) -> bool {
+        self.len_override.is_none()
+    }
+}
+
+impl<P, T> Deref for LengthPrefixed<P, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<P, T> From<T> for LengthPrefixed<P, T> {
+    fn from(value: T) -> Self {
+        LengthPrefixed::new(value)
+    }
+}
+
+/// Seed for the padding bytes of the container holding it. Mark the field with
+/// `#[lain(fuzz_padding)]` to have the `BinarySerialize` derive fill padding inserted by the
+/// [layout][crate::layout] attributes with bytes generated from the seed instead of zeroes. The
+/// seed itself is not serialized.
+///
+/// Generation and mutation pick a new random seed. The default seed of `None` gives zeroed padding.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub struct PaddingSeed {
+    pub(crate) seed: Option<u64>,
+}
+
+impl PaddingSeed {
+    pub fn new(seed: Option<u64>) -> Self {
+        PaddingSeed { seed }
+    }
+
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    pub fn set_seed(&mut self, seed: Option<u64>) {
+        self.seed = seed;
+    }
+}
+
+/// An enum with a `#[lain(tag = "u16")]` attribute (see [TaggedEnum][crate::traits::TaggedEnum])
+/// whose tag mutation may desynchronize from the variant that follows it.
+///
+/// The tag normally belongs to the selected variant. Mutation occasionally replaces it with the tag
+/// of another variant or an arbitrary value of the tag type instead. This override is part of the
+/// object, so clones and serde round trips serialize identically.
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub struct TagOverride<T> {
+    pub(crate) value: T,
+    pub(crate) tag_override: Option<u64>,
+}
+
+impl<T> TagOverride<T> {
+    /// Creates a new value whose tag matches its variant
+    pub fn new(value: T) -> Self {
+        TagOverride {
+            value,
+            tag_override: None,
+        }
+    }
+
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+
+    pub fn value_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+
+    /// Returns the value written in place of the variant's tag, if the tag has been
+    /// desynchronized
+    pub fn tag_override(&self) -> Option<u64> {
+        self.tag_override
+    }
+
+    /// Writes `tag` in place of the variant's tag, or the variant's tag if `None`. The value is
+    /// converted to the tag type with `as`.
+    pub fn set_tag_override(&mut self, tag: Option<u64>) {
+        self.tag_override = tag;
+    }
+
+    /// Returns whether the tag will match the variant
+    pub fn is_exact(&self) -> bool {
+        self.tag_override.is_none()
+    }
+}
+
+impl<T> Deref for TagOverride<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> From<T> for TagOverride<T> {
+    fn from(value: T) -> Self {
+        TagOverride::new(value)
+    }
+}
+
+/// An ordered sequence of messages which is generated and mutated as a whole.
+///
+/// This is useful for stateful protocols where messages have to arrive in a certain order (e.g.
+/// a handshake, then authentication, then commands). Besides mutating individual messages,
+/// mutations may insert, delete, duplicate, or reorder messages. Which messages may follow which
+/// can be restricted by implementing [StateMachine][crate::traits::StateMachine] for `T`.
+///
+/// The sequence serializes as all of its messages back-to-back. Use [Sequence::serialize_steps] to
+/// serialize each message separately.
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub struct Sequence<T> {
+    pub(crate) messages: Vec<T>,
+}
+
+impl<T> Sequence<T> {
+    pub fn new() -> Self {
+        Sequence {
+            messages: Vec::new(),
+        }
+    }
+
+    pub fn messages(&self) -> &[T] {
+        &self.messages
+    }
+
+    pub fn messages_mut(&mut self) -> &mut Vec<T> {
+        &mut self.messages
+    }
+
+    pub fn into_messages(self) -> Vec<T> {
+        self.messages
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, T> {
+        self.messages.iter()
+    }
+
+    /// Returns whether every message is allowed to follow the one before it
+    pub fn is_valid(&self) -> bool {
+        match self.messages.first() {
+            Some(first) if !first.is_valid_start() => false,
+            _ => self
+                .messages
+                .windows(2)
+                .all(|pair| pair[1].is_valid_successor(&pair[0])),
+        }
+    }
+
+    /// Serializes each message into its own buffer so that the steps can be sent individually
+    pub fn serialize_steps<E: ByteOrder>(&self) -> Vec<Vec<u8>>
+    where
+        T: BinarySerialize + SerializedSize,
+    {
+        self.messages
+            .iter()
+            .map(|message| {
+                let mut buffer = Vec::with_capacity(message.serialized_size());
+                message.binary_serialize::<_, E>(&mut buffer);
+
+                buffer
+            })
+            .collect()
+    }
+}
+
+impl<T> From<Vec<T>> for Sequence<T> {
+    fn from(messages: Vec<T>) -> Self {
+        Sequence { messages }
+    }
+}
+
+impl<'a, T> IntoIterator for &'a Sequence<T> {
+    type Item = &'a T;
+    type IntoIter = std::slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.messages.iter()
+    }
+}
+
 /// Represents a UTF-8 character.
 #[derive(Default, Debug, Clone)]
 pub(crate) struct Utf8Char(pub(crate) char);
@@ -111,6 +720,7 @@ pub(crate) struct AsciiChar(pub(crate) char);
 /// Data structure holding constraints that the [NewFuzzed::new_fuzzed][lain::traits::NewFuzzed::new_fuzzed] or
 /// [Mutatable::mutate][lain::traits::Mutatable::mutate] methods should try to respect.
 #[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
 pub struct Constraints<T: Bounded + Debug> {
     /// The contextual "min" bound
     pub min: Option<T>,
@@ -180,6 +790,7 @@ impl<T: Bounded + Debug> Constraints<T> {
 
 /// Which direction to weigh ranges towards (min bound, upper bound, or none).
 #[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
 pub enum Weighted {
     None,
     Min,
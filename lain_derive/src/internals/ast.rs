@@ -62,10 +62,28 @@ impl<'a> Container<'a> {
     ) -> Option<Container<'a>> {
         let attrs = attr::Container::from_ast(cx, item);
 
+        if attrs.pack().is_some() && !attrs.repr_c() {
+            cx.error_spanned_by(item, "`pack` requires `repr_c`");
+        }
+
+        if attrs.tag_endian().is_some() && attrs.tag().is_none() {
+            cx.error_spanned_by(item, "`tag_endian` requires `tag`");
+        }
+
+        if attrs.tag().is_some() {
+            if let syn::Data::Struct(_) = item.data {
+                cx.error_spanned_by(item, "`tag` may only be used on enums");
+            }
+        }
+
         let data = match item.data {
-            syn::Data::Enum(ref data) => Data::Enum(enum_from_ast(cx, &data.variants)),
+            syn::Data::Enum(ref data) => {
+                let mut variants = enum_from_ast(cx, &data.variants, &attrs);
+                resolve_tag_values(cx, &attrs, &mut variants);
+                Data::Enum(variants)
+            }
             syn::Data::Struct(ref data) => {
-                let (style, fields) = struct_from_ast(cx, &data.fields);
+                let (style, fields) = struct_from_ast(cx, &data.fields, &attrs);
                 Data::Struct(style, fields)
             }
             syn::Data::Union(_) => {
@@ -89,12 +107,13 @@ impl<'a> Container<'a> {
 fn enum_from_ast<'a>(
     cx: &Ctxt,
     variants: &'a Punctuated<syn::Variant, Token![,]>,
+    container: &attr::Container,
 ) -> Vec<Variant<'a>> {
     variants
         .iter()
         .map(|variant| {
             let attrs = attr::Variant::from_ast(cx, variant);
-            let (style, fields) = struct_from_ast(cx, &variant.fields);
+            let (style, fields) = struct_from_ast(cx, &variant.fields, container);
 
             Variant {
                 ident: variant.ident.clone(),
@@ -107,15 +126,77 @@ fn enum_from_ast<'a>(
         .collect()
 }
 
-fn struct_from_ast<'a>(cx: &Ctxt, fields: &'a syn::Fields) -> (Style, Vec<Field<'a>>) {
+/// Gives every variant of a tagged enum a tag value, defaulting to the variant's index, and
+/// ensures that the values are distinct and fit in the tag type
+fn resolve_tag_values(cx: &Ctxt, container: &attr::Container, variants: &mut [Variant]) {
+    let tag = match container.tag() {
+        Some(tag) => tag,
+        None => {
+            for variant in variants.iter().filter(|v| v.attrs.tag_value().is_some()) {
+                cx.error_spanned_by(variant.original, "`tag_value` requires a container `tag`");
+            }
+            return;
+        }
+    };
+
+    let tag_bits = primitive_bit_width(tag).unwrap_or(64);
+    let mut seen = std::collections::HashSet::new();
+
+    for (i, variant) in variants.iter_mut().enumerate() {
+        let value = variant.attrs.tag_value().unwrap_or(i as u64);
+        variant.attrs.set_tag_value(value);
+
+        if tag_bits < 64 && value >> tag_bits != 0 {
+            cx.error_spanned_by(variant.original, "tag value does not fit in the `tag` type");
+        }
+
+        if !seen.insert(value) {
+            cx.error_spanned_by(variant.original, "duplicate tag value");
+        }
+    }
+}
+
+/// Points the layout of every field at the field marked `#[lain(fuzz_padding)]`, if there is one
+fn set_padding_seed(cx: &Ctxt, fields: &mut [Field]) {
+    let mut seeds = fields.iter().filter(|field| field.attrs.fuzz_padding());
+
+    let seed = match seeds.next() {
+        Some(seed) => seed.member.clone(),
+        None => return,
+    };
+
+    if let Some(duplicate) = seeds.next() {
+        cx.error_spanned_by(duplicate.original, "only one field may have `fuzz_padding`");
+    }
+
+    for field in fields.iter_mut() {
+        field.attrs.set_padding_seed(seed.clone());
+    }
+}
+
+fn struct_from_ast<'a>(
+    cx: &Ctxt,
+    fields: &'a syn::Fields,
+    container: &attr::Container,
+) -> (Style, Vec<Field<'a>>) {
     match *fields {
-        syn::Fields::Named(ref fields) => (Style::Struct, fields_from_ast(cx, &fields.named)),
-        syn::Fields::Unnamed(ref fields) => (Style::Tuple, fields_from_ast(cx, &fields.unnamed)),
+        syn::Fields::Named(ref fields) => {
+            (Style::Struct, fields_from_ast(cx, &fields.named, container))
+        }
+        syn::Fields::Unnamed(ref fields) => (
+            Style::Tuple,
+            fields_from_ast(cx, &fields.unnamed, container),
+        ),
         syn::Fields::Unit => (Style::Unit, Vec::new()),
     }
 }
 
-fn fields_from_ast<'a>(cx: &Ctxt, fields: &'a Punctuated<syn::Field, Token![,]>) -> Vec<Field<'a>> {
+fn fields_from_ast<'a>(
+    cx: &Ctxt,
+    fields: &'a Punctuated<syn::Field, Token![,]>,
+    container: &attr::Container,
+) -> Vec<Field<'a>> {
+    let bit_order = container.bit_order();
     let mut bitfield_bits = 0;
 
     let mut fields: Vec<Field<'a>> = fields
@@ -132,6 +213,42 @@ fn fields_from_ast<'a>(cx: &Ctxt, fields: &'a Punctuated<syn::Field, Token![,]>)
             original: field,
         };
 
+        if field.attrs.bits().is_some() && field.attrs.is_encoded() {
+            cx.error_spanned_by(field.ty, "Bitfields cannot use `prefix`, `nul_terminated`, or `utf16le`");
+        }
+
+        if field.attrs.bits().is_some() && field.attrs.has_explicit_layout() {
+            cx.error_spanned_by(field.ty, "Bitfields cannot use `align`, `pad`, or `offset`");
+        }
+
+        if field.attrs.fuzz_padding() && (field.attrs.bits().is_some() || field.attrs.is_encoded() || field.attrs.has_explicit_layout()) {
+            cx.error_spanned_by(field.ty, "`fuzz_padding` fields cannot use any other serialization attributes");
+        }
+
+        // bitfields are packed together, so only regular fields get padding inserted before them.
+        // the padding seed isn't written, so it doesn't take part in the layout either.
+        if field.attrs.bits().is_none() && !field.attrs.fuzz_padding() && (container.repr_c() || field.attrs.has_explicit_layout()) {
+            field.attrs.set_layout(attr::Layout {
+                repr_c: container.repr_c(),
+                pack: container.pack(),
+                padding_seed: None, // this gets fixed up later
+            });
+        }
+
+        if field.attrs.pad_bits().is_some() && (bit_order.is_none() || field.attrs.bits().is_none()) {
+            cx.error_spanned_by(field.ty, "`pad_bits` may only be used on bitfields in a container with a `bit_order`");
+        }
+
+        // bit streams don't use a backing bitfield type, so any width up to 128 bits is allowed
+        if let (Some(bits), Some(_)) = (field.attrs.bits(), bit_order) {
+            let max_bits = primitive_bit_width(field.attrs.bitfield_type().unwrap_or(field.ty)).unwrap_or(128);
+            if bits == 0 || bits > max_bits {
+                cx.error_spanned_by(field.ty, format!("Bitfield width must be between 1 and {} bits", max_bits));
+            }
+
+            return field;
+        }
+
         if let Some(bits) = field.attrs.bits() {
             field.attrs.set_bit_shift(bitfield_bits);
             bitfield_bits += bits;
@@ -147,6 +264,8 @@ fn fields_from_ast<'a>(cx: &Ctxt, fields: &'a Punctuated<syn::Field, Token![,]>)
                 bits_in_type = 32
             } else if is_primitive_type(bitfield_type, "u64") {
                 bits_in_type = 64
+            } else if is_primitive_type(bitfield_type, "u128") {
+                bits_in_type = 128
             } else {
                 cx.error_spanned_by(&field.ty, "Unsupported bitfield datatype. Did you forget to specify `#[lain(bitfield_type = \"...\")]`?");
                 return field;
@@ -167,6 +286,12 @@ fn fields_from_ast<'a>(cx: &Ctxt, fields: &'a Punctuated<syn::Field, Token![,]>)
         return fields;
     }
 
+    set_padding_seed(cx, &mut fields);
+
+    if let Some(order) = bit_order {
+        set_bit_stream_positions(&mut fields, order);
+    }
+
     let last_idx = fields.len() - 1;
     let field = &mut fields[last_idx];
     field.attrs.set_is_last_field();
@@ -174,6 +299,58 @@ fn fields_from_ast<'a>(cx: &Ctxt, fields: &'a Punctuated<syn::Field, Token![,]>)
     fields
 }
 
+/// Groups consecutive bitfields into runs which are each serialized as one bit stream
+fn set_bit_stream_positions(fields: &mut [Field], order: attr::BitOrder) {
+    let mut run_bits = 0;
+
+    for i in 0..fields.len() {
+        let bits = match fields[i].attrs.bits() {
+            Some(bits) => bits,
+            None => continue,
+        };
+
+        let is_start = run_bits == 0;
+        run_bits += bits + fields[i].attrs.pad_bits().unwrap_or(0);
+
+        let is_end = fields
+            .get(i + 1)
+            .map(|next| next.attrs.bits().is_none())
+            .unwrap_or(true);
+
+        fields[i].attrs.set_bit_stream(attr::BitStream {
+            order,
+            is_start,
+            total_bits: if is_end { Some(run_bits) } else { None },
+        });
+
+        if is_end {
+            run_bits = 0;
+        }
+    }
+}
+
+/// Returns the width in bits of a primitive integer or bool type
+pub fn primitive_bit_width(ty: &syn::Type) -> Option<usize> {
+    let widths = [
+        ("bool", 1),
+        ("u8", 8),
+        ("i8", 8),
+        ("u16", 16),
+        ("i16", 16),
+        ("u32", 32),
+        ("i32", 32),
+        ("u64", 64),
+        ("i64", 64),
+        ("u128", 128),
+        ("i128", 128),
+    ];
+
+    widths
+        .iter()
+        .find(|(name, _)| is_primitive_type(ty, name))
+        .map(|(_, width)| *width)
+}
+
 pub fn is_primitive_type(ty: &syn::Type, primitive: &str) -> bool {
     match *ty {
         syn::Type::Path(ref ty) => ty.qself.is_none() && is_primitive_path(&ty.path, primitive),
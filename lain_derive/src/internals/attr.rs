@@ -66,6 +66,11 @@ impl<'c> BoolAttr<'c> {
 pub struct Container {
     serialized_size: Option<usize>,
     min_serialized_size: Option<usize>,
+    bit_order: Option<BitOrder>,
+    repr_c: bool,
+    pack: Option<usize>,
+    tag: Option<syn::Type>,
+    tag_endian: Option<Endian>,
 }
 
 impl Container {
@@ -73,6 +78,11 @@ impl Container {
     pub fn from_ast(cx: &Ctxt, item: &syn::DeriveInput) -> Self {
         let mut serialized_size = Attr::none(cx, SERIALIZED_SIZE);
         let mut min_serialized_size = Attr::none(cx, MIN_SERIALIZED_SIZE);
+        let mut bit_order = Attr::none(cx, BIT_ORDER);
+        let mut repr_c = BoolAttr::none(cx, REPR_C);
+        let mut pack = Attr::none(cx, PACK);
+        let mut tag = Attr::none(cx, TAG);
+        let mut tag_endian = Attr::none(cx, TAG_ENDIAN);
 
         for meta_items in item.attrs.iter().filter_map(get_lain_meta_items) {
             for meta_item in meta_items {
@@ -97,6 +107,71 @@ impl Container {
                             );
                         }
                     }
+                    // `#[lain(repr_c)]`
+                    Meta(Word(ref word)) if word == REPR_C => {
+                        repr_c.set_true(word);
+                    }
+                    // `#[lain(pack = 2)]`
+                    Meta(NameValue(ref m)) if m.ident == PACK => {
+                        if let Ok(value) = parse_nonzero_usize(cx, PACK, &m.lit) {
+                            pack.set(&m.ident, value);
+                        }
+                    }
+                    // `#[lain(fuzz_padding)]` used to be a container attribute
+                    Meta(Word(ref word)) if word == FUZZ_PADDING => {
+                        cx.error_spanned_by(
+                            word,
+                            "`fuzz_padding` must be placed on a `PaddingSeed` field of the container",
+                        );
+                    }
+                    // `#[lain(tag = "u16")]`
+                    Meta(NameValue(ref m)) if m.ident == TAG => {
+                        if let Ok(s) = get_lit_str(cx, TAG, TAG, &m.lit) {
+                            match s.value().as_ref() {
+                                "u8" | "u16" | "u32" | "u64" => {
+                                    if let Ok(ty) = parse_lit_into_type(cx, TAG, &m.lit) {
+                                        tag.set(&m.ident, ty);
+                                    }
+                                }
+                                _ => cx.error_spanned_by(
+                                    &m.lit,
+                                    format!(
+                                        "`{}` must be one of \"u8\", \"u16\", \"u32\", or \"u64\"",
+                                        TAG
+                                    ),
+                                ),
+                            }
+                        }
+                    }
+                    // `#[lain(tag_endian = "big")]`
+                    Meta(NameValue(ref m)) if m.ident == TAG_ENDIAN => {
+                        if let Ok(s) = get_lit_str(cx, TAG_ENDIAN, TAG_ENDIAN, &m.lit) {
+                            match s.value().as_ref() {
+                                "big" => tag_endian.set(&m.ident, Endian::Big),
+                                "little" => tag_endian.set(&m.ident, Endian::Little),
+                                _ => cx.error_spanned_by(
+                                    &m.lit,
+                                    format!(
+                                        "`{}` must be either \"big\" or \"little\"",
+                                        TAG_ENDIAN
+                                    ),
+                                ),
+                            }
+                        }
+                    }
+                    // `#[lain(bit_order = "msb")]`
+                    Meta(NameValue(ref m)) if m.ident == BIT_ORDER => {
+                        if let Ok(s) = get_lit_str(cx, BIT_ORDER, BIT_ORDER, &m.lit) {
+                            match s.value().as_ref() {
+                                "msb" => bit_order.set(&m.ident, BitOrder::Msb),
+                                "lsb" => bit_order.set(&m.ident, BitOrder::Lsb),
+                                _ => cx.error_spanned_by(
+                                    &m.lit,
+                                    format!("`{}` must be either \"msb\" or \"lsb\"", BIT_ORDER),
+                                ),
+                            }
+                        }
+                    }
                     Meta(ref meta_item) => {
                         cx.error_spanned_by(
                             meta_item.name(),
@@ -113,6 +188,11 @@ impl Container {
         Container {
             serialized_size: serialized_size.get(),
             min_serialized_size: min_serialized_size.get(),
+            bit_order: bit_order.get(),
+            repr_c: repr_c.get(),
+            pack: pack.get(),
+            tag: tag.get(),
+            tag_endian: tag_endian.get(),
         }
     }
 
@@ -124,11 +204,83 @@ impl Container {
         self.min_serialized_size.clone()
     }
 
+    pub fn bit_order(&self) -> Option<BitOrder> {
+        self.bit_order
+    }
+
+    pub fn repr_c(&self) -> bool {
+        self.repr_c
+    }
+
+    pub fn pack(&self) -> Option<usize> {
+        self.pack
+    }
+
+    pub fn tag(&self) -> Option<&syn::Type> {
+        self.tag.as_ref()
+    }
+
+    pub fn tag_endian(&self) -> Option<Endian> {
+        self.tag_endian
+    }
+
     pub fn lain_path(&self) -> Cow<syn::Path> {
         Cow::Owned(parse_quote!(_lain))
     }
 }
 
+/// Explicit byte order for a value that would otherwise use the serializer's byte order
+#[derive(Copy, Clone, PartialEq)]
+pub enum Endian {
+    Big,
+    Little,
+}
+
+impl ToTokens for Endian {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        match *self {
+            Endian::Big => tokens.extend(quote! {_lain::byteorder::BigEndian}),
+            Endian::Little => tokens.extend(quote! {_lain::byteorder::LittleEndian}),
+        }
+    }
+}
+
+/// Bit packing order for containers which serialize their bitfields as a bit stream
+#[derive(Copy, Clone, PartialEq)]
+pub enum BitOrder {
+    Msb,
+    Lsb,
+}
+
+impl ToTokens for BitOrder {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        match *self {
+            BitOrder::Msb => tokens.extend(quote! {_lain::bitstream::BitOrder::MsbFirst}),
+            BitOrder::Lsb => tokens.extend(quote! {_lain::bitstream::BitOrder::LsbFirst}),
+        }
+    }
+}
+
+/// Position of a bitfield within a run of consecutive bitfields that are serialized as a bit stream
+#[derive(Copy, Clone)]
+pub struct BitStream {
+    pub order: BitOrder,
+    /// This field begins a new run
+    pub is_start: bool,
+    /// Set on the final field of a run to the total number of bits (including padding) in the run
+    pub total_bits: Option<usize>,
+}
+
+/// Container layout information that applies to a field which needs padding inserted before it
+#[derive(Clone)]
+pub struct Layout {
+    /// Align the field to its natural alignment (capped by `pack`) unless `align` is given
+    pub repr_c: bool,
+    pub pack: Option<usize>,
+    /// The `PaddingSeed` field which padding bytes are generated from, if any
+    pub padding_seed: Option<syn::Member>,
+}
+
 #[derive(PartialEq)]
 pub enum WeightTo {
     None,
@@ -163,6 +315,16 @@ pub struct Field {
     little_endian: bool,
     big_endian: bool,
     weight_to: Option<WeightTo>,
+    prefix: Option<syn::Type>,
+    nul_terminated: bool,
+    utf16le: bool,
+    pad_bits: Option<usize>,
+    bit_stream: Option<BitStream>,
+    align: Option<usize>,
+    pad: Option<usize>,
+    offset: Option<usize>,
+    fuzz_padding: bool,
+    layout: Option<Layout>,
     is_last_field: bool,
 }
 
@@ -179,6 +341,14 @@ impl Field {
         let mut big_endian = BoolAttr::none(cx, BIG_ENDIAN);
         let mut little_endian = BoolAttr::none(cx, LITTLE_ENDIAN);
         let mut weight_to = Attr::none(cx, WEIGHT_TO);
+        let mut prefix = Attr::none(cx, PREFIX);
+        let mut nul_terminated = BoolAttr::none(cx, NUL_TERMINATED);
+        let mut utf16le = BoolAttr::none(cx, UTF16LE);
+        let mut pad_bits = Attr::none(cx, PAD_BITS);
+        let mut align = Attr::none(cx, ALIGN);
+        let mut pad = Attr::none(cx, PAD);
+        let mut offset = Attr::none(cx, OFFSET);
+        let mut fuzz_padding = BoolAttr::none(cx, FUZZ_PADDING);
 
         for meta_items in field.attrs.iter().filter_map(get_lain_meta_items) {
             for meta_item in meta_items {
@@ -206,6 +376,45 @@ impl Field {
                             );
                         }
                     }
+                    // `#[lain(align = 8)]`
+                    Meta(NameValue(ref m)) if m.ident == ALIGN => {
+                        if let Ok(value) = parse_nonzero_usize(cx, ALIGN, &m.lit) {
+                            align.set(&m.ident, value);
+                        }
+                    }
+                    // `#[lain(pad = 4)]`
+                    Meta(NameValue(ref m)) if m.ident == PAD => {
+                        if let Int(ref i) = m.lit {
+                            pad.set(&m.ident, i.value() as usize);
+                        } else {
+                            cx.error_spanned_by(
+                                &m.lit,
+                                format!("failed to parse integer expression for `{}`", PAD),
+                            );
+                        }
+                    }
+                    // `#[lain(offset = 0x40)]`
+                    Meta(NameValue(ref m)) if m.ident == OFFSET => {
+                        if let Int(ref i) = m.lit {
+                            offset.set(&m.ident, i.value() as usize);
+                        } else {
+                            cx.error_spanned_by(
+                                &m.lit,
+                                format!("failed to parse integer expression for `{}`", OFFSET),
+                            );
+                        }
+                    }
+                    // `#[lain(pad_bits = 3)]`
+                    Meta(NameValue(ref m)) if m.ident == PAD_BITS => {
+                        if let Int(ref i) = m.lit {
+                            pad_bits.set(&m.ident, i.value() as usize);
+                        } else {
+                            cx.error_spanned_by(
+                                &m.lit,
+                                format!("failed to parse integer expression for `{}`", PAD_BITS),
+                            );
+                        }
+                    }
                     // `#[lain(bitfield_type = "u8")]`
                     Meta(NameValue(ref m)) if m.ident == BITFIELD_TYPE => {
                         if let Ok(expr) = parse_lit_into_type(&cx, BITFIELD_TYPE, &m.lit) {
@@ -287,6 +496,37 @@ impl Field {
                             }
                         }
                     }
+                    // `#[lain(prefix = "u16")]`
+                    Meta(NameValue(ref m)) if m.ident == PREFIX => {
+                        if let Ok(s) = get_lit_str(cx, PREFIX, PREFIX, &m.lit) {
+                            match s.value().as_ref() {
+                                "u8" | "u16" | "u32" | "u64" => {
+                                    if let Ok(ty) = parse_lit_into_type(cx, PREFIX, &m.lit) {
+                                        prefix.set(&m.ident, ty);
+                                    }
+                                }
+                                _ => cx.error_spanned_by(
+                                    &m.lit,
+                                    format!(
+                                        "`{}` must be one of \"u8\", \"u16\", \"u32\", or \"u64\"",
+                                        PREFIX
+                                    ),
+                                ),
+                            }
+                        }
+                    }
+                    // `#[lain(nul_terminated)]`
+                    Meta(Word(ref word)) if word == NUL_TERMINATED => {
+                        nul_terminated.set_true(word);
+                    }
+                    // `#[lain(utf16le)]`
+                    Meta(Word(ref word)) if word == UTF16LE => {
+                        utf16le.set_true(word);
+                    }
+                    // `#[lain(fuzz_padding)]`
+                    Meta(Word(ref word)) if word == FUZZ_PADDING => {
+                        fuzz_padding.set_true(word);
+                    }
                     Meta(ref meta_item) => {
                         cx.error_spanned_by(
                             meta_item.name(),
@@ -312,6 +552,16 @@ impl Field {
             little_endian: little_endian.get(),
             big_endian: big_endian.get(),
             weight_to: weight_to.get(),
+            prefix: prefix.get(),
+            nul_terminated: nul_terminated.get(),
+            utf16le: utf16le.get(),
+            pad_bits: pad_bits.get(),
+            bit_stream: None, // this gets fixed up later
+            align: align.get(),
+            pad: pad.get(),
+            offset: offset.get(),
+            fuzz_padding: fuzz_padding.get(),
+            layout: None, // this gets fixed up later
             is_last_field: false,
         }
     }
@@ -371,6 +621,71 @@ impl Field {
     pub fn weight_to(&self) -> Option<&WeightTo> {
         self.weight_to.as_ref()
     }
+
+    pub fn prefix(&self) -> Option<&syn::Type> {
+        self.prefix.as_ref()
+    }
+
+    pub fn nul_terminated(&self) -> bool {
+        self.nul_terminated
+    }
+
+    pub fn utf16le(&self) -> bool {
+        self.utf16le
+    }
+
+    pub fn pad_bits(&self) -> Option<usize> {
+        self.pad_bits
+    }
+
+    pub fn bit_stream(&self) -> Option<&BitStream> {
+        self.bit_stream.as_ref()
+    }
+
+    pub fn set_bit_stream(&mut self, bit_stream: BitStream) {
+        self.bit_stream = Some(bit_stream);
+    }
+
+    pub fn align(&self) -> Option<usize> {
+        self.align
+    }
+
+    pub fn pad(&self) -> Option<usize> {
+        self.pad
+    }
+
+    pub fn offset(&self) -> Option<usize> {
+        self.offset
+    }
+
+    /// Whether this field has any of the `align`, `pad`, or `offset` attributes
+    pub fn has_explicit_layout(&self) -> bool {
+        self.align.is_some() || self.pad.is_some() || self.offset.is_some()
+    }
+
+    pub fn layout(&self) -> Option<&Layout> {
+        self.layout.as_ref()
+    }
+
+    pub fn set_layout(&mut self, layout: Layout) {
+        self.layout = Some(layout);
+    }
+
+    /// Whether this field holds the seed for the padding of its container
+    pub fn fuzz_padding(&self) -> bool {
+        self.fuzz_padding
+    }
+
+    pub fn set_padding_seed(&mut self, padding_seed: syn::Member) {
+        if let Some(ref mut layout) = self.layout {
+            layout.padding_seed = Some(padding_seed);
+        }
+    }
+
+    /// Whether this field uses a non-default wire encoding
+    pub fn is_encoded(&self) -> bool {
+        self.prefix.is_some() || self.nul_terminated || self.utf16le
+    }
 }
 
 /// Represents enum variant information
@@ -378,6 +693,7 @@ pub struct Variant {
     weight: Option<u64>,
     ignore: bool,
     ignore_chance: Option<f64>,
+    tag_value: Option<u64>,
 }
 
 impl Variant {
@@ -386,6 +702,7 @@ impl Variant {
         let mut weight = Attr::none(cx, WEIGHT);
         let mut ignore = BoolAttr::none(cx, IGNORE);
         let mut ignore_chance = Attr::none(cx, IGNORE_CHANCE);
+        let mut tag_value = Attr::none(cx, TAG_VALUE);
 
         for meta_items in variant.attrs.iter().filter_map(get_lain_meta_items) {
             for meta_item in meta_items {
@@ -418,6 +735,17 @@ impl Variant {
                             );
                         }
                     }
+                    // `#[lain(tag_value = 0x10)]`
+                    Meta(NameValue(ref m)) if m.ident == TAG_VALUE => {
+                        if let Int(ref i) = m.lit {
+                            tag_value.set(&m.ident, i.value());
+                        } else {
+                            cx.error_spanned_by(
+                                &m.lit,
+                                format!("failed to parse integer expression for `{}`", TAG_VALUE),
+                            );
+                        }
+                    }
                     Meta(ref meta_item) => {
                         cx.error_spanned_by(
                             meta_item.name(),
@@ -435,6 +763,7 @@ impl Variant {
             weight: weight.get(),
             ignore: ignore.get(),
             ignore_chance: ignore_chance.get(),
+            tag_value: tag_value.get(),
         }
     }
 
@@ -449,6 +778,14 @@ impl Variant {
     pub fn ignore_chance(&self) -> Option<f64> {
         self.ignore_chance
     }
+
+    pub fn tag_value(&self) -> Option<u64> {
+        self.tag_value
+    }
+
+    pub fn set_tag_value(&mut self, tag_value: u64) {
+        self.tag_value = Some(tag_value);
+    }
 }
 
 pub fn get_lain_meta_items(attr: &syn::Attribute) -> Option<Vec<syn::NestedMeta>> {
@@ -506,6 +843,19 @@ fn parse_min_max(cx: &Ctxt, attr_name: Symbol, lit: &syn::Lit) -> Result<TokenSt
     }
 }
 
+fn parse_nonzero_usize(cx: &Ctxt, attr_name: Symbol, lit: &syn::Lit) -> Result<usize, ()> {
+    match *lit {
+        Int(ref i) if i.value() != 0 => Ok(i.value() as usize),
+        _ => {
+            cx.error_spanned_by(
+                lit,
+                format!("expected a nonzero integer for `{}`", attr_name),
+            );
+            Err(())
+        }
+    }
+}
+
 fn parse_lit_into_type(cx: &Ctxt, attr_name: Symbol, lit: &syn::Lit) -> Result<syn::Type, ()> {
     let string = get_lit_str(cx, attr_name, attr_name, lit)?;
     parse_lit_str(string).map_err(|_| {
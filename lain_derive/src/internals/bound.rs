@@ -0,0 +1,96 @@
+use proc_macro2::{TokenStream, TokenTree};
+use quote::quote;
+use std::collections::HashSet;
+use syn::parse_quote;
+
+use super::ast::{Container, Data, Field};
+
+/// Returns the container's generics with a `FieldType: Bound` predicate added for every field
+/// whose type mentions one of the container's type parameters. `bound` returns the traits that
+/// the derived code requires of a field, or an empty list if it requires nothing.
+///
+/// Bounding the field types rather than the type parameters themselves lets fields such as
+/// `Vec<P>` or `P::Header` pick up whatever the trait impl for that type requires.
+pub fn with_field_bounds<F>(cont: &Container, bound: F) -> syn::Generics
+where
+    F: Fn(&Field) -> Vec<TokenStream>,
+{
+    let mut generics = cont.generics.clone();
+
+    let type_params: HashSet<String> = generics
+        .type_params()
+        .map(|param| param.ident.to_string())
+        .collect();
+
+    if type_params.is_empty() {
+        return generics;
+    }
+
+    let fields: Vec<&Field> = match cont.data {
+        Data::Enum(ref variants) => variants
+            .iter()
+            .flat_map(|variant| variant.fields.iter())
+            .collect(),
+        Data::Struct(_, ref fields) => fields.iter().collect(),
+    };
+
+    let mut bounded_types = HashSet::new();
+    let mut predicates: Vec<syn::WherePredicate> = vec![];
+
+    for field in fields {
+        let ty = field.ty;
+        let ty_tokens = quote! {#ty};
+
+        if !mentions_type_param(ty_tokens.clone(), &type_params) {
+            continue;
+        }
+
+        let traits = bound(field);
+        if traits.is_empty() {
+            continue;
+        }
+
+        // the same type may appear in several fields with different requirements
+        for bound in traits {
+            if bounded_types.insert((ty_tokens.to_string(), bound.to_string())) {
+                predicates.push(parse_quote!(#ty: #bound));
+            }
+        }
+    }
+
+    generics.make_where_clause().predicates.extend(predicates);
+
+    generics
+}
+
+fn mentions_type_param(tokens: TokenStream, type_params: &HashSet<String>) -> bool {
+    tokens.into_iter().any(|token| match token {
+        TokenTree::Ident(ref ident) => type_params.contains(&ident.to_string()),
+        TokenTree::Group(ref group) => mentions_type_param(group.stream(), type_params),
+        _ => false,
+    })
+}
+
+/// Adds a `Container<..>: Bound` predicate for each of `bounds` when the container has type
+/// parameters. Derived impls need this when their bodies rely on another impl of the container
+/// whose field bounds differ from their own.
+pub fn with_self_bounds(
+    cont: &Container,
+    mut generics: syn::Generics,
+    bounds: &[TokenStream],
+) -> syn::Generics {
+    if generics.type_params().next().is_none() {
+        return generics;
+    }
+
+    let ident = &cont.ident;
+    let (_, ty_generics, _) = cont.generics.split_for_impl();
+    let predicates: Vec<syn::WherePredicate> = bounds
+        .iter()
+        .map(|bound| parse_quote!(#ident #ty_generics: #bound))
+        .collect();
+
+    generics.make_where_clause().predicates.extend(predicates);
+
+    generics
+}
@@ -1,5 +1,6 @@
 pub mod ast;
 pub mod attr;
+pub mod bound;
 mod ctxt;
 mod symbol;
 
@@ -18,6 +18,20 @@ pub const SERIALIZED_SIZE: Symbol = Symbol("serialized_size");
 pub const MIN_SERIALIZED_SIZE: Symbol = Symbol("min_serialized_size");
 pub const WEIGHT: Symbol = Symbol("weight");
 pub const WEIGHT_TO: Symbol = Symbol("weight_to");
+pub const PREFIX: Symbol = Symbol("prefix");
+pub const NUL_TERMINATED: Symbol = Symbol("nul_terminated");
+pub const UTF16LE: Symbol = Symbol("utf16le");
+pub const BIT_ORDER: Symbol = Symbol("bit_order");
+pub const PAD_BITS: Symbol = Symbol("pad_bits");
+pub const ALIGN: Symbol = Symbol("align");
+pub const PAD: Symbol = Symbol("pad");
+pub const OFFSET: Symbol = Symbol("offset");
+pub const REPR_C: Symbol = Symbol("repr_c");
+pub const PACK: Symbol = Symbol("pack");
+pub const FUZZ_PADDING: Symbol = Symbol("fuzz_padding");
+pub const TAG: Symbol = Symbol("tag");
+pub const TAG_ENDIAN: Symbol = Symbol("tag_endian");
+pub const TAG_VALUE: Symbol = Symbol("tag_value");
 
 impl PartialEq<Symbol> for Ident {
     fn eq(&self, word: &Symbol) -> bool {
@@ -31,7 +31,8 @@ pub(crate) struct FuzzerObjectStructField<'a> {
 
 pub(crate) fn is_primitive(ty: &str) -> PrimitiveType {
     match ty {
-        "f32" | "f64" | "u8" | "i8" | "u16" | "i16" | "u32" | "i32" | "u64" | "i64" => {
+        "f32" | "f64" | "u8" | "i8" | "u16" | "i16" | "u32" | "i32" | "u64" | "i64" | "u128"
+        | "i128" => {
             PrimitiveType::Number
         }
         "bool" => PrimitiveType::Bool,
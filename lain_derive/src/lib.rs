@@ -209,24 +209,17 @@ fn variable_size_object_helper(input: &DeriveInput) -> TokenStream {
             };
         }
         Data::Struct(ref data) => {
-            if let Fields::Named(ref fields) = data.fields {
-                if fields.named.len() == 0 {
-                    imp = quote! {false};
-                } else {
-                    let mut tokens = quote! {false};
-
-                    for field in fields.named.iter() {
-                        let ty = &field.ty;
-                        tokens.extend(quote_spanned! { field.span() =>
-                            || <#ty>::is_variable_size()
-                        });
-                    }
-
-                    imp = tokens;
-                }
-            } else {
-                panic!("Need to add support for unnamed struct fields");
+            // named, tuple, and unit structs are all variable size if any of their fields are
+            let mut tokens = quote! {false};
+
+            for field in data.fields.iter() {
+                let ty = &field.ty;
+                tokens.extend(quote_spanned! { field.span() =>
+                    || <#ty>::is_variable_size()
+                });
             }
+
+            imp = tokens;
         }
         _ => panic!("Non-enum/struct data types are not supported"),
     }
@@ -267,30 +260,36 @@ pub fn fuzzer_object(input: proc_macro::TokenStream) -> proc_macro::TokenStream
     base_token_stream.into()
 }
 
-/// Implements `ToPrimitive<u8>` for the given enum.
+/// Implements `ToPrimitive<u8>` and `FromPrimitive` for the given enum.
 #[proc_macro_derive(ToPrimitiveU8)]
 pub fn to_primitive_u8(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     to_primitive_of_type(input, quote! {u8})
 }
 
-/// Implements `ToPrimitive<u16>` for the given enum.
+/// Implements `ToPrimitive<u16>` and `FromPrimitive` for the given enum.
 #[proc_macro_derive(ToPrimitiveU16)]
 pub fn to_primitive_u16(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     to_primitive_of_type(input, quote! {u16})
 }
 
-/// Implements `ToPrimitive<u32>` for the given enum.
+/// Implements `ToPrimitive<u32>` and `FromPrimitive` for the given enum.
 #[proc_macro_derive(ToPrimitiveU32)]
 pub fn to_primitive_u32(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     to_primitive_of_type(input, quote! {u32})
 }
 
-/// Implements `ToPrimitive<u64>` for the given enum.
+/// Implements `ToPrimitive<u64>` and `FromPrimitive` for the given enum.
 #[proc_macro_derive(ToPrimitiveU64)]
 pub fn to_primitive_u64(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     to_primitive_of_type(input, quote! {u64})
 }
 
+/// Implements `ToPrimitive<u128>` and `FromPrimitive` for the given enum.
+#[proc_macro_derive(ToPrimitiveU128)]
+pub fn to_primitive_u128(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    to_primitive_of_type(input, quote! {u128})
+}
+
 fn to_primitive_of_type(
     input: proc_macro::TokenStream,
     ty: proc_macro2::TokenStream,
@@ -301,6 +300,25 @@ fn to_primitive_of_type(
 
     let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
 
+    let variants: Vec<TokenStream> = match input.data {
+        Data::Enum(ref data) => data
+            .variants
+            .iter()
+            .map(|variant| {
+                let variant_ident = &variant.ident;
+                quote! {#name::#variant_ident}
+            })
+            .collect(),
+        _ => vec![],
+    };
+    // quote can't repeat a non-iterator, so the casts are built up front
+    let values: Vec<TokenStream> = variants
+        .iter()
+        .map(|variant| quote! {#variant as #ty})
+        .collect();
+    let values = &values;
+    let variants = &variants;
+
     let expanded = quote! {
         #[allow(clippy)]
         #[allow(unknown_lints)]
@@ -311,6 +329,26 @@ fn to_primitive_of_type(
                 *self as #ty
             }
         }
+
+        #[allow(clippy)]
+        #[allow(unknown_lints)]
+        impl #impl_generics ::lain::traits::FromPrimitive for #name #ty_generics #where_clause {
+            fn primitive_values() -> &'static [#ty] {
+                const VALUES: &[#ty] = &[#(#values,)*];
+
+                VALUES
+            }
+
+            fn from_primitive(value: #ty) -> Option<Self> {
+                #(
+                    if value == #values {
+                        return Some(#variants);
+                    }
+                )*
+
+                None
+            }
+        }
     };
 
     // Uncomment to dump the AST
@@ -4,8 +4,9 @@ use std::str::FromStr;
 use syn::spanned::Spanned;
 
 use crate::dummy;
-use crate::internals::ast::{Container, Data, Field, Style, Variant};
-use crate::internals::{attr, Ctxt, Derive};
+use crate::internals::ast::{primitive_bit_width, Container, Data, Field, Style, Variant};
+use crate::internals::{attr, bound, Ctxt, Derive};
+use crate::serialize::{encoded_field_encoding, encoded_field_prefix};
 
 pub fn expand_mutatable(input: &syn::DeriveInput) -> Result<TokenStream, Vec<syn::Error>> {
     let ctx = Ctxt::new();
@@ -18,7 +19,9 @@ pub fn expand_mutatable(input: &syn::DeriveInput) -> Result<TokenStream, Vec<syn
     ctx.check()?;
 
     let ident = &cont.ident;
-    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let generics = bound::with_field_bounds(&cont, mutatable_bound);
+    let generics = bound::with_self_bounds(&cont, generics, &mutatable_self_bounds(&cont));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
 
     let body = mutatable_body(&cont);
     let lain = cont.attrs.lain_path();
@@ -34,7 +37,7 @@ pub fn expand_mutatable(input: &syn::DeriveInput) -> Result<TokenStream, Vec<syn
             // really use the min/max
             type RangeType = u8;
 
-            fn mutate<R: #lain::rand::Rng>(&mut self, mutator: &mut #lain::mutator::Mutator<R>, parent_constraints: Option<&#lain::types::Constraints<Self::RangeType>>)
+            fn mutate<__R: #lain::rand::Rng>(&mut self, mutator: &mut #lain::mutator::Mutator<__R>, parent_constraints: Option<&#lain::types::Constraints<Self::RangeType>>)
             {
                 _lain::log::trace!("Mutating {}", #ident_str);
 
@@ -65,7 +68,10 @@ pub fn expand_new_fuzzed(input: &syn::DeriveInput) -> Result<TokenStream, Vec<sy
     ctx.check()?;
 
     let ident = &cont.ident;
-    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let generics = bound::with_field_bounds(&cont, new_fuzzed_bound);
+    let generics =
+        bound::with_self_bounds(&cont, generics, &[quote! {_lain::traits::SerializedSize}]);
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
 
     let body = new_fuzzed_body(&cont);
     let lain = cont.attrs.lain_path();
@@ -79,7 +85,7 @@ pub fn expand_new_fuzzed(input: &syn::DeriveInput) -> Result<TokenStream, Vec<sy
 
             // structs always have a RangeType of u8 since they shouldn't
             // really use the min/max
-            fn new_fuzzed<R: #lain::rand::Rng>(mutator: &mut #lain::mutator::Mutator<R>, parent_constraints: Option<&#lain::types::Constraints<Self::RangeType>>) -> Self
+            fn new_fuzzed<__R: #lain::rand::Rng>(mutator: &mut #lain::mutator::Mutator<__R>, parent_constraints: Option<&#lain::types::Constraints<Self::RangeType>>) -> Self
             {
                 #body
             }
@@ -91,6 +97,53 @@ pub fn expand_new_fuzzed(input: &syn::DeriveInput) -> Result<TokenStream, Vec<sy
     Ok(data)
 }
 
+/// The traits that the generated `NewFuzzed` implementation requires of a field's type
+fn new_fuzzed_bound(field: &Field) -> Vec<TokenStream> {
+    let attrs = &field.attrs;
+    let mut bounds = vec![quote! {_lain::traits::SerializedSize}];
+
+    if attrs.ignore() {
+        bounds.push(quote! {Default});
+    } else if attrs.ignore_chance().is_some() {
+        bounds.push(quote! {Default});
+        bounds.push(quote! {_lain::traits::NewFuzzed});
+    } else if attrs.initializer().is_none() {
+        bounds.push(quote! {_lain::traits::NewFuzzed});
+    }
+
+    if attrs.prefix().is_some() {
+        bounds.push(quote! {_lain::encoding::EncodedSequence});
+    }
+
+    bounds
+}
+
+/// The traits that the generated `Mutatable` implementation requires of a field's type
+fn mutatable_bound(field: &Field) -> Vec<TokenStream> {
+    let mut bounds = vec![
+        quote! {_lain::traits::SerializedSize},
+        quote! {_lain::traits::Mutatable},
+    ];
+
+    if field.attrs.prefix().is_some() {
+        bounds.push(quote! {_lain::encoding::EncodedSequence});
+    }
+
+    bounds
+}
+
+/// The traits that the generated `Mutatable` implementation requires of the container itself.
+/// Enums may be regenerated while being mutated.
+fn mutatable_self_bounds(cont: &Container) -> Vec<TokenStream> {
+    let mut bounds = vec![quote! {_lain::traits::SerializedSize}];
+
+    if let Data::Enum(_) = cont.data {
+        bounds.push(quote! {_lain::traits::NewFuzzed<RangeType = u8>});
+    }
+
+    bounds
+}
+
 fn mutatable_body(cont: &Container) -> TokenStream {
     match cont.data {
         Data::Enum(ref variants) if variants[0].style != Style::Unit => {
@@ -154,7 +207,7 @@ fn mutatable_unit_enum(variants: &[Variant], cont_ident: &syn::Ident) -> TokenSt
         use _lain::rand::seq::SliceRandom;
         use _lain::rand::distributions::Distribution;
 
-        static options: [#cont_ident; #variant_count] = [#(#variant_tokens,)*];
+        let options: [Self; #variant_count] = [#(#variant_tokens,)*];
 
         static weights: [u64; #variant_count] = [#(#weights,)*];
 
@@ -239,9 +292,15 @@ fn mutatable_enum_visitor(variants: &[Variant], cont_ident: &syn::Ident) -> Vec<
             //     return None;
             // }
 
+            // unit variants have nothing to mutate and are handled by the wildcard arm
+            if variant.fields.is_empty() {
+                return None;
+            }
+
             let variant_ident = &variant.ident;
             let full_ident = quote! {#cont_ident::#variant_ident};
             let mut field_identifiers = vec![];
+            let mut field_members = vec![];
 
             let field_mutators: Vec<TokenStream> = variant
                 .fields
@@ -250,13 +309,14 @@ fn mutatable_enum_visitor(variants: &[Variant], cont_ident: &syn::Ident) -> Vec<
                     let (value_ident, _field_ident_string, initializer) =
                         field_mutator(field, "__field", true);
                     field_identifiers.push(quote_spanned! { field.member.span() => #value_ident });
+                    field_members.push(&field.member);
 
                     initializer
                 })
                 .collect();
 
             let match_arm = quote! {
-                #full_ident(#(ref mut #field_identifiers,)*) => {
+                #full_ident { #(#field_members: ref mut #field_identifiers,)* } => {
                     #(#field_mutators)*
                 }
             };
@@ -370,7 +430,7 @@ fn new_fuzzed_unit_enum(variants: &[Variant], cont_ident: &syn::Ident) -> TokenS
         use _lain::rand::seq::SliceRandom;
         use _lain::rand::distributions::Distribution;
 
-        static options: [#cont_ident; #variant_count] = [#(#variant_tokens,)*];
+        let options: [Self; #variant_count] = [#(#variant_tokens,)*];
         static ignore_chances: [f64; #variant_count] = [#(#ignore_chances,)*];
         static weights: [u64; #variant_count] = [#(#weights,)*];
 
@@ -381,7 +441,7 @@ fn new_fuzzed_unit_enum(variants: &[Variant], cont_ident: &syn::Ident) -> TokenS
 
         // this shouldn't need to be an option but is because the compiler analysis
         // doesn't think the loop will go at least once
-        let mut option: Option<#cont_ident> = None;
+        let mut option: Option<Self> = None;
 
         // loop a max of 5 times so we don't infinite loop
         for _i in 0..5 {
@@ -400,7 +460,7 @@ fn new_fuzzed_unit_enum(variants: &[Variant], cont_ident: &syn::Ident) -> TokenS
 }
 
 fn new_fuzzed_struct(fields: &[Field], cont_ident: &syn::Ident) -> TokenStream {
-    let initializers = new_fuzzed_struct_visitor(fields, cont_ident);
+    let initializers = new_fuzzed_struct_visitor(fields);
     let prelude = constraints_prelude();
 
     let len = initializers.len();
@@ -422,7 +482,7 @@ fn new_fuzzed_struct(fields: &[Field], cont_ident: &syn::Ident) -> TokenStream {
 
         #prelude
 
-        let mut uninit_struct = std::mem::MaybeUninit::<#cont_ident>::uninit();
+        let mut uninit_struct = std::mem::MaybeUninit::<Self>::uninit();
         let uninit_struct_ptr = uninit_struct.as_mut_ptr();
 
         _lain::log::trace!("Generating a new {} with constraints: {:#X?}", #type_name_string, parent_constraints);
@@ -452,21 +512,18 @@ fn new_fuzzed_unit_struct(cont_ident: &syn::Ident) -> TokenStream {
     }
 }
 
-fn new_fuzzed_struct_visitor(fields: &[Field], cont_ident: &syn::Ident) -> Vec<TokenStream> {
+fn new_fuzzed_struct_visitor(fields: &[Field]) -> Vec<TokenStream> {
     fields
         .iter()
         .map(|field| {
             let (field_ident, _field_ident_string, initializer) = field_initializer(field, "self");
-            let ty = &field.ty;
             let member = &field.member;
 
             quote! {
                 #initializer
 
-                let field_offset = _lain::field_offset::offset_of!(#cont_ident => #member).get_byte_offset() as isize;
-
                 unsafe {
-                    let field_ptr = (uninit_struct_ptr as *mut u8).offset(field_offset) as *mut #ty;
+                    let field_ptr = std::ptr::addr_of_mut!((*uninit_struct_ptr).#member);
 
                     std::ptr::write(field_ptr, #field_ident);
                 }
@@ -489,12 +546,21 @@ fn struct_field_constraints(field: &Field, for_mutation: bool) -> TokenStream {
 
         if let Some(bits) = attrs.bits() {
             // TODO: maybe refactor attributes so that they can retain original span
-            let bitfield_max = syn::LitInt::new(
-                2_u64.pow(bits as u32),
-                syn::IntSuffix::None,
-                Span::call_site(),
-            );
-            max = quote! {Some(#bitfield_max)};
+            let type_bits = primitive_bit_width(field.ty);
+            max = if type_bits == Some(bits) {
+                // the field spans the entire type, so there is no upper bound
+                quote! {None}
+            } else if bits < 64 {
+                let bitfield_max = syn::LitInt::new(
+                    2_u64.pow(bits as u32),
+                    syn::IntSuffix::None,
+                    Span::call_site(),
+                );
+                quote! {Some(#bitfield_max)}
+            } else {
+                let bits = bits as u32;
+                quote! {Some(1 << #bits)}
+            };
             min = quote! {Some(0)};
         } else {
             min = option_to_tokens(attrs.min());
@@ -571,12 +637,26 @@ fn field_initializer(
         }
     };
 
+    let fit_to_prefix = if field.attrs.prefix().is_some() {
+        let prefix = encoded_field_prefix(field);
+        let encoding = encoded_field_encoding(field);
+
+        quote! {
+            let mut #value_ident = #value_ident;
+            _lain::encoding::fit_to_prefix::<#prefix, _>(&mut #value_ident, #encoding);
+        }
+    } else {
+        TokenStream::new()
+    };
+
     let inc_max_size = decrement_max_size(&field, &value_ident);
     let initializer = quote! {
         #default_constraints
 
         #initializer
 
+        #fit_to_prefix
+
         #inc_max_size
     };
 
@@ -669,12 +749,24 @@ fn field_mutator(
         quote! {&mut}
     };
 
+    let fit_to_prefix = if field.attrs.prefix().is_some() {
+        let prefix = encoded_field_prefix(field);
+        let encoding = encoded_field_encoding(field);
+
+        quote! {
+            _lain::encoding::fit_to_prefix::<#prefix, _>(#borrow #value_ident, #encoding);
+        }
+    } else {
+        TokenStream::new()
+    };
+
     let mutator_stmts = quote! {
         let previous_size = #value_ident.serialized_size();
         let mutated = mutator.gen_chance(0.98);
 
         if mutated {
             <#ty>::mutate(#borrow #value_ident, mutator, constraints.as_ref());
+            #fit_to_prefix
         }
 
         if mutator.should_early_bail_mutation() {
@@ -740,6 +832,7 @@ fn new_fuzzed_enum_visitor(
             let full_ident = quote! {#cont_ident::#variant_ident};
             let full_ident_string = full_ident.to_string();
             let mut field_identifiers = vec![];
+            let mut field_members = vec![];
 
             // TODO: Add ignoring inner fields
             let mut field_ignore_chances = vec![];
@@ -752,6 +845,7 @@ fn new_fuzzed_enum_visitor(
                         field_initializer(field, "__field");
 
                     field_identifiers.push(quote_spanned! { field.member.span() => #value_ident });
+                    field_members.push(&field.member);
                     field_ignore_chances.push(variant.attrs.ignore_chance().unwrap_or(1.0));
 
                     initializer
@@ -770,7 +864,7 @@ fn new_fuzzed_enum_visitor(
                     #(#field_initializers)*
 
                     _lain::log::trace!("Initializing {}", #full_ident_string);
-                    let mut value = #full_ident(#(#field_identifiers,)*);
+                    let mut value = #full_ident { #(#field_members: #field_identifiers,)* };
                 }
             };
 
@@ -1,4 +1,4 @@
-use proc_macro2::TokenStream;
+use proc_macro2::{Span, TokenStream};
 use quote::{quote, quote_spanned};
 use std::str::FromStr;
 use syn::export::quote::ToTokens;
@@ -6,7 +6,7 @@ use syn::spanned::Spanned;
 
 use crate::dummy;
 use crate::internals::ast::{is_primitive_type, Container, Data, Field, Style, Variant};
-use crate::internals::{Ctxt, Derive};
+use crate::internals::{attr, bound, Ctxt, Derive};
 
 struct SerializedSizeBodies {
     serialized_size: TokenStream,
@@ -35,7 +35,8 @@ pub fn expand_binary_serialize(input: &syn::DeriveInput) -> Result<TokenStream,
 
     let ident = &cont.ident;
     let ident_as_string = ident.to_string();
-    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let generics = bound::with_field_bounds(&cont, binary_serialize_bound);
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
 
     let serialize_body = binary_serialize_body(&cont);
     let SerializedSizeBodies {
@@ -50,34 +51,76 @@ pub fn expand_binary_serialize(input: &syn::DeriveInput) -> Result<TokenStream,
     );
 
     let lain = cont.attrs.lain_path();
+    let prefix_assertions = prefixed_array_assertions(&cont, &lain);
 
-    let impl_block = quote! {
-        #[allow(clippy)]
-        #[allow(unknown_lints)]
-        #[automatically_derived]
-        impl #impl_generics #lain::traits::BinarySerialize for #ident #ty_generics #where_clause {
-            fn binary_serialize<W: std::io::Write, E: #lain::byteorder::ByteOrder>(&self, buffer: &mut W) -> usize {
-                use #lain::traits::SerializedSize;
-                use #lain::byteorder::{LittleEndian, BigEndian, WriteBytesExt};
+    let serialize_body = quote! {
+        use #lain::traits::SerializedSize;
+        use #lain::byteorder::{LittleEndian, BigEndian, WriteBytesExt};
 
-                let mut bytes_written = 0;
+        let mut bytes_written = 0;
 
-                #serialize_body
+        #serialize_body
 
-                if bytes_written < self.serialized_size() {
-                    let padding_bytes = std::cmp::max(self.serialized_size(), Self::min_nonzero_elements_size()) - bytes_written;
-                    if padding_bytes != 0 {
-                        let null = 0x0u8;
-                        for _i in 0..padding_bytes {
-                            bytes_written += null.binary_serialize::<_, E>(buffer);
-                        }
+        if bytes_written < self.serialized_size() {
+            let padding_bytes = std::cmp::max(self.serialized_size(), Self::min_nonzero_elements_size()) - bytes_written;
+            if padding_bytes != 0 {
+                let null = 0x0u8;
+                for _i in 0..padding_bytes {
+                    bytes_written += null.binary_serialize::<_, __E>(buffer);
+                }
+            }
+        }
+
+        bytes_written
+    };
+
+    // tagged enums are serialized through `TaggedEnum` so that the tag may be overridden
+    let (serialize_body, tagged_enum_impl) = match enum_tag(&cont) {
+        Some(EnumTag { ty, ref values, .. }) => {
+            let tagged_enum_impl = quote! {
+                #[allow(clippy)]
+                #[allow(unknown_lints)]
+                #[automatically_derived]
+                impl #impl_generics #lain::traits::TaggedEnum for #ident #ty_generics #where_clause {
+                    fn tags() -> &'static [u64] {
+                        &[#(#values),*]
+                    }
+
+                    fn arbitrary_tag<__R: #lain::rand::Rng>(mutator: &mut #lain::mutator::Mutator<__R>) -> u64 {
+                        <#ty as #lain::traits::NewFuzzed>::new_fuzzed(mutator, None) as u64
+                    }
+
+                    fn binary_serialize_with_tag<__W: std::io::Write, __E: #lain::byteorder::ByteOrder>(&self, tag: Option<u64>, buffer: &mut __W) -> usize {
+                        use #lain::traits::BinarySerialize;
+
+                        #serialize_body
                     }
                 }
+            };
+
+            let serialize_body = quote! {
+                #lain::traits::TaggedEnum::binary_serialize_with_tag::<__W, __E>(self, None, buffer)
+            };
+
+            (serialize_body, tagged_enum_impl)
+        }
+        None => (serialize_body, TokenStream::new()),
+    };
+
+    let impl_block = quote! {
+        #prefix_assertions
 
-                bytes_written
+        #[allow(clippy)]
+        #[allow(unknown_lints)]
+        #[automatically_derived]
+        impl #impl_generics #lain::traits::BinarySerialize for #ident #ty_generics #where_clause {
+            fn binary_serialize<__W: std::io::Write, __E: #lain::byteorder::ByteOrder>(&self, buffer: &mut __W) -> usize {
+                #serialize_body
             }
         }
 
+        #tagged_enum_impl
+
         // TODO: Split this into its own derive
         #[allow(clippy)]
         #[allow(unknown_lints)]
@@ -121,10 +164,28 @@ pub fn expand_binary_serialize(input: &syn::DeriveInput) -> Result<TokenStream,
     Ok(data)
 }
 
+/// The traits that the generated `BinarySerialize` and `SerializedSize` implementations require of
+/// a field's type
+fn binary_serialize_bound(field: &Field) -> Vec<TokenStream> {
+    if field.attrs.bits().is_some() {
+        // bitfields are always primitives
+        vec![]
+    } else if field.attrs.is_encoded() {
+        vec![quote! {_lain::encoding::EncodedSequence}]
+    } else {
+        vec![
+            quote! {_lain::traits::BinarySerialize},
+            quote! {_lain::traits::SerializedSize},
+        ]
+    }
+}
+
 fn binary_serialize_body(cont: &Container) -> TokenStream {
     match cont.data {
-        Data::Enum(ref variants) if variants[0].style != Style::Unit => {
-            binary_serialize_enum(variants, &cont.ident)
+        Data::Enum(ref variants)
+            if variants[0].style != Style::Unit || cont.attrs.tag().is_some() =>
+        {
+            binary_serialize_enum(variants, &cont.ident, enum_tag(cont).as_ref())
         }
         Data::Enum(ref _variants) => binary_serialize_unit_enum(&cont.ident),
         Data::Struct(Style::Struct, ref fields) | Data::Struct(Style::Tuple, ref fields) => {
@@ -151,8 +212,16 @@ fn serialized_size_body(
     }
 
     match cont.data {
-        Data::Enum(ref variants) if variants[0].style != Style::Unit => {
-            serialized_size_enum(variants, &cont.ident, size, min_size)
+        Data::Enum(ref variants)
+            if variants[0].style != Style::Unit || cont.attrs.tag().is_some() =>
+        {
+            serialized_size_enum(
+                variants,
+                &cont.ident,
+                enum_tag(cont).as_ref(),
+                size,
+                min_size,
+            )
         }
         Data::Enum(ref _variants) => serialized_size_unit_enum(&cont.ident),
         Data::Struct(Style::Struct, ref fields) | Data::Struct(Style::Tuple, ref fields) => {
@@ -170,11 +239,66 @@ fn serialized_size_body(
     }
 }
 
-fn binary_serialize_enum(variants: &[Variant], cont_ident: &syn::Ident) -> TokenStream {
-    let match_arms = binary_serialize_enum_visitor(variants, cont_ident);
+/// The tag written before the payload of each variant of a `#[lain(tag = "...")]` enum
+struct EnumTag<'a> {
+    ty: &'a syn::Type,
+    endian: TokenStream,
+    /// Every variant's tag value, in declaration order
+    values: Vec<syn::LitInt>,
+}
+
+fn enum_tag<'a>(cont: &'a Container) -> Option<EnumTag<'a>> {
+    let ty = cont.attrs.tag()?;
+
+    let endian = match cont.attrs.tag_endian() {
+        Some(endian) => quote! {#endian},
+        None => quote! {__E},
+    };
+
+    let values = match cont.data {
+        Data::Enum(ref variants) => variants
+            .iter()
+            .map(|variant| {
+                syn::LitInt::new(
+                    variant.attrs.tag_value().unwrap(),
+                    syn::IntSuffix::None,
+                    Span::call_site(),
+                )
+            })
+            .collect(),
+        Data::Struct(..) => return None,
+    };
+
+    Some(EnumTag { ty, endian, values })
+}
+
+/// Statement serializing the tag of the variant at `variant_idx`, or the `tag` override passed to
+/// `TaggedEnum::binary_serialize_with_tag`
+fn serialize_enum_tag(tag: &EnumTag, variant_idx: usize) -> TokenStream {
+    let EnumTag {
+        ty,
+        ref endian,
+        ref values,
+    } = *tag;
+    let value = &values[variant_idx];
+
+    quote! {
+        bytes_written += <#ty>::binary_serialize::<_, #endian>(
+            &tag.map_or(#value, |tag| tag as #ty),
+            buffer,
+        );
+    }
+}
+
+fn binary_serialize_enum(
+    variants: &[Variant],
+    cont_ident: &syn::Ident,
+    tag: Option<&EnumTag>,
+) -> TokenStream {
+    let match_arms = binary_serialize_enum_visitor(variants, cont_ident, tag);
 
     quote! {
-        let mut bitfield: u64 = 0;
+        let mut bitfield: u128 = 0;
 
         match *self {
             #(#match_arms)*
@@ -184,17 +308,20 @@ fn binary_serialize_enum(variants: &[Variant], cont_ident: &syn::Ident) -> Token
 
 fn binary_serialize_unit_enum(cont_ident: &syn::Ident) -> TokenStream {
     quote! {
-        bytes_written += <<#cont_ident as _lain::traits::ToPrimitive>::Output>::binary_serialize::<_, E>(&self.to_primitive(), buffer);
+        bytes_written += <<#cont_ident as _lain::traits::ToPrimitive>::Output>::binary_serialize::<_, __E>(&self.to_primitive(), buffer);
     }
 }
 
 fn binary_serialize_struct(fields: &[Field]) -> TokenStream {
     let serializers = binary_serialize_struct_visitor(fields);
+    let trailing_padding = serialize_trailing_padding(fields, "self.");
 
     quote! {
-        let mut bitfield: u64 = 0;
+        let mut bitfield: u128 = 0;
 
         #(#serializers)*
+
+        #trailing_padding
     }
 }
 
@@ -235,11 +362,51 @@ fn field_serializer(
         quote! {_lain::byteorder::LittleEndian}
     } else {
         // inherit
-        quote! {E}
+        quote! {__E}
     };
 
-    let serialize_stmts = if let Some(bits) = field.attrs.bits() {
-        let bit_mask = 2_u64.pow(bits as u32) - 1;
+    let serialize_stmts = if let Some(stream) = field.attrs.bit_stream() {
+        let bits = field.attrs.bits().unwrap();
+        let order = stream.order;
+
+        let bitfield_value = if field.attrs.bitfield_type().is_some() {
+            quote_spanned! {field.ty.span() => _lain::traits::ToPrimitive::to_primitive(#borrow#value_ident) as u128}
+        } else {
+            quote_spanned! {field.original.span() => *#borrow#value_ident as u128}
+        };
+
+        let mut stmts = if stream.is_start {
+            quote! { let mut bit_writer = _lain::bitstream::BitWriter::new(#order); }
+        } else {
+            TokenStream::new()
+        };
+
+        if let Some(pad_bits) = field.attrs.pad_bits() {
+            stmts.extend(quote! { bit_writer.pad(#pad_bits); });
+        }
+
+        stmts.extend(quote_spanned! { field.ty.span() =>
+            _lain::dump::record_bit_stream_field(#field_ident_string, bit_writer.bit_len(), #bits, #bitfield_value);
+        });
+
+        stmts.extend(
+            quote_spanned! { field.ty.span() => bit_writer.write_bits(#bitfield_value, #bits); },
+        );
+
+        if stream.total_bits.is_some() {
+            stmts.extend(quote! { bytes_written += bit_writer.finish(buffer); });
+        }
+
+        stmts
+    } else if let Some(bits) = field.attrs.bits() {
+        let bit_mask = if bits == 128 {
+            u128::MAX
+        } else {
+            (1_u128 << bits) - 1
+        };
+        // this version of quote can't emit u128 literals directly. the literal is left unsuffixed
+        // so that its type is inferred as the bitfield type
+        let bit_mask = TokenStream::from_str(&bit_mask.to_string()).unwrap();
         let bit_shift = field.attrs.bit_shift().unwrap();
         let is_last_field = field.attrs.is_last_field();
 
@@ -253,6 +420,8 @@ fn field_serializer(
             32
         } else if is_primitive_type(&bitfield_type, "u64") {
             64
+        } else if is_primitive_type(bitfield_type, "u128") {
+            128
         } else {
             panic!("got to field_serialize with an unsupported bitfield type `{}`. ensure that checks in ast code are correct", bitfield_type.into_token_stream());
         };
@@ -263,47 +432,283 @@ fn field_serializer(
             quote_spanned! {field.original.span() => #value_ident}
         };
 
-        let mut bitfield_setter = quote_spanned! { field.ty.span() =>
-            bitfield |= ((#bitfield_value as #bitfield_type & #bit_mask as #bitfield_type) << #bit_shift) as u64;
+        let masked_value = quote_spanned! { field.ty.span() =>
+            (#bitfield_value as #bitfield_type & #bit_mask) << #bit_shift
+        };
+
+        let mut bitfield_setter = if type_total_bits == 128 {
+            quote_spanned! { field.ty.span() => bitfield |= #masked_value; }
+        } else {
+            quote_spanned! { field.ty.span() => bitfield |= u128::from(#masked_value); }
+        };
+
+        let type_size = type_total_bits / 8;
+        let recorded_value = if type_total_bits == 128 {
+            quote_spanned! { field.ty.span() => #bitfield_value as #bitfield_type & #bit_mask }
+        } else {
+            quote_spanned! { field.ty.span() => u128::from(#bitfield_value as #bitfield_type & #bit_mask) }
         };
+        bitfield_setter.extend(quote_spanned! { field.ty.span() =>
+            _lain::dump::record_bitfield(#field_ident_string, #type_size, #bit_shift, #bits, #recorded_value);
+        });
 
         if bits + bit_shift == type_total_bits || is_last_field {
             bitfield_setter.extend(quote_spanned!{field.ty.span() => bytes_written += <#bitfield_type>::binary_serialize::<_, #endian>(&(bitfield as #bitfield_type), buffer);});
         }
 
         bitfield_setter
+    } else if field.attrs.is_encoded() {
+        let prefix = encoded_field_prefix(field);
+        let encoding = encoded_field_encoding(field);
+
+        quote_spanned! { field.original.span() =>
+            _lain::dump::enter_field(#field_ident_string);
+            bytes_written += _lain::encoding::serialize_encoded::<#prefix, _, _, #endian>(#borrow#value_ident, #encoding, buffer);
+            _lain::dump::exit_field();
+        }
     } else {
         if let syn::Type::Array(ref _a) = ty {
             // TODO: Change this once const generics are stabilized
             quote_spanned! { field.original.span() =>
+                _lain::dump::enter_field(#field_ident_string);
                 bytes_written += #value_ident.binary_serialize::<_, #endian>(buffer);
+                _lain::dump::exit_field();
             }
         } else {
             quote_spanned! { field.original.span() =>
+                _lain::dump::enter_field(#field_ident_string);
                 bytes_written += <#ty>::binary_serialize::<_, #endian>(#borrow#value_ident, buffer);
+                _lain::dump::exit_field();
+            }
+        }
+    };
+
+    let serialize_stmts = match field_padding(field, quote! {bytes_written}) {
+        Some(padding) => {
+            let padding_seed = padding_seed(field.attrs.layout().unwrap(), name_prefix);
+            let field_id = padding_id(field, &field_ident_string);
+
+            quote! {
+                bytes_written += _lain::layout::write_padding(#padding, #padding_seed, #field_id, buffer);
+                #serialize_stmts
             }
         }
+        None => serialize_stmts,
     };
 
     (value_ident, field_ident_string, serialize_stmts)
 }
 
+/// The alignment a field is moved to when it has a layout
+fn field_alignment(field: &Field) -> TokenStream {
+    let ty = field.ty;
+    let layout = field.attrs.layout().unwrap();
+
+    match field.attrs.align() {
+        Some(align) => quote! {#align},
+        None if layout.repr_c => match layout.pack {
+            Some(pack) => quote! {std::cmp::min(std::mem::align_of::<#ty>(), #pack)},
+            None => quote! {std::mem::align_of::<#ty>()},
+        },
+        None => quote! {1},
+    }
+}
+
+/// Expression for the number of padding bytes inserted before a field, given an expression for
+/// the offset the field would otherwise start at
+fn field_padding(field: &Field, offset: TokenStream) -> Option<TokenStream> {
+    field.attrs.layout()?;
+
+    let align = field_alignment(field);
+    let pad = field.attrs.pad().unwrap_or(0);
+    let absolute_offset = match field.attrs.offset() {
+        Some(absolute_offset) => quote! {Some(#absolute_offset)},
+        None => quote! {None},
+    };
+
+    Some(quote! {
+        _lain::layout::padding_before(#offset, #align, #pad, #absolute_offset)
+    })
+}
+
+/// Expression for the padding after the last field of a `repr_c` container, which rounds its size
+/// up to a multiple of its largest field alignment
+fn trailing_padding(fields: &[Field], offset: TokenStream) -> Option<TokenStream> {
+    let alignments: Vec<TokenStream> = fields
+        .iter()
+        .filter(|field| field.attrs.layout().is_some_and(|layout| layout.repr_c))
+        .map(field_alignment)
+        .collect();
+
+    if alignments.is_empty() {
+        return None;
+    }
+
+    Some(quote! {
+        _lain::layout::padding_before(#offset, 1usize #(.max(#alignments))*, 0, None)
+    })
+}
+
+/// Expression for the seed of the padding bytes before a field with the given layout
+fn padding_seed(layout: &attr::Layout, name_prefix: &str) -> TokenStream {
+    match layout.padding_seed {
+        Some(ref member) => {
+            let seed_string = match *member {
+                syn::Member::Named(ref ident) => ident.to_string(),
+                syn::Member::Unnamed(ref idx) => idx.index.to_string(),
+            };
+            let seed_ident =
+                TokenStream::from_str(&format!("{}{}", name_prefix, seed_string)).unwrap();
+
+            quote! {#seed_ident.seed()}
+        }
+        None => quote! {None},
+    }
+}
+
+fn serialize_trailing_padding(fields: &[Field], name_prefix: &str) -> TokenStream {
+    match trailing_padding(fields, quote! {bytes_written}) {
+        Some(padding) => {
+            let padding_seed = fields
+                .iter()
+                .find_map(|field| field.attrs.layout())
+                .map(|layout| padding_seed(layout, name_prefix))
+                .unwrap();
+
+            quote! {
+                bytes_written += _lain::layout::write_padding(#padding, #padding_seed, 0, buffer);
+            }
+        }
+        None => TokenStream::new(),
+    }
+}
+
+/// Sums the field sizes of a struct or enum variant. If any field has padding inserted before it
+/// the sizes are added up one at a time, since padding depends on the offset of the field.
+fn sum_field_sizes(fields: &[Field], sizes: &[TokenStream], start: TokenStream) -> TokenStream {
+    if fields.iter().all(|field| field.attrs.layout().is_none()) {
+        return quote! {#start #(+#sizes)*};
+    }
+
+    let steps = fields.iter().zip(sizes).map(|(field, field_size)| {
+        let padding = field_padding(field, quote! {size}).unwrap_or(quote! {0});
+        quote! {
+            size += #padding;
+            size += #field_size;
+        }
+    });
+
+    let trailing =
+        trailing_padding(fields, quote! {size}).map(|padding| quote! {size += #padding;});
+
+    quote! {
+        {
+            let mut size: usize = #start;
+            #(#steps)*
+            #trailing
+            size
+        }
+    }
+}
+
+/// The `LengthPrefix` type used for a field with a custom wire encoding
+/// Arrays can't be truncated to fit their length prefix, so arrays which are too long for their
+/// prefix are rejected at compile time instead of panicking every time they're serialized
+fn prefixed_array_assertions(cont: &Container, lain: &syn::Path) -> TokenStream {
+    let fields: Vec<&Field> = match cont.data {
+        Data::Enum(ref variants) => variants
+            .iter()
+            .flat_map(|variant| variant.fields.iter())
+            .collect(),
+        Data::Struct(_, ref fields) => fields.iter().collect(),
+    };
+
+    fields
+        .into_iter()
+        .filter_map(|field| {
+            let prefix = field.attrs.prefix()?;
+            let len = match *field.ty {
+                syn::Type::Array(ref array) => &array.len,
+                _ => return None,
+            };
+
+            let name = match field.member {
+                syn::Member::Named(ref ident) => ident.to_string(),
+                syn::Member::Unnamed(ref idx) => idx.index.to_string(),
+            };
+            let message = format!(
+                "array field `{}` is too long for its `{}` length prefix",
+                name,
+                prefix.into_token_stream()
+            );
+
+            Some(quote_spanned! { field.ty.span() =>
+                const _: () = assert!(
+                    (#len) <= <#prefix as #lain::encoding::LengthPrefix>::MAX_LEN,
+                    #message
+                );
+            })
+        })
+        .collect()
+}
+
+pub(crate) fn encoded_field_prefix(field: &Field) -> TokenStream {
+    match field.attrs.prefix() {
+        Some(prefix) => quote! {#prefix},
+        None => quote! {()},
+    }
+}
+
+pub(crate) fn encoded_field_encoding(field: &Field) -> TokenStream {
+    let utf16le = field.attrs.utf16le();
+    let nul_terminated = field.attrs.nul_terminated();
+
+    quote! {
+        _lain::encoding::Encoding {
+            utf16le: #utf16le,
+            nul_terminated: #nul_terminated,
+        }
+    }
+}
+
+/// Stable identifier for the padding before a field, so that fuzzed padding differs between fields.
+/// This is an FNV-1a hash of the field name and type.
+fn padding_id(field: &Field, field_ident_string: &str) -> u64 {
+    let ty = field.ty.into_token_stream().to_string();
+
+    fnv_hash(field_ident_string.bytes().chain(ty.bytes()))
+}
+
+/// FNV-1a hash, used for identifiers which need to be stable across builds
+fn fnv_hash<I: Iterator<Item = u8>>(bytes: I) -> u64 {
+    bytes.fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
 fn binary_serialize_enum_visitor(
     variants: &[Variant],
     cont_ident: &syn::Ident,
+    tag: Option<&EnumTag>,
 ) -> Vec<TokenStream> {
     let match_arms = variants
         .iter()
-        .map(|variant| {
+        .enumerate()
+        .map(|(variant_idx, variant)| {
             let variant_ident = &variant.ident;
             let full_ident = quote! {#cont_ident::#variant_ident};
             let mut field_identifiers = vec![];
+            let mut field_members = vec![];
+
+            let serialize_tag = tag
+                .map(|tag| serialize_enum_tag(tag, variant_idx))
+                .unwrap_or_default();
 
             // TODO BUGBUG: need to decouple BinarySerialize from NewFuzzed to not hit this case with mixed unit/struct enum
             if variant.fields.is_empty() {
                 return quote_spanned! { variant.original.span() =>
                     #full_ident => {
-                        // do nothing for #full_ident
+                        #serialize_tag
                     }
                 };
             }
@@ -315,14 +720,26 @@ fn binary_serialize_enum_visitor(
                     let (value_ident, _field_ident_string, initializer) =
                         field_serializer(field, "__field", true);
                     field_identifiers.push(quote_spanned! { field.member.span() => #value_ident });
+                    field_members.push(&field.member);
 
                     initializer
                 })
                 .collect();
 
+            let trailing_padding = serialize_trailing_padding(&variant.fields, "__field");
+            let variant_ident_string = variant_ident.to_string();
+
             let match_arm = quote! {
-                #full_ident(#(ref #field_identifiers,)*) => {
+                #full_ident { #(#field_members: ref #field_identifiers,)* } => {
+                    #serialize_tag
+
+                    _lain::dump::enter_field(#variant_ident_string);
+
                     #(#field_serializers)*
+
+                    #trailing_padding
+
+                    _lain::dump::exit_field();
                 }
             };
 
@@ -336,27 +753,32 @@ fn binary_serialize_enum_visitor(
 fn serialized_size_enum(
     variants: &[Variant],
     cont_ident: &syn::Ident,
+    tag: Option<&EnumTag>,
     size: Option<usize>,
     min_size: Option<usize>,
 ) -> SerializedSizeBodies {
     let match_arms = serialized_size_enum_visitor(
         variants,
         cont_ident,
+        tag,
         SerializedSizeVisitorType::SerializedSize,
     );
     let nonzero_variants = serialized_size_enum_visitor(
         variants,
         cont_ident,
+        tag,
         SerializedSizeVisitorType::MinNonzeroElements,
     );
     let max_obj = serialized_size_enum_visitor(
         variants,
         cont_ident,
+        tag,
         SerializedSizeVisitorType::MaxDefaultObjectSize,
     );
     let min_variant = serialized_size_enum_visitor(
         variants,
         cont_ident,
+        tag,
         SerializedSizeVisitorType::MinEnumVariantSize,
     );
 
@@ -416,9 +838,9 @@ fn serialized_size_struct(fields: &[Field]) -> SerializedSizeBodies {
         serialized_size_struct_visitor(fields, SerializedSizeVisitorType::MaxDefaultObjectSize);
 
     SerializedSizeBodies {
-        serialized_size: quote! {0 #(+#serialized_size)* },
-        min_nonzero_elements_size: quote! { 0 #(+#min_nonzero)* },
-        max_default_object_size: quote! {0 #(+#max_default)*},
+        serialized_size: sum_field_sizes(fields, &serialized_size, quote! {0}),
+        min_nonzero_elements_size: sum_field_sizes(fields, &min_nonzero, quote! {0}),
+        max_default_object_size: sum_field_sizes(fields, &max_default, quote! {0}),
         min_enum_variant_size: quote! {Self::min_nonzero_elements_size()},
     }
 }
@@ -458,7 +880,16 @@ fn field_serialized_size(
         quote! {&}
     };
 
-    let serialized_size_stmts = if let Some(bits) = field.attrs.bits() {
+    let serialized_size_stmts = if let Some(stream) = field.attrs.bit_stream() {
+        // the whole run is accounted for on its final field
+        match stream.total_bits {
+            Some(total_bits) => {
+                let total_bytes = total_bits.div_ceil(8);
+                quote! {#total_bytes}
+            }
+            None => quote! {0 /* bit stream */},
+        }
+    } else if let Some(bits) = field.attrs.bits() {
         let bit_shift = field.attrs.bit_shift().unwrap();
         let bitfield_type = field.attrs.bitfield_type().unwrap_or(&field.ty);
         let is_last_field = field.attrs.is_last_field();
@@ -471,6 +902,8 @@ fn field_serialized_size(
             32
         } else if is_primitive_type(&bitfield_type, "u64") {
             64
+        } else if is_primitive_type(bitfield_type, "u128") {
+            128
         } else {
             panic!("got to field_serialize_size with an unsupported bitfield type `{}`. ensure that checks in ast code are correct", bitfield_type.into_token_stream());
         };
@@ -498,6 +931,12 @@ fn field_serialized_size(
         } else {
             quote! {0 /* bitfield */}
         }
+    } else if field.attrs.is_encoded() && visitor_type == SerializedSizeVisitorType::SerializedSize
+    {
+        let prefix = encoded_field_prefix(field);
+        let encoding = encoded_field_encoding(field);
+
+        quote_spanned! { field.original.span() => _lain::encoding::encoded_serialized_size::<#prefix, _>(#borrow#value_ident, #encoding)}
     } else {
         match visitor_type {
             SerializedSizeVisitorType::SerializedSize => {
@@ -539,12 +978,27 @@ fn field_serialized_size(
         }
     };
 
+    let serialized_size_stmts =
+        if field.attrs.is_encoded() && visitor_type != SerializedSizeVisitorType::SerializedSize {
+            let prefix = encoded_field_prefix(field);
+            let encoding = encoded_field_encoding(field);
+
+            quote_spanned! { field.original.span() =>
+                (<#prefix as _lain::encoding::LengthPrefix>::SIZE
+                    + <#ty as _lain::encoding::EncodedSequence>::terminator_size(#encoding)
+                    + #serialized_size_stmts)
+            }
+        } else {
+            serialized_size_stmts
+        };
+
     (value_ident, field_ident_string, serialized_size_stmts)
 }
 
 fn serialized_size_enum_visitor(
     variants: &[Variant],
     cont_ident: &syn::Ident,
+    tag: Option<&EnumTag>,
     visitor_type: SerializedSizeVisitorType,
 ) -> Vec<TokenStream> {
     let match_arms = variants
@@ -553,6 +1007,14 @@ fn serialized_size_enum_visitor(
             let variant_ident = &variant.ident;
             let full_ident = quote! {#cont_ident::#variant_ident};
 
+            let tag_size = match tag {
+                Some(tag) => {
+                    let ty = tag.ty;
+                    quote! {std::mem::size_of::<#ty>()}
+                }
+                None => quote! {0},
+            };
+
             // TODO BUGBUG: need to decouple BinarySerialize from NewFuzzed to not hit this case with mixed unit/struct enum
             if variant.fields.is_empty() {
                 return match visitor_type {
@@ -560,17 +1022,18 @@ fn serialized_size_enum_visitor(
                     | SerializedSizeVisitorType::MinEnumVariantSize => {
                         quote_spanned! { variant.original.span() =>
                             #full_ident => {
-                                0
+                                #tag_size
                             }
                         }
                     }
                     _ => quote_spanned! { variant.original.span() =>
-                        0
+                        #tag_size
                     },
                 };
             }
 
             let mut field_identifiers = vec![];
+            let mut field_members = vec![];
 
             let field_sizes: Vec<TokenStream> = variant
                 .fields
@@ -579,22 +1042,25 @@ fn serialized_size_enum_visitor(
                     let (value_ident, _field_ident_string, field_size) =
                         field_serialized_size(field, "__field", true, visitor_type);
                     field_identifiers.push(quote_spanned! { field.member.span() => #value_ident });
+                    field_members.push(&field.member);
 
                     field_size
                 })
                 .collect();
 
+            let total_size = sum_field_sizes(&variant.fields, &field_sizes, tag_size);
+
             match visitor_type {
                 SerializedSizeVisitorType::SerializedSize
                 | SerializedSizeVisitorType::MinEnumVariantSize => {
                     quote_spanned! { variant.original.span() =>
-                        #full_ident(#(ref #field_identifiers,)*) => {
-                            0 #(+#field_sizes)*
+                        #full_ident { #(#field_members: ref #field_identifiers,)* } => {
+                            #total_size
                         }
                     }
                 }
                 _ => quote_spanned! { variant.original.span() =>
-                    0 #(+#field_sizes)*
+                    #total_size
                 },
             }
         })
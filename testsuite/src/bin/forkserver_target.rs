@@ -0,0 +1,25 @@
+//! A stand-in target for exercising lain's fork server executor. The input is read from the file
+//! given as the first argument, or from stdin if there is none.
+use std::io::Read;
+
+fn main() {
+    lain::forkserver::start_forkserver();
+
+    let mut input = Vec::new();
+    match std::env::args().nth(1) {
+        Some(path) => input = std::fs::read(path).unwrap(),
+        None => {
+            std::io::stdin().read_to_end(&mut input).unwrap();
+        }
+    }
+
+    if input.starts_with(b"crash") {
+        std::process::abort();
+    } else if input.starts_with(b"hang") {
+        loop {
+            std::thread::sleep(std::time::Duration::from_secs(1));
+        }
+    } else if input.starts_with(b"exit") {
+        std::process::exit(2);
+    }
+}
@@ -555,43 +555,2462 @@ mod test {
     }
 
     #[test]
-    fn test_post_mutation_called() {
-        #[derive(NewFuzzed, Clone, BinarySerialize)]
+    fn driver_builder_runs_stateful_harnesses() {
+        use lain::rand::rngs::StdRng;
+        use std::rc::Rc;
+        use std::sync::{Arc, RwLock};
+
+        #[derive(Default)]
+        struct GlobalContext {
+            thread_iterations: Vec<(usize, usize)>,
+        }
+
+        // captured by the factory and moved into each harness -- no `Default` required
+        let iterations_per_thread = 10;
+
+        let global_context: Arc<RwLock<GlobalContext>> = Default::default();
+        let driver = lain::driver::FuzzerDriver::<GlobalContext>::builder(2)
+            .global_context(global_context.clone())
+            .start_with_factory(move |thread_index| {
+                // harness state doesn't need to be `Send` since it never leaves the thread
+                let iterations = Rc::new(std::cell::Cell::new(0usize));
+
+                move |mutator: &mut Mutator<StdRng>,
+                      global_context: Option<Arc<RwLock<GlobalContext>>>| {
+                    let _value = u32::new_fuzzed(mutator, None);
+
+                    iterations.set(iterations.get() + 1);
+                    if iterations.get() == iterations_per_thread {
+                        global_context
+                            .unwrap()
+                            .write()
+                            .unwrap()
+                            .thread_iterations
+                            .push((thread_index, iterations.get()));
+                    }
+
+                    Ok(())
+                }
+            });
+
+        let one_milli = std::time::Duration::from_millis(1);
+        while global_context.read().unwrap().thread_iterations.len() < 2 {
+            std::thread::sleep(one_milli);
+        }
+
+        driver.signal_exit();
+        driver.join_threads();
+
+        let mut thread_iterations = global_context.read().unwrap().thread_iterations.clone();
+        thread_iterations.sort();
+        assert_eq!(
+            thread_iterations,
+            vec![(0, iterations_per_thread), (1, iterations_per_thread)]
+        );
+    }
+
+    #[test]
+    fn async_driver_can_reproduce_mutations() {
+        use lain::rand::rngs::StdRng;
+        use lain::rand::Rng;
+        use std::sync::{Arc, RwLock};
+
+        #[derive(Debug, Default, NewFuzzed, Mutatable, Clone, PartialEq, BinarySerialize)]
         struct S {
-            #[lain(ignore)]
-            pub post_mutation_called: bool,
+            value: u32,
         }
 
-        impl Fixup for S {
-            fn fixup<R: lain::rand::Rng>(&mut self, _mutator: &mut Mutator<R>) {
-                println!("fixup called");
-                self.post_mutation_called = true;
+        #[derive(Default)]
+        struct LocalContext {}
+
+        #[derive(Default, Debug)]
+        struct GlobalContext {
+            mutated_data: Vec<S>,
+        }
+
+        async fn fuzzer_routine(
+            mut mutator: Mutator<StdRng>,
+            ctx: LocalContext,
+            global_ctx: Option<Arc<RwLock<GlobalContext>>>,
+        ) -> (Mutator<StdRng>, LocalContext, Result<(), ()>) {
+            let data = S::new_fuzzed(&mut mutator, None);
+
+            tokio::task::yield_now().await;
+
+            global_ctx.unwrap().write().unwrap().mutated_data.push(data);
+
+            (mutator, ctx, Ok(()))
+        }
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+
+        let seed: u64 = lain::rand::thread_rng().gen();
+
+        // Do the first run. There's no way to signal an exit from outside of the runtime here, so
+        // use reproduce mode starting from 0 to get a fixed number of iterations
+        let mut driver = lain::driver::FuzzerDriver::<GlobalContext>::new(1);
+        let global_context: Arc<RwLock<GlobalContext>> = Default::default();
+        driver.set_global_context(global_context.clone());
+        driver.set_to_reproduce_mode(0, 20);
+        driver.set_seed(seed);
+
+        runtime.block_on(lain::driver::start_fuzzer_async(
+            Arc::new(driver),
+            fuzzer_routine,
+        ));
+
+        assert_eq!(global_context.read().unwrap().mutated_data.len(), 20);
+        let mutated_data = global_context.read().unwrap().mutated_data[10..15].to_vec();
+
+        // Recreate the driver for a reproduction run
+        let mut driver = lain::driver::FuzzerDriver::<GlobalContext>::new(1);
+        let global_context: Arc<RwLock<GlobalContext>> = Default::default();
+        driver.set_global_context(global_context.clone());
+        driver.set_to_reproduce_mode(10, 15);
+        driver.set_seed(seed);
+
+        runtime.block_on(lain::driver::start_fuzzer_async(
+            Arc::new(driver),
+            fuzzer_routine,
+        ));
+
+        assert_eq!(global_context.read().unwrap().mutated_data, mutated_data);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn executor_delivers_input() {
+        use lain::executor::{CommandExecutor, ExitKind, InputDelivery};
+
+        let input = b"hello lain";
+
+        let mut executor = CommandExecutor::new("cat");
+        let result = executor.execute(input).unwrap();
+        assert_eq!(result.exit_kind, ExitKind::Ok);
+        assert_eq!(result.stdout, input);
+
+        executor.input_delivery(InputDelivery::File).arg("@@");
+        let result = executor.execute(input).unwrap();
+        assert_eq!(result.exit_kind, ExitKind::Ok);
+        assert_eq!(result.stdout, input);
+
+        let mut executor = CommandExecutor::new("echo");
+        executor
+            .input_delivery(InputDelivery::Argv)
+            .args(&["-n", "@@"]);
+        let result = executor.execute(input).unwrap();
+        assert_eq!(result.stdout, input);
+        assert_eq!(result.as_iteration_result(), Ok(()));
+        assert!(!result.input_truncated);
+
+        let result = executor.execute(b"hello\0lain").unwrap();
+        assert_eq!(result.exit_kind, ExitKind::Ok);
+        assert_eq!(result.stdout, b"hello");
+        assert!(result.input_truncated);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn executor_classifies_exits() {
+        use lain::executor::{CommandExecutor, ExitKind};
+        use std::time::Duration;
+
+        let mut executor = CommandExecutor::new("sh");
+        executor.args(&["-c", "exit 3"]);
+        let result = executor.execute(&[]).unwrap();
+        assert_eq!(result.exit_kind, ExitKind::NonZeroExit(3));
+        assert_eq!(result.as_iteration_result(), Ok(()));
+
+        let mut executor = CommandExecutor::new("sh");
+        executor.args(&["-c", "kill -SEGV $$"]);
+        let result = executor.execute(&[]).unwrap();
+        assert_eq!(result.exit_kind, ExitKind::Crash(11));
+        assert_eq!(result.as_iteration_result(), Err(()));
+
+        let mut executor = CommandExecutor::new("sleep");
+        executor.arg("5").timeout(Duration::from_millis(100));
+        let result = executor.execute(&[]).unwrap();
+        assert_eq!(result.exit_kind, ExitKind::Timeout);
+        assert!(result.duration < Duration::from_secs(5));
+        assert_eq!(result.as_iteration_result(), Err(()));
+
+        // the background process inherits stdout and keeps it open after the shell exits
+        let mut executor = CommandExecutor::new("sh");
+        executor.args(["-c", "echo started; sleep 5 & exit 0"]);
+        let start = std::time::Instant::now();
+        let result = executor.execute(&[]).unwrap();
+        assert_eq!(result.exit_kind, ExitKind::Ok);
+        assert_eq!(result.stdout, b"started\n");
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        // processes spawned by a target which times out are killed along with it
+        let mut executor = CommandExecutor::new("sh");
+        executor
+            .args(["-c", "sleep 5 & echo $!; wait"])
+            .timeout(Duration::from_millis(100));
+        let result = executor.execute(&[]).unwrap();
+        assert_eq!(result.exit_kind, ExitKind::Timeout);
+
+        // the kill is delivered asynchronously, so give the process a moment to exit
+        let pid = String::from_utf8(result.stdout).unwrap();
+        let is_alive = || {
+            std::fs::read_to_string(format!("/proc/{}/stat", pid.trim()))
+                .is_ok_and(|stat| !stat.contains(") Z "))
+        };
+        let start = std::time::Instant::now();
+        while is_alive() && start.elapsed() < Duration::from_secs(1) {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        assert!(!is_alive());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn executor_caps_captured_output() {
+        use lain::executor::{CommandExecutor, ExitKind};
+
+        // the target isn't blocked by output which isn't kept
+        let mut executor = CommandExecutor::new("sh");
+        executor
+            .args(["-c", "head -c 1000000 /dev/zero; echo done >&2"])
+            .max_output_size(100);
+        let result = executor.execute(&[]).unwrap();
+        assert_eq!(result.exit_kind, ExitKind::Ok);
+        assert_eq!(result.stdout, vec![0u8; 100]);
+        assert_eq!(result.stderr, b"done\n");
+    }
+
+    #[test]
+    fn tcp_transport_reuses_connection() {
+        use lain::transport::{ExchangeKind, TcpTransport, Transport, TransportConfig};
+        use std::io::{Read, Write};
+        use std::net::TcpListener;
+        use std::time::Duration;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // echo server which only accepts a single connection
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buffer = [0u8; 0x100];
+            loop {
+                match stream.read(&mut buffer) {
+                    Ok(0) | Err(_) => break,
+                    Ok(read) => stream.write_all(&buffer[..read]).unwrap(),
+                }
+            }
+        });
+
+        let mut config = TransportConfig::new();
+        config.read_timeout(Duration::from_millis(20));
+
+        let mut transport = TcpTransport::new(addr, config).unwrap();
+        for message in &[&b"first"[..], &b"second"[..], &b"third"[..]] {
+            let result = transport.exchange(message);
+
+            assert_eq!(result.kind, ExchangeKind::Ok);
+            assert_eq!(&result.response[..], *message);
+        }
+
+        transport.disconnect();
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn tcp_transport_replaces_stale_connection() {
+        use lain::transport::{ExchangeKind, TcpTransport, Transport, TransportConfig};
+        use std::io::{Read, Write};
+        use std::net::TcpListener;
+        use std::time::Duration;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // answers a single message per connection and closes it once the client stopped reading
+        let server = std::thread::spawn(move || {
+            for _i in 0..2 {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut buffer = [0u8; 0x100];
+                let read = stream.read(&mut buffer).unwrap();
+                stream.write_all(&buffer[..read]).unwrap();
+                std::thread::sleep(Duration::from_millis(100));
+            }
+
+            listener
+        });
+
+        let mut config = TransportConfig::new();
+        config.read_timeout(Duration::from_millis(20));
+
+        let mut transport = TcpTransport::new(addr, config).unwrap();
+
+        let result = transport.exchange(b"first");
+        assert_eq!(result.kind, ExchangeKind::Ok);
+        assert_eq!(result.response, b"first");
+
+        std::thread::sleep(Duration::from_millis(200));
+
+        let result = transport.exchange(b"second");
+        assert_eq!(result.kind, ExchangeKind::Ok);
+        assert_eq!(result.response, b"second");
+
+        transport.disconnect();
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn tcp_transport_never_resends_inputs() {
+        use lain::transport::{ExchangeKind, TcpTransport, Transport, TransportConfig};
+        use std::io::Read;
+        use std::net::TcpListener;
+        use std::time::Duration;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // reads a single message per connection and closes it without replying
+        let server = std::thread::spawn(move || {
+            let mut received = vec![];
+            for _i in 0..3 {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut buffer = [0u8; 0x100];
+                let read = stream.read(&mut buffer).unwrap_or(0);
+                if read > 0 {
+                    received.push(buffer[..read].to_vec());
+                }
+            }
+
+            received
+        });
+
+        let mut config = TransportConfig::new();
+        config.read_timeout(Duration::from_millis(500));
+
+        let mut transport = TcpTransport::new(addr, config).unwrap();
+        for message in &[&b"first"[..], &b"second"[..]] {
+            let result = transport.exchange(message);
+
+            assert_eq!(result.kind, ExchangeKind::ClosedWithoutResponse);
+            assert!(result.response.is_empty());
+            assert_eq!(result.as_iteration_result(), Ok(()));
+        }
+
+        transport.disconnect();
+        let received = server.join().unwrap();
+        assert_eq!(received, vec![b"first".to_vec(), b"second".to_vec()]);
+    }
+
+    #[test]
+    fn tcp_transport_only_probes_closed_connections() {
+        use lain::transport::{ExchangeKind, TcpTransport, Transport, TransportConfig};
+        use std::io::{Read, Write};
+        use std::net::TcpListener;
+        use std::time::Duration;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // echo server which handles one connection at a time
+        let server = std::thread::spawn(move || {
+            for _i in 0..3 {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut buffer = [0u8; 0x100];
+                loop {
+                    match stream.read(&mut buffer) {
+                        Ok(0) | Err(_) => break,
+                        Ok(read) => stream.write_all(&buffer[..read]).unwrap(),
+                    }
+                }
+            }
+
+            listener
+        });
+
+        let mut config = TransportConfig::new();
+        config
+            .read_timeout(Duration::from_millis(20))
+            .reuse_connection(false);
+
+        let mut transport = TcpTransport::new(addr, config).unwrap();
+        for message in &[&b"first"[..], &b"second"[..], &b"third"[..]] {
+            let result = transport.exchange(message);
+
+            assert_eq!(result.kind, ExchangeKind::Ok);
+            assert_eq!(&result.response[..], *message);
+        }
+
+        // every exchange got a response, so no extra connections were made
+        let listener = server.join().unwrap();
+        listener.set_nonblocking(true).unwrap();
+        assert!(listener.accept().is_err());
+    }
+
+    #[test]
+    fn tcp_transport_detects_unreachable_target() {
+        use lain::transport::{ExchangeKind, TcpTransport, Transport, TransportConfig};
+        use std::io::Read;
+        use std::net::TcpListener;
+        use std::time::Duration;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // "crashes" as soon as it receives data
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buffer = [0u8; 0x100];
+            let _ = stream.read(&mut buffer);
+        });
+
+        let mut config = TransportConfig::new();
+        config
+            .read_timeout(Duration::from_millis(500))
+            .reconnect_attempts(2)
+            .reconnect_delay(Duration::from_millis(10));
+
+        let mut transport = TcpTransport::new(addr, config).unwrap();
+
+        let result = transport.exchange(b"crash");
+        server.join().unwrap();
+
+        assert_eq!(result.kind, ExchangeKind::PossibleCrash);
+        assert_eq!(result.as_iteration_result(), Err(()));
+
+        let result = transport.exchange(b"hello");
+        assert_eq!(result.kind, ExchangeKind::Unreachable);
+    }
+
+    #[test]
+    fn udp_transport_captures_response() {
+        use lain::transport::{ExchangeKind, Transport, TransportConfig, UdpTransport};
+        use std::net::UdpSocket;
+        use std::time::Duration;
+
+        let server_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = server_socket.local_addr().unwrap();
+
+        let server = std::thread::spawn(move || {
+            let mut buffer = [0u8; 0x100];
+            let (read, peer) = server_socket.recv_from(&mut buffer).unwrap();
+            server_socket.send_to(&buffer[..read], peer).unwrap();
+        });
+
+        let mut config = TransportConfig::new();
+        config.read_timeout(Duration::from_millis(50));
+
+        let mut transport = UdpTransport::new(addr, config).unwrap();
+        let result = transport.exchange(b"datagram");
+
+        server.join().unwrap();
+
+        assert_eq!(result.kind, ExchangeKind::Ok);
+        assert_eq!(result.response, b"datagram");
+    }
+
+    #[derive(Debug, Clone, PartialEq, NewFuzzed, Mutatable, BinarySerialize)]
+    enum ProtocolMessage {
+        Hello(u8),
+        Auth(u32),
+        Command(u16),
+        Bye(u8),
+    }
+
+    impl StateMachine for ProtocolMessage {
+        fn is_valid_start(&self) -> bool {
+            matches!(self, ProtocolMessage::Hello(_))
+        }
+
+        fn is_valid_successor(&self, previous: &Self) -> bool {
+            matches!(
+                (previous, self),
+                (ProtocolMessage::Hello(_), ProtocolMessage::Auth(_))
+                    | (ProtocolMessage::Auth(_), ProtocolMessage::Command(_))
+                    | (ProtocolMessage::Command(_), ProtocolMessage::Command(_))
+                    | (ProtocolMessage::Command(_), ProtocolMessage::Bye(_))
+            )
+        }
+    }
+
+    #[test]
+    fn new_fuzzed_sequence_respects_state_machine() {
+        let mut mutator = get_mutator();
+
+        for _i in 0..100 {
+            let sequence = Sequence::<ProtocolMessage>::new_fuzzed(&mut mutator, None);
+
+            assert!(!sequence.is_empty());
+            assert!(sequence.is_valid());
+
+            let mut serialized = vec![];
+            sequence.binary_serialize::<_, LittleEndian>(&mut serialized);
+
+            assert_eq!(serialized.len(), sequence.serialized_size());
+            assert_eq!(
+                serialized,
+                sequence.serialize_steps::<LittleEndian>().concat()
+            );
+        }
+    }
+
+    #[test]
+    fn sequence_mutations_change_structure() {
+        let mut mutator = get_mutator();
+        let mut sequence = Sequence::<ProtocolMessage>::new_fuzzed(&mut mutator, None);
+
+        let mut lengths = std::collections::HashSet::new();
+        let mut violations = 0;
+        for _i in 0..1000 {
+            sequence.mutate(&mut mutator, None);
+
+            lengths.insert(sequence.len());
+            if !sequence.is_valid() {
+                violations += 1;
+                sequence = Sequence::<ProtocolMessage>::new_fuzzed(&mut mutator, None);
             }
         }
 
+        // only the mutations which deliberately ignore the state machine may break it
+        assert!(lengths.len() > 2);
+        assert!(
+            violations as f64 <= 1000.0 * lain::mutator::CHANCE_TO_VIOLATE_STATE_MACHINE * 2.0,
+            "{} mutations broke the state machine",
+            violations
+        );
+    }
+
+    #[derive(Debug, Clone, NewFuzzed, Mutatable, BinarySerialize)]
+    struct Toggle {
+        on: bool,
+    }
+
+    impl StateMachine for Toggle {
+        fn is_valid_start(&self) -> bool {
+            !self.on
+        }
+
+        fn is_valid_successor(&self, previous: &Self) -> bool {
+            self.on != previous.on
+        }
+    }
+
+    #[test]
+    fn sequence_message_mutations_respect_state_machine() {
         let mut mutator = get_mutator();
+        let mut sequence = Sequence::<Toggle>::new_fuzzed(&mut mutator, None);
 
-        let instance = S::new_fuzzed(&mut mutator, None);
+        let mut violations = 0;
+        for _i in 0..1000 {
+            sequence.mutate(&mut mutator, None);
 
-        assert!(instance.post_mutation_called);
+            if !sequence.is_valid() {
+                violations += 1;
+                sequence = Sequence::<Toggle>::new_fuzzed(&mut mutator, None);
+            }
+        }
+
+        assert!(
+            violations as f64 <= 1000.0 * lain::mutator::CHANCE_TO_VIOLATE_STATE_MACHINE * 2.0,
+            "{} mutations broke the state machine",
+            violations
+        );
+    }
+
+    struct ArithmeticGrammar;
+
+    impl lain::grammar::GrammarDefinition for ArithmeticGrammar {
+        fn grammar() -> &'static lain::grammar::Grammar {
+            lain::lazy_static::lazy_static! {
+                static ref GRAMMAR: lain::grammar::Grammar = lain::grammar::Grammar::parse(r#"
+                    # simple arithmetic expressions
+                    <expr>   ::= <term> | <term> <op> <expr>
+                    <term>   ::= <number> | "(" <expr> ")"
+                    <op>     ::= "+" | "-" | '*' | "\x2f"
+                    number    = digit / digit number  ; ABNF-style rules
+                    digit     = %x30-39
+                "#).unwrap();
+            }
+
+            &GRAMMAR
+        }
+    }
+
+    /// Returns whether `input` is a valid expression in `ArithmeticGrammar`
+    fn is_arithmetic_expression(input: &[u8]) -> bool {
+        let mut depth = 0i32;
+        let mut expect_term = true;
+
+        for &b in input {
+            match b {
+                b'0'..=b'9' => expect_term = false,
+                b'(' if expect_term => depth += 1,
+                b')' if !expect_term && depth > 0 => depth -= 1,
+                b'+' | b'-' | b'*' | b'/' if !expect_term => expect_term = true,
+                _ => return false,
+            }
+        }
+
+        depth == 0 && !expect_term
     }
 
     #[test]
-    fn test_string_mutation() {
-        // this test mostly ensures that the string generation does not panic
+    fn grammar_tree_generates_valid_strings() {
+        use lain::grammar::GrammarTree;
+
+        const MAX_SIZE: usize = 32;
+
         let mut mutator = get_mutator();
+        let mut constraints = Constraints::new();
+        constraints.max_size(MAX_SIZE);
 
-        let mut utf8_str = Utf8String::new_fuzzed(&mut mutator, None);
-        println!("{:?}", utf8_str);
+        for _i in 0..200 {
+            let tree =
+                GrammarTree::<ArithmeticGrammar>::new_fuzzed(&mut mutator, Some(&constraints));
 
-        utf8_str.mutate(&mut mutator, None);
-        println!("{:?}", utf8_str);
+            let mut serialized = vec![];
+            tree.binary_serialize::<_, LittleEndian>(&mut serialized);
 
-        let mut ascii_str = AsciiString::new_fuzzed(&mut mutator, None);
-        println!("{:?}", ascii_str);
+            assert_eq!(serialized.len(), tree.serialized_size());
+            assert!(serialized.len() <= MAX_SIZE);
+            assert!(
+                is_arithmetic_expression(&serialized),
+                "{}",
+                String::from_utf8_lossy(&serialized)
+            );
+        }
+    }
 
-        ascii_str.mutate(&mut mutator, None);
-        println!("{:?}", ascii_str);
+    #[test]
+    fn grammar_tree_mutations_stay_in_grammar() {
+        use lain::grammar::GrammarTree;
+
+        const MAX_SIZE: usize = 64;
+
+        let mut mutator = get_mutator();
+        let mut constraints = Constraints::new();
+        constraints.max_size(MAX_SIZE);
+
+        let mut tree =
+            GrammarTree::<ArithmeticGrammar>::new_fuzzed(&mut mutator, Some(&constraints));
+
+        let mut outputs = std::collections::HashSet::new();
+        for _i in 0..500 {
+            tree.mutate(&mut mutator, Some(&constraints));
+
+            let output = tree.to_bytes();
+            assert!(output.len() <= MAX_SIZE);
+            assert!(
+                is_arithmetic_expression(&output),
+                "{}",
+                String::from_utf8_lossy(&output)
+            );
+
+            outputs.insert(output);
+        }
+
+        assert!(outputs.len() > 100);
+    }
+
+    #[test]
+    fn invalid_grammars_are_rejected() {
+        use lain::grammar::{Grammar, GrammarBuilder, GrammarError, Symbol};
+
+        assert_eq!(
+            Grammar::parse("<a> ::= <b>").unwrap_err(),
+            GrammarError::UndefinedRule("b".to_string())
+        );
+
+        assert_eq!(
+            Grammar::parse("<a> ::= \"x\" <a>").unwrap_err(),
+            GrammarError::UnproductiveRule("a".to_string())
+        );
+
+        assert!(matches!(
+            Grammar::parse("<a> ::= \"x\"\n<b> ::= \"unterminated"),
+            Err(GrammarError::Parse { line: 2, .. })
+        ));
+
+        assert_eq!(
+            GrammarBuilder::new().build().unwrap_err(),
+            GrammarError::Empty
+        );
+
+        let grammar = GrammarBuilder::new()
+            .rule(
+                "greeting",
+                vec![Symbol::terminal("hello "), Symbol::rule("name")],
+            )
+            .rule("name", vec![Symbol::terminal("world")])
+            .start("greeting")
+            .build()
+            .unwrap();
+
+        assert_eq!(grammar.start_rule(), "greeting");
+        assert_eq!(grammar.min_size(), "hello world".len());
+    }
+
+    #[derive(Debug, NewFuzzed, Mutatable, Clone, BinarySerialize)]
+    struct WideStruct {
+        uuid: u128,
+        signed: i128,
+        letter: char,
+        #[lain(bits = 100)]
+        wide_bitfield_1: u128,
+        #[lain(bits = 28)]
+        wide_bitfield_2: u128,
+    }
+
+    #[test]
+    fn test_128_bit_and_char_serialization() {
+        let s = WideStruct {
+            uuid: 0x0011_2233_4455_6677_8899_AABB_CCDD_EEFF,
+            signed: -2,
+            letter: '\u{1F600}',
+            wide_bitfield_1: 1,
+            wide_bitfield_2: 0xFFF_FFFF,
+        };
+
+        assert_eq!(WideStruct::min_nonzero_elements_size(), 16 + 16 + 4 + 16);
+
+        let mut output = vec![];
+        s.binary_serialize::<_, BigEndian>(&mut output);
+
+        let mut expected = vec![
+            0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xAA, 0xBB, 0xCC, 0xDD,
+            0xEE, 0xFF,
+        ];
+        expected.extend_from_slice(&[0xFF; 15]);
+        expected.push(0xFE);
+        expected.extend_from_slice(&[0x00, 0x01, 0xF6, 0x00]);
+        expected.extend_from_slice(&[0xFF, 0xFF, 0xFF, 0xF0]);
+        expected.extend_from_slice(&[0x00; 11]);
+        expected.push(0x01);
+
+        compare_slices(&expected, &output);
+
+        let mut output = vec![];
+        s.binary_serialize::<_, LittleEndian>(&mut output);
+
+        assert_eq!(output[0], 0xFF);
+        assert_eq!(output[15], 0x00);
+        assert_eq!(&output[32..36], &[0x00, 0xF6, 0x01, 0x00]);
+    }
+
+    #[test]
+    fn test_128_bit_and_char_mutation() {
+        let mut mutator = get_mutator();
+
+        let mut s = WideStruct::new_fuzzed(&mut mutator, None);
+        let mut saw_high_bits = false;
+        let mut saw_non_ascii = false;
+
+        for _ in 0..1000 {
+            s.mutate(&mut mutator, None);
+
+            saw_high_bits |= s.uuid > u64::MAX as u128;
+            saw_non_ascii |= !s.letter.is_ascii();
+
+            let mut output = vec![];
+            s.binary_serialize::<_, BigEndian>(&mut output);
+            assert_eq!(output.len(), s.serialized_size());
+        }
+
+        assert!(saw_high_bits);
+        assert!(saw_non_ascii);
+
+        #[derive(Debug, NewFuzzed, Clone, BinarySerialize)]
+        struct HalfWide {
+            #[lain(bits = 64)]
+            low: u128,
+            #[lain(bits = 64)]
+            high: u128,
+        }
+
+        // a 64-bit bitfield in a wider type is generated within its bits unless the constraints
+        // are randomly ignored
+        let mut in_range = 0;
+        for _ in 0..1000 {
+            let half = HalfWide::new_fuzzed(&mut mutator, None);
+            if half.low <= u64::MAX as u128 && half.high <= u64::MAX as u128 {
+                in_range += 1;
+            }
+        }
+        assert!(in_range > 800, "{}", in_range);
+
+        for _ in 0..1000 {
+            let mut c = char::new_fuzzed(&mut mutator, None);
+            c.mutate(&mut mutator, None);
+            assert!(std::char::from_u32(c as u32).is_some());
+        }
+    }
+
+    #[derive(Debug, NewFuzzed, Mutatable, Clone, BinarySerialize)]
+    struct CollectionStruct {
+        pair: (u8, u16),
+        map: std::collections::BTreeMap<u8, u32>,
+        set: std::collections::BTreeSet<u16>,
+        queue: std::collections::VecDeque<u8>,
+    }
+
+    #[test]
+    fn test_collection_serialization() {
+        let mut s = CollectionStruct {
+            pair: (1, 0x0203),
+            map: Default::default(),
+            set: Default::default(),
+            queue: Default::default(),
+        };
+
+        s.map.insert(2, 0xAABBCCDD);
+        s.map.insert(1, 0x11223344);
+        s.set.insert(0x0506);
+        s.queue.push_back(8);
+        s.queue.push_front(7);
+
+        let expected: Vec<u8> = vec![
+            0x01, 0x02, 0x03, // pair
+            0x00, 0x00, 0x00, 0x02, // map count
+            0x01, 0x11, 0x22, 0x33, 0x44, // first map entry
+            0x02, 0xAA, 0xBB, 0xCC, 0xDD, // second map entry
+            0x00, 0x00, 0x00, 0x01, // set count
+            0x05, 0x06, // set element
+            0x07, 0x08, // queue
+        ];
+
+        let mut output = vec![];
+        s.binary_serialize::<_, BigEndian>(&mut output);
+
+        assert_eq!(s.serialized_size(), expected.len());
+        compare_slices(&expected, &output);
+
+        let mut output = vec![];
+        (0x1234u16, 'A', (true,)).binary_serialize::<_, LittleEndian>(&mut output);
+        compare_slices(&[0x34, 0x12, 0x41, 0x00, 0x00, 0x00, 0x01], &output);
+    }
+
+    #[test]
+    fn test_collection_generation_and_mutation() {
+        let mut mutator = get_mutator();
+
+        let mut constraints = Constraints::new();
+        constraints.max_size(0x40);
+
+        for _ in 0..100 {
+            let map = std::collections::HashMap::<u32, (u8, u64)>::new_fuzzed(
+                &mut mutator,
+                Some(&constraints),
+            );
+            assert!(map.serialized_size() <= 0x40);
+        }
+
+        let mut set = std::collections::HashSet::<u32>::new();
+        let mut saw_insert = false;
+        let mut saw_remove = false;
+        for _ in 0..1000 {
+            let previous_len = set.len();
+            set.mutate(&mut mutator, Some(&constraints));
+
+            saw_insert |= set.len() > previous_len;
+            saw_remove |= set.len() < previous_len;
+            assert!(set.serialized_size() <= 0x40);
+        }
+
+        assert!(saw_insert);
+        assert!(saw_remove);
+
+        let mut s = CollectionStruct::new_fuzzed(&mut mutator, None);
+        for _ in 0..100 {
+            s.mutate(&mut mutator, None);
+
+            let mut output = vec![];
+            s.binary_serialize::<_, LittleEndian>(&mut output);
+            assert_eq!(output.len(), s.serialized_size());
+        }
+    }
+
+    #[test]
+    fn test_hash_collections_are_deterministic() {
+        use std::collections::{HashMap, HashSet};
+
+        // every map and set has its own random hasher state, so these iterate in different orders
+        let mut mutators = (get_mutator(), get_mutator());
+        let mut maps = (HashMap::<u16, u8>::new(), HashMap::<u16, u8>::new());
+        let mut sets = (HashSet::<u16>::new(), HashSet::<u16>::new());
+
+        for _ in 0..200 {
+            maps.0.mutate(&mut mutators.0, None);
+            sets.0.mutate(&mut mutators.0, None);
+            maps.1.mutate(&mut mutators.1, None);
+            sets.1.mutate(&mut mutators.1, None);
+
+            let mut outputs = (vec![], vec![]);
+            maps.0.binary_serialize::<_, LittleEndian>(&mut outputs.0);
+            sets.0.binary_serialize::<_, LittleEndian>(&mut outputs.0);
+            maps.1.binary_serialize::<_, LittleEndian>(&mut outputs.1);
+            sets.1.binary_serialize::<_, LittleEndian>(&mut outputs.1);
+
+            assert_eq!(outputs.0, outputs.1);
+        }
+
+        assert!(maps.0.len() > 1);
+    }
+
+    #[derive(NewFuzzed, Mutatable, Clone, BinarySerialize)]
+    struct LargeArrays {
+        key: [u8; 256],
+        table: [u32; 64],
+        empty: [u16; 0],
+    }
+
+    #[test]
+    fn test_large_arrays() {
+        let mut mutator = get_mutator();
+
+        assert_eq!(LargeArrays::max_default_object_size(), 256 + 64 * 4);
+
+        let mut s = LargeArrays::new_fuzzed(&mut mutator, None);
+        assert!(s.key.iter().any(|b| *b != s.key[0]));
+
+        let mut output = vec![];
+        s.binary_serialize::<_, LittleEndian>(&mut output);
+        assert_eq!(output.len(), 256 + 64 * 4);
+        compare_slices(&s.key, &output[..256]);
+        compare_slices(&s.table[0].to_le_bytes(), &output[256..260]);
+
+        let original_key = s.key;
+        let mut key_changed = false;
+        for _ in 0..10 {
+            s.mutate(&mut mutator, None);
+            key_changed |= s.key != original_key;
+        }
+
+        assert!(key_changed);
+    }
+
+    #[test]
+    fn test_array_items_respect_max_size() {
+        let mut mutator = get_mutator();
+
+        let mut constraints = Constraints::new();
+        constraints.max_size(128);
+
+        for _ in 0..100 {
+            let strings = <[Vec<u8>; 100]>::new_fuzzed(&mut mutator, Some(&constraints));
+            assert!(strings.serialized_size() <= 128);
+        }
+    }
+
+    #[test]
+    fn test_post_mutation_called() {
+        #[derive(NewFuzzed, Clone, BinarySerialize)]
+        struct S {
+            #[lain(ignore)]
+            pub post_mutation_called: bool,
+        }
+
+        impl Fixup for S {
+            fn fixup<R: lain::rand::Rng>(&mut self, _mutator: &mut Mutator<R>) {
+                println!("fixup called");
+                self.post_mutation_called = true;
+            }
+        }
+
+        let mut mutator = get_mutator();
+
+        let instance = S::new_fuzzed(&mut mutator, None);
+
+        assert!(instance.post_mutation_called);
+    }
+
+    #[test]
+    fn test_string_mutation() {
+        // this test mostly ensures that the string generation does not panic
+        let mut mutator = get_mutator();
+
+        let mut utf8_str = Utf8String::new_fuzzed(&mut mutator, None);
+        println!("{:?}", utf8_str);
+
+        utf8_str.mutate(&mut mutator, None);
+        println!("{:?}", utf8_str);
+
+        let mut ascii_str = AsciiString::new_fuzzed(&mut mutator, None);
+        println!("{:?}", ascii_str);
+
+        ascii_str.mutate(&mut mutator, None);
+        println!("{:?}", ascii_str);
+    }
+
+    #[test]
+    fn test_std_string_generation_and_mutation() {
+        #[derive(Debug, NewFuzzed, Mutatable, Clone, BinarySerialize)]
+        struct Request {
+            method: u8,
+            path: String,
+        }
+
+        let mut mutator = get_mutator();
+
+        let mut constraints = Constraints::new();
+        constraints.max_size(0x40);
+
+        let mut saw_bidi_control = false;
+        for _ in 0..100 {
+            let mut request = Request::new_fuzzed(&mut mutator, Some(&constraints));
+            assert!(request.serialized_size() <= 0x40);
+
+            for _ in 0..10 {
+                request.mutate(&mut mutator, Some(&constraints));
+                assert!(request.serialized_size() <= 0x40);
+                saw_bidi_control |= request.path.contains('\u{202e}');
+            }
+        }
+
+        assert!(saw_bidi_control);
+
+        let mut s = String::from("hello");
+        let mut saw_long_run = false;
+        for _ in 0..1000 {
+            s.mutate(&mut mutator, None);
+            saw_long_run |= s.len() > 0x100;
+            s.truncate(s.char_indices().nth(16).map(|(i, _)| i).unwrap_or(s.len()));
+        }
+
+        assert!(saw_long_run);
+    }
+
+    #[test]
+    fn test_utf8_string_can_become_invalid() {
+        let mut mutator = get_mutator();
+
+        let mut s = Utf8String::from("hello");
+        assert_eq!(s.to_str(), Some("hello"));
+        assert_eq!(&s[..], b"hello");
+
+        let mut output = vec![];
+        s.binary_serialize::<_, BigEndian>(&mut output);
+        compare_slices(b"hello", &output);
+
+        let mut saw_invalid = false;
+        for _ in 0..100 {
+            s.mutate(&mut mutator, None);
+            saw_invalid |= s.to_str().is_none();
+        }
+
+        assert!(saw_invalid);
+
+        use std::convert::TryFrom;
+
+        let ascii = AsciiString::try_from(String::from("abc")).unwrap();
+        assert_eq!(ascii.len(), 3);
+        assert_eq!(String::from(ascii), "abc");
+
+        let error = AsciiString::try_from("ab\u{e9}c").unwrap_err();
+        assert_eq!(error.position(), 2);
+        assert_eq!(error.into_string(), "ab\u{e9}c");
+        assert_eq!(AsciiString::new("ab\u{e9}c").as_str(), "ab?c");
+    }
+
+    #[derive(Debug, Default, Clone, NewFuzzed, Mutatable, BinarySerialize)]
+    struct EncodedFields {
+        #[lain(prefix = "u16")]
+        name: String,
+        #[lain(prefix = "u8", little_endian)]
+        values: Vec<u32>,
+        #[lain(nul_terminated)]
+        path: String,
+        #[lain(prefix = "u32", utf16le, nul_terminated)]
+        wide: String,
+    }
+
+    #[test]
+    fn test_encoded_field_serialization() {
+        let obj = EncodedFields {
+            name: "abc".to_string(),
+            values: vec![1, 2],
+            path: "/a".to_string(),
+            wide: "h\u{e9}".to_string(),
+        };
+
+        let expected: &[u8] = &[
+            0x00, 0x03, b'a', b'b', b'c', // name
+            0x02, 0x01, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, // values
+            b'/', b'a', 0x00, // path
+            0x00, 0x00, 0x00, 0x02, b'h', 0x00, 0xe9, 0x00, 0x00, 0x00, // wide
+        ];
+
+        let mut output = vec![];
+        let bytes_written = obj.binary_serialize::<_, BigEndian>(&mut output);
+
+        compare_slices(expected, &output);
+        assert_eq!(bytes_written, expected.len());
+        assert_eq!(obj.serialized_size(), expected.len());
+        assert_eq!(
+            EncodedFields::min_nonzero_elements_size(),
+            (2 + 1) + (1 + 4) + (1 + 1) + (4 + 2 + 1)
+        );
+    }
+
+    #[test]
+    fn test_prefixed_fields_fit_their_prefix() {
+        #[derive(Debug, Clone, NewFuzzed, Mutatable, BinarySerialize)]
+        struct Short {
+            #[lain(prefix = "u8")]
+            data: Vec<u8>,
+            #[lain(prefix = "u8", utf16le)]
+            text: String,
+        }
+
+        let mut mutator = get_mutator();
+        let mut obj = Short::new_fuzzed(&mut mutator, None);
+
+        for _ in 0..500 {
+            obj.mutate(&mut mutator, None);
+
+            assert!(obj.data.len() <= 0xff);
+            assert!(obj.text.encode_utf16().count() <= 0xff);
+
+            let mut output = vec![];
+            obj.binary_serialize::<_, BigEndian>(&mut output);
+            assert_eq!(output.len(), obj.serialized_size());
+            assert_eq!(usize::from(output[0]), obj.data.len());
+        }
+    }
+
+    #[derive(Debug, Clone, NewFuzzed, Mutatable, BinarySerialize, Serialize, Deserialize)]
+    #[serde(crate = "lain::serde")]
+    struct Desynced {
+        name: LengthPrefixed<u16, String>,
+        #[lain(nul_terminated)]
+        values: LengthPrefixed<u8, Vec<u32>>,
+    }
+
+    #[test]
+    fn test_mutation_can_mismatch_length_prefixes() {
+        let mut mutator = get_mutator();
+        let mut obj = Desynced::new_fuzzed(&mut mutator, None);
+
+        let serialize = |obj: &Desynced| {
+            let mut output = vec![];
+            obj.binary_serialize::<_, BigEndian>(&mut output);
+            output
+        };
+
+        let mut saw_mismatch = false;
+        for _ in 0..500 {
+            obj.mutate(&mut mutator, None);
+
+            let output = serialize(&obj);
+            assert_eq!(output.len(), obj.serialized_size());
+
+            // the prefix is part of the object, so copies serialize identically
+            compare_slices(&output, &serialize(&obj.clone()));
+            let json = serde_json::to_string(&obj).unwrap();
+            compare_slices(&output, &serialize(&serde_json::from_str(&json).unwrap()));
+
+            let prefix = usize::from(u16::from_be_bytes([output[0], output[1]]));
+            if obj.name.is_exact() {
+                assert_eq!(prefix, obj.name.len());
+            }
+            saw_mismatch |= prefix != obj.name.len();
+        }
+
+        assert!(saw_mismatch);
+
+        obj.name.set_len_override(None);
+        let output = serialize(&obj);
+        assert_eq!(
+            usize::from(u16::from_be_bytes([output[0], output[1]])),
+            obj.name.len()
+        );
+    }
+
+    #[test]
+    fn test_attribute_prefixes_are_exact() {
+        let mut mutator = get_mutator();
+        let mut obj = EncodedFields::new_fuzzed(&mut mutator, None);
+
+        for _ in 0..500 {
+            obj.mutate(&mut mutator, None);
+
+            let mut output = vec![];
+            obj.binary_serialize::<_, BigEndian>(&mut output);
+            assert_eq!(output.len(), obj.serialized_size());
+
+            let prefix = usize::from(u16::from_be_bytes([output[0], output[1]]));
+            assert_eq!(prefix, obj.name.len());
+        }
+    }
+
+    #[test]
+    fn test_varint_serialization() {
+        #[derive(Debug, Default, Clone, NewFuzzed, Mutatable, BinarySerialize)]
+        struct Varints {
+            small: VarU32,
+            large: VarU64,
+            negative: ZigZag32,
+        }
+
+        let obj = Varints {
+            small: VarU32::new(1),
+            large: VarU64::new(300),
+            negative: ZigZag32::new(-2),
+        };
+
+        let mut output = vec![];
+        obj.binary_serialize::<_, BigEndian>(&mut output);
+
+        compare_slices(&[0x01, 0xac, 0x02, 0x03], &output);
+        assert_eq!(obj.serialized_size(), 4);
+
+        assert_eq!(VarU64::new(u64::MAX).serialized_size(), 10);
+        assert_eq!(ZigZag64::new(i64::MIN).encode().len(), 10);
+        assert_eq!(VarU32::new(0).encode(), vec![0x00]);
+
+        let (decoded, consumed) = VarU64::decode(&[0xac, 0x02, 0xff]).unwrap();
+        assert_eq!(decoded.value(), 300);
+        assert_eq!(consumed, 2);
+        assert!(decoded.is_canonical());
+
+        let (decoded, consumed) = ZigZag32::decode(&[0x03]).unwrap();
+        assert_eq!(decoded.value(), -2);
+        assert_eq!(consumed, 1);
+
+        assert!(VarU32::decode(&[0x80, 0x80]).is_none());
+    }
+
+    #[test]
+    fn test_varint_mutation_makes_non_canonical_encodings() {
+        let mut mutator = get_mutator();
+
+        let mut saw_overlong = false;
+        let mut saw_truncated = false;
+
+        for _ in 0..200 {
+            let mut value = VarU32::new_fuzzed(&mut mutator, None);
+            value.mutate(&mut mutator, None);
+
+            let encoded = value.encode();
+            assert_eq!(encoded.len(), value.serialized_size());
+
+            match VarU32::decode(&encoded) {
+                Some((decoded, consumed)) => {
+                    assert_eq!(consumed, encoded.len());
+                    assert_eq!(decoded.value(), value.value());
+                    saw_overlong |= !decoded.is_canonical();
+                }
+                None => {
+                    assert!(!value.is_canonical());
+                    saw_truncated = true;
+                }
+            }
+        }
+
+        assert!(saw_overlong);
+        assert!(saw_truncated);
+    }
+
+    #[derive(Debug, Default, Clone, NewFuzzed, Mutatable, BinarySerialize)]
+    #[lain(bit_order = "msb")]
+    struct MsbBitStream {
+        #[lain(bits = 1)]
+        forbidden_zero_bit: bool,
+        #[lain(bits = 2)]
+        nal_ref_idc: u8,
+        #[lain(bits = 5)]
+        nal_unit_type: u8,
+        #[lain(bits = 12)]
+        width: u16,
+        #[lain(bits = 7)]
+        level: u8,
+        marker: u8,
+        #[lain(bits = 3, pad_bits = 2)]
+        flags: u8,
+    }
+
+    #[derive(Debug, Default, Clone, NewFuzzed, Mutatable, BinarySerialize)]
+    #[lain(bit_order = "lsb")]
+    struct LsbBitStream {
+        #[lain(bits = 1)]
+        forbidden_zero_bit: bool,
+        #[lain(bits = 2)]
+        nal_ref_idc: u8,
+        #[lain(bits = 5)]
+        nal_unit_type: u8,
+        #[lain(bits = 12)]
+        width: u16,
+        #[lain(bits = 7)]
+        level: u8,
+        marker: u8,
+        #[lain(bits = 3, pad_bits = 2)]
+        flags: u8,
+    }
+
+    #[test]
+    fn test_bit_stream_serialization() {
+        let msb = MsbBitStream {
+            forbidden_zero_bit: false,
+            nal_ref_idc: 3,
+            nal_unit_type: 5,
+            width: 0xabc,
+            level: 0x55,
+            marker: 0xff,
+            flags: 0b101,
+        };
+
+        let mut output = vec![];
+        msb.binary_serialize::<_, BigEndian>(&mut output);
+        compare_slices(&[0x65, 0xab, 0xca, 0xa0, 0xff, 0x28], &output);
+        assert_eq!(msb.serialized_size(), 6);
+
+        let lsb = LsbBitStream {
+            forbidden_zero_bit: false,
+            nal_ref_idc: 3,
+            nal_unit_type: 5,
+            width: 0xabc,
+            level: 0x55,
+            marker: 0xff,
+            flags: 0b101,
+        };
+
+        let mut output = vec![];
+        lsb.binary_serialize::<_, BigEndian>(&mut output);
+        compare_slices(&[0x2e, 0xbc, 0x5a, 0x05, 0xff, 0x14], &output);
+        assert_eq!(lsb.serialized_size(), 6);
+    }
+
+    #[test]
+    fn test_bit_stream_size_is_stable() {
+        let mut mutator = get_mutator();
+
+        assert_eq!(MsbBitStream::min_nonzero_elements_size(), 6);
+        assert_eq!(MsbBitStream::max_default_object_size(), 6);
+
+        for _ in 0..100 {
+            // values may occasionally exceed their width, but only the low bits are serialized
+            let mut obj = MsbBitStream::new_fuzzed(&mut mutator, None);
+            obj.mutate(&mut mutator, None);
+
+            let mut output = vec![];
+            assert_eq!(obj.binary_serialize::<_, BigEndian>(&mut output), 6);
+            assert_eq!(output.len(), obj.serialized_size());
+            // padding bits are always zero
+            assert_eq!(output[5] & 0b1100_0111, 0);
+        }
+    }
+
+    #[derive(
+        Debug, Default, Clone, NewFuzzed, Mutatable, BinarySerialize, Serialize, Deserialize,
+    )]
+    #[serde(crate = "lain::serde")]
+    #[lain(repr_c)]
+    struct ReprC {
+        a: u8,
+        b: u32,
+        c: u16,
+        d: u8,
+        #[lain(fuzz_padding)]
+        padding: PaddingSeed,
+    }
+
+    #[test]
+    fn test_layout_padding() {
+        #[derive(Debug, Default, Clone, NewFuzzed, Mutatable, BinarySerialize)]
+        #[lain(repr_c, pack = 2)]
+        struct Packed {
+            a: u8,
+            b: u32,
+            c: u16,
+            d: u8,
+        }
+
+        #[derive(Debug, Default, Clone, NewFuzzed, Mutatable, BinarySerialize)]
+        struct Explicit {
+            a: u8,
+            #[lain(align = 8)]
+            b: u16,
+            #[lain(pad = 3)]
+            c: u8,
+            #[lain(offset = 0x10)]
+            d: u8,
+        }
+
+        let repr_c = ReprC {
+            a: 1,
+            b: 2,
+            c: 3,
+            d: 4,
+            padding: PaddingSeed::default(),
+        };
+        let mut output = vec![];
+        repr_c.binary_serialize::<_, BigEndian>(&mut output);
+        compare_slices(&[1, 0, 0, 0, 0, 0, 0, 2, 0, 3, 4, 0], &output);
+        assert_eq!(repr_c.serialized_size(), 12);
+        assert_eq!(ReprC::min_nonzero_elements_size(), 12);
+
+        let packed = Packed {
+            a: 1,
+            b: 2,
+            c: 3,
+            d: 4,
+        };
+        let mut output = vec![];
+        packed.binary_serialize::<_, BigEndian>(&mut output);
+        compare_slices(&[1, 0, 0, 0, 0, 2, 0, 3, 4, 0], &output);
+        assert_eq!(packed.serialized_size(), 10);
+
+        let explicit = Explicit {
+            a: 1,
+            b: 2,
+            c: 3,
+            d: 4,
+        };
+        let mut output = vec![];
+        explicit.binary_serialize::<_, BigEndian>(&mut output);
+        compare_slices(
+            &[1, 0, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 3, 0, 0, 4],
+            &output,
+        );
+        assert_eq!(explicit.serialized_size(), 17);
+    }
+
+    #[test]
+    fn test_padding_can_be_fuzzed() {
+        let mut mutator = get_mutator();
+        let mut obj = ReprC::new_fuzzed(&mut mutator, None);
+
+        let padding = |obj: &ReprC| {
+            let mut output = vec![];
+            obj.binary_serialize::<_, BigEndian>(&mut output);
+            assert_eq!(output.len(), 12);
+            vec![output[1], output[2], output[3], output[11]]
+        };
+
+        let mut saw_nonzero_padding = false;
+        for _ in 0..10 {
+            obj.mutate(&mut mutator, None);
+
+            let first = padding(&obj);
+            assert_eq!(first, padding(&obj));
+            saw_nonzero_padding |= first.iter().any(|b| *b != 0);
+
+            // the seed is part of the object, so copies have the same padding
+            assert_eq!(first, padding(&obj.clone()));
+            let json = serde_json::to_string(&obj).unwrap();
+            assert_eq!(first, padding(&serde_json::from_str(&json).unwrap()));
+        }
+
+        assert!(saw_nonzero_padding);
+
+        obj.padding = PaddingSeed::default();
+        assert_eq!(padding(&obj), vec![0, 0, 0, 0]);
+    }
+
+    #[derive(Debug, Clone, NewFuzzed, Mutatable, BinarySerialize, Serialize, Deserialize)]
+    #[serde(crate = "lain::serde")]
+    #[lain(tag = "u16", tag_endian = "big")]
+    enum Tlv {
+        #[lain(tag_value = 0x10)]
+        Data(u8, u16),
+        #[lain(tag_value = 0x20)]
+        Flags(u32),
+        Other(u8),
+        #[lain(tag_value = 0xff)]
+        Empty,
+    }
+
+    impl Tlv {
+        fn expected_tag(&self) -> u16 {
+            match *self {
+                Tlv::Data(..) => 0x10,
+                Tlv::Flags(..) => 0x20,
+                Tlv::Other(..) => 2,
+                Tlv::Empty => 0xff,
+            }
+        }
+    }
+
+    #[test]
+    fn test_tagged_enum_serialization() {
+        let cases: Vec<(Tlv, &[u8])> = vec![
+            (Tlv::Data(1, 0x0203), &[0x00, 0x10, 0x01, 0x03, 0x02]),
+            (Tlv::Flags(4), &[0x00, 0x20, 0x04, 0x00, 0x00, 0x00]),
+            (Tlv::Other(5), &[0x00, 0x02, 0x05]),
+            (Tlv::Empty, &[0x00, 0xff]),
+        ];
+
+        for (value, expected) in cases {
+            let mut output = vec![];
+            value.binary_serialize::<_, LittleEndian>(&mut output);
+            compare_slices(expected, &output);
+            assert_eq!(value.serialized_size(), expected.len());
+        }
+
+        assert_eq!(Tlv::min_nonzero_elements_size(), 2);
+        assert_eq!(Tlv::max_default_object_size(), 6);
+    }
+
+    #[test]
+    fn test_mutation_can_desynchronize_enum_tags() {
+        let mut mutator = get_mutator();
+        let mut value = TagOverride::<Tlv>::new_fuzzed(&mut mutator, None);
+        let mut plain = Tlv::new_fuzzed(&mut mutator, None);
+
+        let serialize = |value: &TagOverride<Tlv>| {
+            let mut output = vec![];
+            value.binary_serialize::<_, LittleEndian>(&mut output);
+            output
+        };
+
+        let mut saw_mismatch = false;
+        for _ in 0..500 {
+            value.mutate(&mut mutator, None);
+
+            let output = serialize(&value);
+            assert_eq!(output.len(), value.serialized_size());
+
+            // the tag is part of the object, so copies serialize identically
+            compare_slices(&output, &serialize(&value.clone()));
+            let json = serde_json::to_string(&value).unwrap();
+            compare_slices(&output, &serialize(&serde_json::from_str(&json).unwrap()));
+
+            let tag = u16::from_be_bytes([output[0], output[1]]);
+            if value.is_exact() {
+                assert_eq!(tag, value.expected_tag());
+            }
+            saw_mismatch |= tag != value.expected_tag();
+
+            // without the wrapper tags always match
+            plain.mutate(&mut mutator, None);
+            let mut output = vec![];
+            plain.binary_serialize::<_, LittleEndian>(&mut output);
+            assert_eq!(
+                u16::from_be_bytes([output[0], output[1]]),
+                plain.expected_tag()
+            );
+        }
+
+        assert!(saw_mismatch);
+        assert_eq!(Tlv::tags(), &[0x10, 0x20, 2, 0xff]);
+    }
+
+    #[derive(Debug, Clone, NewFuzzed, Mutatable, BinarySerialize)]
+    struct Frame<P> {
+        header: u16,
+        payload: P,
+        trailer: Vec<P>,
+    }
+
+    #[derive(Debug, Clone, NewFuzzed, Mutatable, BinarySerialize)]
+    enum Either<L, R: Clone> {
+        Left(L),
+        Right(R, u8),
+    }
+
+    #[derive(BinarySerialize)]
+    struct Borrowed<'a> {
+        id: u8,
+        #[lain(prefix = "u8")]
+        name: &'a str,
+    }
+
+    #[test]
+    fn test_generic_containers() {
+        let mut mutator = get_mutator();
+
+        let mut frame = Frame::<u32>::new_fuzzed(&mut mutator, None);
+        let mut either = Either::<u16, [u8; 3]>::new_fuzzed(&mut mutator, None);
+
+        for _ in 0..100 {
+            frame.mutate(&mut mutator, None);
+            either.mutate(&mut mutator, None);
+
+            let mut output = vec![];
+            frame.binary_serialize::<_, LittleEndian>(&mut output);
+            assert_eq!(output.len(), frame.serialized_size());
+            assert_eq!(output.len(), 6 + frame.trailer.len() * 4);
+
+            let mut output = vec![];
+            either.binary_serialize::<_, LittleEndian>(&mut output);
+            assert_eq!(output.len(), either.serialized_size());
+        }
+
+        let frame = Frame {
+            header: 0x0102,
+            payload: 3u8,
+            trailer: vec![4u8, 5],
+        };
+
+        let mut output = vec![];
+        frame.binary_serialize::<_, BigEndian>(&mut output);
+        compare_slices(&[0x01, 0x02, 0x03, 0x04, 0x05], &output);
+
+        let nested = Frame {
+            header: 0x0102,
+            payload: frame,
+            trailer: vec![],
+        };
+
+        let mut output = vec![];
+        nested.binary_serialize::<_, BigEndian>(&mut output);
+        compare_slices(&[0x01, 0x02, 0x01, 0x02, 0x03, 0x04, 0x05], &output);
+        assert_eq!(nested.serialized_size(), output.len());
+    }
+
+    #[test]
+    fn test_borrowed_fields_serialization() {
+        let name = String::from("lain");
+        let value = Borrowed { id: 7, name: &name };
+
+        let mut output = vec![];
+        value.binary_serialize::<_, LittleEndian>(&mut output);
+        compare_slices(&[0x07, 0x04, b'l', b'a', b'i', b'n'], &output);
+        assert_eq!(value.serialized_size(), output.len());
+    }
+
+    #[derive(Debug, Clone, PartialEq, FuzzerObject, BinarySerialize)]
+    struct Handle(u32);
+
+    #[derive(Debug, Clone, NewFuzzed, Mutatable, VariableSizeObject, BinarySerialize)]
+    struct Header(
+        #[lain(bits = 3)] u8,
+        #[lain(bits = 5)] u8,
+        #[lain(big_endian)] u16,
+        Handle,
+        Vec<u8>,
+    );
+
+    #[derive(Debug, Clone, PartialEq, FuzzerObject, BinarySerialize)]
+    struct Marker;
+
+    #[derive(Debug, Clone, NewFuzzed, Mutatable, BinarySerialize)]
+    enum Message {
+        Open { handle: Handle, flags: u8 },
+        Close(Handle),
+        Ping(Marker),
+    }
+
+    #[test]
+    fn test_tuple_struct_serialization() {
+        let header = Header(0b101, 0b10011, 0x0102, Handle(0x03040506), vec![7, 8]);
+
+        let mut output = vec![];
+        header.binary_serialize::<_, LittleEndian>(&mut output);
+        compare_slices(
+            &[0b1001_1101, 0x01, 0x02, 0x06, 0x05, 0x04, 0x03, 0x07, 0x08],
+            &output,
+        );
+        assert_eq!(header.serialized_size(), output.len());
+
+        let mut output = vec![];
+        Marker.binary_serialize::<_, LittleEndian>(&mut output);
+        assert!(output.is_empty());
+        assert_eq!(Marker.serialized_size(), 0);
+
+        let message = Message::Open {
+            handle: Handle(1),
+            flags: 2,
+        };
+        let mut output = vec![];
+        message.binary_serialize::<_, LittleEndian>(&mut output);
+        compare_slices(&[0x01, 0x00, 0x00, 0x00, 0x02], &output);
+
+        assert!(!Handle::is_variable_size());
+        assert!(!Marker::is_variable_size());
+        assert!(Header::is_variable_size());
+    }
+
+    #[test]
+    fn test_tuple_struct_mutation() {
+        let mut mutator = get_mutator();
+
+        let mut header = Header::new_fuzzed(&mut mutator, None);
+        let mut marker = Marker::new_fuzzed(&mut mutator, None);
+        let mut message = Message::new_fuzzed(&mut mutator, None);
+        let mut handles = vec![];
+
+        for _ in 0..100 {
+            header.mutate(&mut mutator, None);
+            marker.mutate(&mut mutator, None);
+            message.mutate(&mut mutator, None);
+
+            let mut output = vec![];
+            header.binary_serialize::<_, LittleEndian>(&mut output);
+            assert_eq!(output.len(), header.serialized_size());
+
+            let mut output = vec![];
+            message.binary_serialize::<_, LittleEndian>(&mut output);
+            assert_eq!(output.len(), message.serialized_size());
+
+            handles.push(header.3.clone());
+        }
+
+        assert_eq!(marker, Marker);
+        assert!(handles.iter().any(|handle| *handle != handles[0]));
+    }
+
+    #[derive(Debug, Copy, Clone, PartialEq, NewFuzzed, BinarySerialize, ToPrimitiveU8)]
+    enum Opcode {
+        Read = 1,
+        Write = 5,
+        Close = 10,
+    }
+
+    #[test]
+    fn test_unsafe_enum_from_primitive() {
+        assert_eq!(Opcode::primitive_values(), &[1, 5, 10]);
+        assert_eq!(Opcode::from_primitive(5), Some(Opcode::Write));
+        assert_eq!(Opcode::from_primitive(4), None);
+
+        match UnsafeEnum::<Opcode, u8>::from_primitive(10) {
+            UnsafeEnum::Valid(Opcode::Close) => {}
+            other => panic!("expected Close, got {:?}", other),
+        }
+
+        let mut value = UnsafeEnum::<Opcode, u8>::Invalid(1);
+        value.normalize();
+        assert!(value.is_valid());
+        assert_eq!(value.to_primitive(), 1);
+
+        let mut value = UnsafeEnum::<Opcode, u8>::Invalid(3);
+        value.normalize();
+        assert!(!value.is_valid());
+    }
+
+    #[test]
+    fn test_unsafe_enum_mutation_returns_to_valid_variants() {
+        let mut mutator = get_mutator();
+        let mut value = UnsafeEnum::<Opcode, u8>::Valid(Opcode::Read);
+
+        let mut seen_variants = vec![];
+        let mut went_invalid = false;
+        let mut came_back = false;
+
+        for _ in 0..1000 {
+            let was_valid = value.is_valid();
+            value.mutate(&mut mutator, None);
+
+            match value {
+                UnsafeEnum::Valid(variant) => {
+                    came_back |= went_invalid && !was_valid;
+                    if !seen_variants.contains(&variant) {
+                        seen_variants.push(variant);
+                    }
+                }
+                UnsafeEnum::Invalid(raw) => {
+                    went_invalid = true;
+                    assert_eq!(Opcode::from_primitive(raw), None);
+                }
+            }
+        }
+
+        assert_eq!(seen_variants.len(), 3);
+        assert!(went_invalid);
+        assert!(came_back);
+    }
+
+    #[derive(Debug, Copy, Clone, PartialEq, NewFuzzed, BinarySerialize, ToPrimitiveU8)]
+    enum Only {
+        One = 7,
+    }
+
+    #[test]
+    fn test_single_variant_unsafe_enum_goes_invalid() {
+        let mut mutator = get_mutator();
+
+        for _ in 0..100 {
+            let mut value = UnsafeEnum::<Only, u8>::Valid(Only::One);
+            value.mutate(&mut mutator, None);
+            assert!(!value.is_valid());
+        }
+    }
+
+    #[derive(Debug, Copy, Clone, PartialEq, NewFuzzed)]
+    enum RawOnly {
+        A = 1,
+        B = 2,
+    }
+
+    impl ToPrimitive for RawOnly {
+        type Output = u8;
+
+        fn to_primitive(&self) -> u8 {
+            *self as u8
+        }
+    }
+
+    #[test]
+    fn test_unsafe_enum_without_from_primitive() {
+        let mut mutator = get_mutator();
+        let mut value = UnsafeEnum::<RawOnly, u8>::new_fuzzed(&mut mutator, None);
+
+        // without FromPrimitive mutated values can't be mapped back to a variant
+        for _ in 0..100 {
+            value.mutate(&mut mutator, None);
+            assert!(!value.is_valid());
+        }
+    }
+
+    #[derive(
+        Debug,
+        Copy,
+        Clone,
+        PartialEq,
+        NewFuzzed,
+        BinarySerialize,
+        ToPrimitiveU8,
+        Serialize,
+        Deserialize,
+    )]
+    #[serde(crate = "lain::serde")]
+    enum RecordKind {
+        Text = 1,
+        Binary = 2,
+    }
+
+    #[derive(Debug, Clone, NewFuzzed, Mutatable, BinarySerialize, Serialize, Deserialize)]
+    #[serde(crate = "lain::serde")]
+    struct Record {
+        kind: UnsafeEnum<RecordKind, u8>,
+        #[lain(big_endian)]
+        id: u16,
+        #[lain(prefix = "u8")]
+        name: AsciiString,
+        #[lain(prefix = "u8")]
+        comment: Utf8String,
+        length: VarU32,
+    }
+
+    #[test]
+    fn test_serde_round_trip() {
+        let mut mutator = get_mutator();
+
+        for _ in 0..100 {
+            let mut record = Record::new_fuzzed(&mut mutator, None);
+            record.mutate(&mut mutator, None);
+
+            let json = serde_json::to_string(&record).unwrap();
+            let loaded: Record = serde_json::from_str(&json).unwrap();
+
+            let mut original = vec![];
+            record.binary_serialize::<_, LittleEndian>(&mut original);
+            let mut reloaded = vec![];
+            loaded.binary_serialize::<_, LittleEndian>(&mut reloaded);
+
+            compare_slices(&original, &reloaded);
+        }
+    }
+
+    #[test]
+    fn test_serde_dump_can_be_edited() {
+        let record = Record {
+            kind: UnsafeEnum::Invalid(7),
+            id: 0x0102,
+            name: AsciiString::new("ab"),
+            // malformed strings can only be loaded as raw bytes
+            comment: serde_json::from_str("[192,175]").unwrap(),
+            length: VarU32::new(300),
+        };
+
+        let json = serde_json::to_string(&record).unwrap();
+        assert_eq!(
+            json,
+            r#"{"kind":{"Invalid":7},"id":258,"name":"ab","comment":[192,175],"length":{"value":300,"overlong_bytes":0,"truncated":false}}"#
+        );
+
+        // a triager fixes up the dumped fields by hand and re-serializes the input
+        let edited = json
+            .replace(r#"{"Invalid":7}"#, r#"{"Valid":"Text"}"#)
+            .replace("[192,175]", r#""hi""#);
+        let edited: Record = serde_json::from_str(&edited).unwrap();
+
+        let mut output = vec![];
+        edited.binary_serialize::<_, LittleEndian>(&mut output);
+        compare_slices(
+            &[
+                0x01, 0x01, 0x02, 0x02, b'a', b'b', 0x02, b'h', b'i', 0xac, 0x02,
+            ],
+            &output,
+        );
+
+        let constraints: Constraints<u32> = serde_json::from_str(
+            r#"{"min":1,"max":10,"weighted":"Max","max_size":null,"base_object_size_accounted_for":false}"#,
+        )
+        .unwrap();
+        assert_eq!(constraints.max, Some(10));
+        assert_eq!(constraints.weighted, Weighted::Max);
+
+        assert!(serde_json::from_str::<AsciiString>(r#""caf\u00e9""#).is_err());
+    }
+
+    #[test]
+    fn test_hexdump_columns_are_aligned() {
+        let dump = hexdump(b"ABCDEFGHIJKLMNOP\x00\x7f~");
+        let lines: Vec<&str> = dump.lines().collect();
+
+        assert_eq!(
+            lines,
+            vec![
+                "      00 01 02 03 04 05 06 07 08 09 0A 0B 0C 0D 0E 0F",
+                "0000: 41 42 43 44 45 46 47 48 49 4A 4B 4C 4D 4E 4F 50  ABCDEFGHIJKLMNOP",
+                "0010: 00 7F 7E                                         ..~",
+            ]
+        );
+    }
+
+    fn dump_test_struct(uint32: u32) -> TestStruct {
+        TestStruct {
+            single_byte: 0x11,
+            bitfield_1: 1,
+            bitfield_2: 2,
+            bitfield_3: 0,
+            bitfield_4: 1,
+            bitfield_5: 5,
+            uint32,
+            short: 0x2233,
+            end_byte: 0x44,
+        }
+    }
+
+    #[test]
+    fn test_dump_records_field_layout() {
+        let frame = Frame {
+            header: 0xaabb,
+            payload: dump_test_struct(0x01020304),
+            trailer: vec![dump_test_struct(5), dump_test_struct(6)],
+        };
+
+        let dump = lain::dump::Dump::new::<_, BigEndian>(&frame);
+        let mut serialized = vec![];
+        frame.binary_serialize::<_, BigEndian>(&mut serialized);
+        assert_eq!(dump.bytes, serialized);
+
+        let range = |path: &str| dump.field(path).unwrap().range.clone();
+        assert_eq!(range("header"), 0..2);
+        assert_eq!(range("payload"), 2..11);
+        assert_eq!(range("payload.uint32"), 4..8);
+        assert_eq!(range("trailer"), 11..29);
+        assert_eq!(range("trailer[1]"), 20..29);
+        assert_eq!(range("trailer[1].end_byte"), 28..29);
+        assert_eq!(
+            dump.field_bytes(dump.field("payload.uint32").unwrap()),
+            &[1, 2, 3, 4]
+        );
+
+        let bitfield = dump.field("payload.bitfield_5").unwrap();
+        assert_eq!(bitfield.range, 3..4);
+        assert_eq!(
+            bitfield.bits,
+            Some(lain::dump::BitSpan {
+                offset: 5,
+                len: 3,
+                value: 5,
+            })
+        );
+
+        // parents are listed before their children
+        let paths: Vec<&str> = dump.fields.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(&paths[..3], &["header", "payload", "payload.single_byte"]);
+
+        let printed = dump.to_string();
+        assert!(printed.contains("payload.uint32"));
+        assert!(printed.contains("01 02 03 04"));
+
+        let stream = MsbBitStream {
+            forbidden_zero_bit: false,
+            nal_ref_idc: 3,
+            nal_unit_type: 5,
+            width: 0xabc,
+            level: 0x55,
+            marker: 0xff,
+            flags: 0b101,
+        };
+
+        let dump = lain::dump::Dump::new::<_, BigEndian>(&stream);
+        let level = dump.field("level").unwrap();
+        assert_eq!(level.range, 2..4);
+        assert_eq!(level.bits.as_ref().unwrap().offset, 4);
+        assert_eq!(level.bits.as_ref().unwrap().value, 0x55);
+        assert_eq!(dump.field("marker").unwrap().range, 4..5);
+        assert_eq!(dump.field("flags").unwrap().range, 5..6);
+
+        let either: Either<u8, u16> = Either::Right(0x0102, 3);
+        let dump = lain::dump::Dump::new::<_, BigEndian>(&either);
+        assert_eq!(dump.field("Right.0").unwrap().range, 0..2);
+        assert_eq!(dump.field("Right.1").unwrap().range, 2..3);
+    }
+
+    #[test]
+    fn test_dump_diff_shows_changed_fields() {
+        let mut changed = dump_test_struct(5);
+        changed.bitfield_2 = 3;
+        changed.short = 0x2234;
+
+        let old = lain::dump::Dump::new::<_, LittleEndian>(&vec![dump_test_struct(5)]);
+        let new = lain::dump::Dump::new::<_, LittleEndian>(&vec![changed, dump_test_struct(7)]);
+
+        let changed_fields = old.changed_fields(&new);
+        assert!(changed_fields.contains(&"[0].bitfield_2"));
+        assert!(changed_fields.contains(&"[0].short"));
+        assert!(changed_fields.contains(&"[1]"));
+        assert!(!changed_fields.contains(&"[0].uint32"));
+        assert!(!changed_fields.contains(&"[0].bitfield_1"));
+
+        let diff = old.diff(&new);
+        let line = |path: &str| {
+            diff.lines()
+                .find(|line| line[8..].starts_with(&format!("{} ", path)))
+                .unwrap()
+                .to_string()
+        };
+
+        assert!(line("[0].short").starts_with('*'));
+        assert!(line("[0].short").ends_with("33 22  ->  34 22"));
+        assert!(line("[0].uint32").starts_with(' '));
+        assert!(line("[1]").starts_with('+'));
+    }
+
+    struct PanicsWhileSerializing;
+
+    impl BinarySerialize for PanicsWhileSerializing {
+        fn binary_serialize<W: std::io::Write, E: lain::byteorder::ByteOrder>(
+            &self,
+            buffer: &mut W,
+        ) -> usize {
+            lain::dump::enter_field("first");
+            buffer.write_all(&[1]).unwrap();
+            panic!("failed to serialize");
+        }
+    }
+
+    #[test]
+    fn test_dump_stops_recording_after_panic() {
+        assert!(!lain::dump::is_recording());
+
+        let result = std::panic::catch_unwind(|| {
+            lain::dump::Dump::new::<_, BigEndian>(&PanicsWhileSerializing)
+        });
+        assert!(result.is_err());
+        assert!(!lain::dump::is_recording());
+
+        let dump = lain::dump::Dump::new::<_, BigEndian>(&dump_test_struct(5));
+        assert!(!lain::dump::is_recording());
+        assert!(dump.field("uint32").is_some());
+    }
+
+    #[test]
+    fn test_havoc_chance_is_respected() {
+        use lain::havoc::Havoc;
+
+        let mut mutator = get_mutator();
+        let object = dump_test_struct(0x01020304);
+        let mut expected = vec![];
+        object.binary_serialize::<_, BigEndian>(&mut expected);
+
+        let disabled = Havoc::new(0.0);
+        for _ in 0..100 {
+            let data = disabled.serialize::<_, BigEndian, _>(&object, &mut mutator);
+            compare_slices(&expected, &data);
+        }
+
+        let always = Havoc::new(1.0);
+        let mut mutated = 0;
+        for _ in 0..100 {
+            let data = always.serialize::<_, BigEndian, _>(&object, &mut mutator);
+            if data != expected {
+                mutated += 1;
+            }
+        }
+
+        // a swap of identical bytes or a dangerous number which was already there is a no-op
+        assert!(mutated > 80, "only {} of 100 inputs were mutated", mutated);
+    }
+
+    #[test]
+    fn test_havoc_operations() {
+        use lain::havoc::{Havoc, HavocOperation};
+
+        let mut mutator = get_mutator();
+        let havoc = Havoc::default();
+        let original: Vec<u8> = (0..64).collect();
+
+        for _ in 0..200 {
+            for operation in HavocOperation::ALL.iter().cloned() {
+                let mut data = original.clone();
+                havoc.apply(operation, &mut mutator, &mut data);
+
+                match operation {
+                    HavocOperation::Truncate => {
+                        assert!(data.len() < original.len());
+                        assert!(original.starts_with(&data));
+                    }
+                    HavocOperation::InsertRandomBytes | HavocOperation::DuplicateBlock => {
+                        assert!(data.len() > original.len());
+                        assert!(data.len() <= original.len() + havoc.max_block_size);
+                    }
+                    HavocOperation::SwapBlocks => {
+                        assert_ne!(data, original);
+                        let mut sorted = data.clone();
+                        sorted.sort();
+                        assert_eq!(sorted, original);
+                    }
+                    HavocOperation::OverwriteDangerousNumber => {
+                        assert_eq!(data.len(), original.len());
+                    }
+                }
+
+                // operations on tiny inputs must not go out of bounds
+                let mut data = vec![0x41];
+                havoc.apply(operation, &mut mutator, &mut data);
+                let mut data = vec![];
+                havoc.apply(operation, &mut mutator, &mut data);
+            }
+        }
+    }
+
+    fn count_picks<S: lain::scheduler::PowerSchedule + 'static>(
+        schedule: S,
+        setup: impl Fn(&lain::scheduler::Scheduler<Vec<u8>>),
+    ) -> Vec<usize> {
+        let mut mutator = get_mutator();
+        let scheduler = lain::scheduler::Scheduler::new(schedule);
+        setup(&scheduler);
+
+        let mut picks = vec![0; scheduler.len()];
+        for _ in 0..1000 {
+            picks[scheduler.next(&mut mutator).unwrap().id] += 1;
+        }
+
+        picks
+    }
+
+    #[test]
+    fn test_power_schedules_favour_the_right_entries() {
+        use lain::scheduler::{Productive, Rare, Recent, SmallFast, Uniform};
+        use std::time::Duration;
+
+        let fast = Duration::from_micros(10);
+        let slow = Duration::from_millis(10);
+
+        let picks = count_picks(Uniform, |scheduler| {
+            scheduler.add(vec![0; 4], fast, &[1]);
+            scheduler.add(vec![0; 4], slow, &[2]);
+        });
+        assert!(picks[0] > 400 && picks[1] > 400, "{:?}", picks);
+
+        let picks = count_picks(SmallFast, |scheduler| {
+            scheduler.add(vec![0; 100], slow, &[1]);
+            scheduler.add(vec![0; 4], fast, &[2]);
+        });
+        assert!(picks[1] > picks[0] * 4, "{:?}", picks);
+
+        let picks = count_picks(Recent::default(), |scheduler| {
+            for i in 0..64 {
+                scheduler.add(vec![i], fast, &[i as usize]);
+            }
+        });
+        assert!(picks[63] > picks[0] * 4, "{:?}", picks);
+
+        let picks = count_picks(Rare, |scheduler| {
+            scheduler.add(vec![0], fast, &[1]);
+            scheduler.add(vec![1], fast, &[1, 2]);
+            scheduler.add(vec![2], fast, &[3]);
+            for _ in 0..50 {
+                scheduler.record_execution(0, &[1, 2]);
+            }
+        });
+        assert!(picks[2] > picks[0] * 4, "{:?}", picks);
+        assert!(picks[2] > picks[1] * 4, "{:?}", picks);
+
+        let picks = count_picks(Productive, |scheduler| {
+            scheduler.add(vec![0], fast, &[1]);
+            scheduler.add(vec![1], fast, &[2]);
+            for _ in 0..10 {
+                scheduler.record_find(1);
+            }
+        });
+        assert!(picks[1] > picks[0], "{:?}", picks);
+    }
+
+    #[test]
+    fn test_scheduled_input_grows_corpus() {
+        use lain::scheduler::{Fast, ScheduledInput, Scheduler};
+        use std::time::Duration;
+
+        let mut mutator = get_mutator();
+        let scheduler = Scheduler::new(Fast);
+        scheduler.add(dump_test_struct(0), Duration::from_micros(10), &[0]);
+
+        // pretend that every distinct value of `single_byte` takes a different path
+        let coverage = |input: &TestStruct| vec![0, input.single_byte as usize + 1];
+
+        let mut scheduled = ScheduledInput::default();
+        let mut finds = 0;
+        for _ in 0..500 {
+            let input = scheduled.next(&scheduler, &mut mutator).unwrap();
+            input.single_byte = mutator.gen();
+            let hit = coverage(input);
+
+            if let Some(id) = scheduled.report(&scheduler, Duration::from_micros(10), &hit) {
+                assert_eq!(scheduler.entry(id).unwrap().coverage, hit);
+                finds += 1;
+            }
+        }
+
+        let metadata = scheduler.metadata();
+        assert_eq!(scheduler.len(), finds + 1);
+        assert_eq!(scheduler.total_execs(), 501);
+        assert_eq!(metadata.iter().map(|e| e.exec_count).sum::<u64>(), 500);
+        assert_eq!(metadata.iter().map(|e| e.finds).sum::<u64>(), finds as u64);
+        assert!(finds > 50);
+
+        // metadata can be restored after the inputs have been re-added
+        let restored = Scheduler::new(Fast);
+        for id in 0..scheduler.len() {
+            let entry = scheduler.entry(id).unwrap();
+            restored.add(
+                scheduler.input(id).unwrap(),
+                entry.exec_time,
+                &entry.coverage,
+            );
+        }
+        restored.restore_metadata(&metadata);
+        assert_eq!(restored.metadata(), metadata);
+    }
+
+    fn sync_root(name: &str) -> std::path::PathBuf {
+        let root = std::env::temp_dir().join(format!("lain-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+
+        root
+    }
+
+    #[test]
+    fn test_sync_dir_exchanges_entries_and_crashes() {
+        use lain::corpus_sync::{Origin, SyncDir, SyncKind};
+        use std::time::Duration;
+
+        let root = sync_root("sync-exchange");
+        let mut main = SyncDir::new(&root, "main").unwrap();
+        let mut secondary = SyncDir::new(&root, "secondary").unwrap();
+
+        main.export_entry(b"seed", &Origin::seed()).unwrap();
+        main.export_entry(b"mutated", &Origin::mutation(Some(0), "havoc"))
+            .unwrap();
+        let crash = main
+            .export_crash(b"boom", &Origin::crash(Some(1), Some(11)))
+            .unwrap();
+        assert!(crash
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .starts_with("id:000000,sig:11,src:000001,time:"));
+
+        // an AFL instance sharing the directory
+        let afl_queue = root.join("afl-main").join("queue");
+        std::fs::create_dir_all(&afl_queue).unwrap();
+        std::fs::create_dir_all(root.join("afl-main").join(".state")).unwrap();
+        std::fs::write(
+            afl_queue.join("id:000004,src:000001,op:flip1,pos:0"),
+            b"afl",
+        )
+        .unwrap();
+        std::fs::write(afl_queue.join(".id:000005.tmp"), b"partial").unwrap();
+
+        let imported = secondary.import().unwrap();
+        let summary: Vec<(&str, SyncKind, Option<u64>, &[u8])> = imported
+            .iter()
+            .map(|input| {
+                (
+                    input.origin.instance.as_str(),
+                    input.kind,
+                    input.origin.id,
+                    input.data.as_slice(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("afl-main", SyncKind::Entry, Some(4), &b"afl"[..]),
+                ("main", SyncKind::Entry, Some(0), &b"seed"[..]),
+                ("main", SyncKind::Entry, Some(1), &b"mutated"[..]),
+                ("main", SyncKind::Crash, Some(0), &b"boom"[..]),
+            ]
+        );
+        assert_eq!(imported[0].origin.op.as_deref(), Some("flip1"));
+        assert_eq!(imported[2].origin.parent, Some(0));
+        assert_eq!(imported[3].origin.signal, Some(11));
+
+        // only new inputs are imported, and never an instance's own
+        assert!(secondary.import().unwrap().is_empty());
+        secondary
+            .export_entry(b"mutated", &Origin::synced(&imported[2].origin))
+            .unwrap();
+        main.export_entry(b"newer", &Origin::mutation(Some(1), "splice"))
+            .unwrap();
+
+        let imported = secondary.import().unwrap();
+        assert_eq!(imported.len(), 1);
+        assert_eq!(imported[0].data, b"newer");
+
+        let imported = main.import().unwrap();
+        let synced: Vec<_> = imported
+            .iter()
+            .filter(|input| input.origin.instance == "secondary")
+            .collect();
+        assert_eq!(synced.len(), 1);
+        assert_eq!(synced[0].origin.synced_from.as_deref(), Some("main"));
+        assert_eq!(synced[0].origin.parent, Some(1));
+
+        secondary.set_interval(Duration::from_secs(0));
+        main.export_entry(b"later", &Origin::seed()).unwrap();
+        assert_eq!(secondary.import_if_due().unwrap().len(), 1);
+        secondary.set_interval(Duration::from_secs(3600));
+        main.export_entry(b"even later", &Origin::seed()).unwrap();
+        assert!(secondary.import_if_due().unwrap().is_empty());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_sync_dir_resumes_ids() {
+        use lain::corpus_sync::{Origin, SyncDir, SyncKind};
+
+        let root = sync_root("sync-resume");
+        let mut sync = SyncDir::new(&root, "worker").unwrap();
+        sync.export_entry(b"a", &Origin::seed()).unwrap();
+        sync.export_entry(b"b", &Origin::seed()).unwrap();
+        drop(sync);
+
+        let mut sync = SyncDir::new(&root, "worker").unwrap();
+        let path = sync.export_entry(b"c", &Origin::seed()).unwrap();
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        assert!(name.starts_with("id:000002,op:seed,time:"), "{}", name);
+        assert_eq!(path.parent().unwrap(), sync.dir(SyncKind::Entry));
+
+        let origin = Origin::from_file_name("worker", &name);
+        assert_eq!(origin.id, Some(2));
+        assert_eq!(origin.to_file_name(), name);
+
+        assert!(SyncDir::new(&root, "../escape").is_err());
+        assert!(SyncDir::new(&root, "").is_err());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_differential_records_divergences() {
+        use lain::driver::Differential;
+        use lain::executor::CommandExecutor;
+
+        // the "port" mishandles inputs starting with 0xff
+        let mut differential = Differential::new(|a: &Vec<u8>, b: &Vec<u8>| a == b)
+            .target("legacy", |input| input.to_vec())
+            .target("port", |input| {
+                if input.first() == Some(&0xff) {
+                    input[1..].to_vec()
+                } else {
+                    input.to_vec()
+                }
+            })
+            .command("cat", CommandExecutor::new("cat"), |result| {
+                result.unwrap().stdout
+            });
+
+        assert_eq!(differential.run(b"hello"), Ok(()));
+        assert_eq!(differential.run_object::<_, BigEndian>(&0x1234u16), Ok(()));
+        assert_eq!(differential.run(b"\xffabc"), Err(()));
+        assert_eq!(differential.run(b"\xffabc"), Err(()));
+
+        let divergences = differential.divergences();
+        let divergences = divergences.lock().unwrap();
+        assert_eq!(divergences.len(), 1);
+
+        let divergence = &divergences[0];
+        assert_eq!(divergence.input, b"\xffabc");
+        assert_eq!(divergence.mismatch, (0, 1));
+        assert_eq!(divergence.hits, 2);
+        assert_eq!(
+            divergence.left(),
+            &("legacy".to_string(), b"\xffabc".to_vec())
+        );
+        assert_eq!(divergence.right(), &("port".to_string(), b"abc".to_vec()));
+        assert_eq!(divergence.outputs[2].1, b"\xffabc");
+    }
+
+    #[test]
+    fn test_differential_dedups_and_caps_divergences() {
+        use lain::driver::Differential;
+
+        let differential = || {
+            Differential::new(|a: &Vec<u8>, b: &Vec<u8>| a == b)
+                .target("legacy", |input| input.to_vec())
+                .target("port", |input| input[1..].to_vec())
+        };
+
+        // every input diverges with different outputs, so only the cap limits the list
+        let mut capped = differential().with_max_divergences(3);
+        for i in 0..10u8 {
+            assert_eq!(capped.run(&[i, i]), Err(()));
+        }
+        assert_eq!(capped.divergences().lock().unwrap().len(), 3);
+
+        // divergences with the same first byte are duplicates
+        let mut deduped = differential().dedup_by(|a, b| a.input[0] == b.input[0]);
+        for i in 0..10u8 {
+            assert_eq!(deduped.run(&[i % 2, i]), Err(()));
+        }
+
+        let divergences = deduped.divergences();
+        let divergences = divergences.lock().unwrap();
+        assert_eq!(divergences.len(), 2);
+        assert!(divergences.iter().all(|d| d.hits == 5));
+    }
+
+    #[test]
+    fn test_differential_driver_shares_divergences() {
+        use lain::driver::Differential;
+        use lain::rand::rngs::StdRng;
+        use std::sync::{Arc, Mutex};
+
+        let divergences = Arc::new(Mutex::new(vec![]));
+        let thread_divergences = divergences.clone();
+
+        let driver = lain::driver::FuzzerDriver::<()>::builder(2)
+            .seed(1)
+            .start_differential(move |_thread_index| {
+                let differential = Differential::new(|a: &bool, b: &bool| a == b)
+                    .target("even", |input| input[0] % 2 == 0)
+                    .target("not odd", |input| input[0] & 1 == 0 && input[0] != 0x80)
+                    .with_divergences(thread_divergences.clone());
+
+                let generate = |mutator: &mut Mutator<StdRng>, _global_context| {
+                    let mut input = vec![];
+                    u8::new_fuzzed(mutator, None).binary_serialize::<_, BigEndian>(&mut input);
+                    input
+                };
+
+                (differential, generate)
+            });
+
+        let one_milli = std::time::Duration::from_millis(1);
+        let start = std::time::Instant::now();
+        while divergences.lock().unwrap().is_empty() {
+            assert!(
+                start.elapsed() < std::time::Duration::from_secs(30),
+                "timed out waiting for a divergence"
+            );
+            std::thread::sleep(one_milli);
+        }
+
+        driver.signal_exit();
+        driver.join_threads();
+
+        let divergences = divergences.lock().unwrap();
+        assert!(divergences.iter().all(|d| d.input == [0x80]));
+        assert!(driver.num_failed_iterations() >= divergences.len());
     }
 
     #[test]
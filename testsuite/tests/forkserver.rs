@@ -0,0 +1,59 @@
+#![cfg(unix)]
+
+use lain::executor::{ExitKind, InputDelivery};
+use lain::forkserver::ForkserverExecutor;
+use std::time::Duration;
+
+const TARGET: &str = env!("CARGO_BIN_EXE_forkserver_target");
+const SIGABRT: i32 = 6;
+
+#[test]
+fn forkserver_classifies_exits() {
+    let mut executor = ForkserverExecutor::new(TARGET);
+    executor.timeout(Duration::from_millis(200));
+
+    let result = executor.execute(b"hello").unwrap();
+    assert_eq!(result.exit_kind, ExitKind::Ok);
+
+    let result = executor.execute(b"exit").unwrap();
+    assert_eq!(result.exit_kind, ExitKind::NonZeroExit(2));
+    assert_eq!(result.as_iteration_result(), Ok(()));
+
+    let result = executor.execute(b"crash").unwrap();
+    assert_eq!(result.exit_kind, ExitKind::Crash(SIGABRT));
+    assert_eq!(result.as_iteration_result(), Err(()));
+
+    let result = executor.execute(b"hang").unwrap();
+    assert_eq!(result.exit_kind, ExitKind::Timeout);
+    assert_eq!(result.as_iteration_result(), Err(()));
+
+    // the fork server should keep serving after a timeout
+    let result = executor.execute(b"hello").unwrap();
+    assert_eq!(result.exit_kind, ExitKind::Ok);
+    assert!(executor.is_running());
+}
+
+#[test]
+fn forkserver_reuses_target_process() {
+    let mut executor = ForkserverExecutor::new(TARGET);
+    executor.input_delivery(InputDelivery::File).arg("@@");
+
+    for i in 0..200 {
+        let input = if i % 2 == 0 {
+            &b"crash"[..]
+        } else {
+            &b"fine"[..]
+        };
+        let result = executor.execute(input).unwrap();
+
+        assert_eq!(result.exit_kind.is_crash(), i % 2 == 0);
+    }
+}
+
+#[test]
+fn forkserver_rejects_target_without_stub() {
+    let mut executor = ForkserverExecutor::new("true");
+
+    assert!(executor.execute(b"").is_err());
+    assert!(!executor.is_running());
+}